@@ -0,0 +1,765 @@
+use colored::Colorize;
+use std::collections::BTreeSet;
+
+use super::Backend;
+use super::rsfqlib_verilog::{ident, idents};
+use crate::circuit::Circuit;
+use crate::gate::Gate;
+use crate::netlist::Netlist;
+
+// ゲートごとに振る舞いモデルを出力する Verilog バックエンド
+// パルスは幅 PW の 1 として表し, posedge をイベントとして扱う
+pub struct BehavioralVerilog;
+
+//  (circuit, gate名, [ピン名 => 引数WireID], モデル名)
+macro_rules! gate_string {
+    ($c:ident, $name:ident, [$($pin:ident => $arg:ident),*], $model:expr) => {
+        format!("{} {} ({});", $model, ident($name),
+            vec![ $(format!(".{}({})", stringify!($pin), ident($c.wire_names.get($arg).unwrap())), )*].join(", ")
+        )
+    };
+}
+
+// 遅延 DELAY 後に幅 PW のパルスを q に出力する文
+fn pulse(q: &str, indent: &str) -> String {
+    return format!(
+        "{}{} <= #DELAY 1'b1;\n{}{} <= #(DELAY + PW) 1'b0;",
+        indent, q, indent, q
+    );
+}
+
+// モデル名に対応する振る舞い記述
+fn model(kind: &str) -> String {
+    let body = match kind {
+        "sfq_jtl" | "sfq_buff" => format!(
+            "module {} #(parameter DELAY = 5, PW = 2) (input a, output reg q);\n  \
+             initial q = 1'b0;\n  \
+             always @(posedge a) begin\n{}\n  end\nendmodule",
+            kind,
+            pulse("q", "    ")
+        ),
+        "sfq_split" => format!(
+            "module sfq_split #(parameter DELAY = 5, PW = 2) (input a, output reg q1, output reg q2);\n  \
+             initial begin q1 = 1'b0; q2 = 1'b0; end\n  \
+             always @(posedge a) begin\n{}\n{}\n  end\nendmodule",
+            pulse("q1", "    "),
+            pulse("q2", "    ")
+        ),
+        "sfq_merge" => format!(
+            "module sfq_merge #(parameter DELAY = 7, PW = 2) (input a, input b, output reg q);\n  \
+             initial q = 1'b0;\n  \
+             always @(posedge a or posedge b) begin\n{}\n  end\nendmodule",
+            pulse("q", "    ")
+        ),
+        "sfq_split3" => splitter(kind, 3),
+        "sfq_split4" => splitter(kind, 4),
+        "sfq_merge3" => merger(kind, &["a", "b", "c"]),
+        "sfq_merge4" => merger(kind, &["a", "b", "c", "d"]),
+        "sfq_and2" => clocked2(kind, "sa && sb"),
+        "sfq_or2" => clocked2(kind, "sa || sb"),
+        "sfq_xor" => clocked2(kind, "sa ^ sb"),
+        "sfq_xnor" => clocked2(kind, "!(sa ^ sb)"),
+        "sfq_not" => clocked1(kind, "!sa"),
+        "sfq_dff" => clocked1(kind, "sa"),
+        "sfq_ndro" => format!(
+            "module sfq_ndro #(parameter DELAY = 8, PW = 2) (input a, input b, input clk, output reg q);\n  \
+             reg s = 1'b0;\n  \
+             initial q = 1'b0;\n  \
+             always @(posedge a) s <= 1'b1;\n  \
+             always @(posedge b) s <= 1'b0;\n  \
+             always @(posedge clk) begin\n    if (s) begin\n{}\n    end\n  end\nendmodule",
+            pulse("q", "      ")
+        ),
+        // r のパルスで保持したデータを消す
+        "sfq_dffr" => format!(
+            "module sfq_dffr #(parameter DELAY = 8, PW = 2) (input a, input r, input clk, output reg q);\n  \
+             reg sa = 1'b0;\n  \
+             initial q = 1'b0;\n  \
+             always @(posedge a) sa <= 1'b1;\n  \
+             always @(posedge r) sa <= 1'b0;\n  \
+             always @(posedge clk) begin\n    if (sa) begin\n{}\n    end\n    sa <= 1'b0;\n  end\nendmodule",
+            pulse("q", "      ")
+        ),
+        // r を受けたサイクルのクロックでは出力しない
+        "sfq_dffr_sync" => format!(
+            "module sfq_dffr_sync #(parameter DELAY = 8, PW = 2) (input a, input r, input clk, output reg q);\n  \
+             reg sa = 1'b0, sr = 1'b0;\n  \
+             initial q = 1'b0;\n  \
+             always @(posedge a) sa <= 1'b1;\n  \
+             always @(posedge r) sr <= 1'b1;\n  \
+             always @(posedge clk) begin\n    if (sa && !sr) begin\n{}\n    end\n    sa <= 1'b0;\n    sr <= 1'b0;\n  end\nendmodule",
+            pulse("q", "      ")
+        ),
+        "sfq_dffc" => format!(
+            "module sfq_dffc #(parameter DELAY = 8, PW = 2) (input a, input clk, output reg q1, output reg q2);\n  \
+             reg sa = 1'b0;\n  \
+             initial begin q1 = 1'b0; q2 = 1'b0; end\n  \
+             always @(posedge a) sa <= 1'b1;\n  \
+             always @(posedge clk) begin\n    if (sa) begin\n{}\n    end else begin\n{}\n    end\n    sa <= 1'b0;\n  end\nendmodule",
+            pulse("q1", "      "),
+            pulse("q2", "      ")
+        ),
+        "sfq_ndroc" => format!(
+            "module sfq_ndroc #(parameter DELAY = 8, PW = 2) (input a, input b, input clk, output reg q1, output reg q2);\n  \
+             reg s = 1'b0;\n  \
+             initial begin q1 = 1'b0; q2 = 1'b0; end\n  \
+             always @(posedge a) s <= 1'b1;\n  \
+             always @(posedge b) s <= 1'b0;\n  \
+             always @(posedge clk) begin\n    if (s) begin\n{}\n    end else begin\n{}\n    end\n  end\nendmodule",
+            pulse("q1", "      "),
+            pulse("q2", "      ")
+        ),
+        // 両方の入力が届いたら次の到着に備えて状態を戻す
+        "sfq_first_arrival" => arrival(kind, Some("!sa && !sb"), Some("!sa && !sb")),
+        "sfq_last_arrival" => arrival(kind, Some("sb"), Some("sa")),
+        "sfq_inhibit" => arrival(kind, Some("!sb"), None),
+        // パルスごとに状態を反転し, 1 から 0 に戻るときに出力する
+        "sfq_tff" => format!(
+            "module sfq_tff #(parameter DELAY = 6, PW = 2) (input a, output reg q);\n  \
+             reg s = 1'b0;\n  \
+             initial q = 1'b0;\n  \
+             always @(posedge a) begin\n    if (s) begin\n{}\n    end\n    s <= !s;\n  end\nendmodule",
+            pulse("q", "      ")
+        ),
+        // 0 から 1 になるときに q1, 1 から 0 に戻るときに q2 に出力する
+        "sfq_tff2" => format!(
+            "module sfq_tff2 #(parameter DELAY = 6, PW = 2) (input a, output reg q1, output reg q2);\n  \
+             reg s = 1'b0;\n  \
+             initial begin q1 = 1'b0; q2 = 1'b0; end\n  \
+             always @(posedge a) begin\n    if (s) begin\n{}\n    end else begin\n{}\n    end\n    s <= !s;\n  end\nendmodule",
+            pulse("q2", "      "),
+            pulse("q1", "      ")
+        ),
+        // r のパルスで出力せずに 0 に戻す
+        "sfq_tffr" => format!(
+            "module sfq_tffr #(parameter DELAY = 6, PW = 2) (input a, input r, output reg q);\n  \
+             reg s = 1'b0;\n  \
+             initial q = 1'b0;\n  \
+             always @(posedge a) begin\n    if (s) begin\n{}\n    end\n    s <= !s;\n  end\n  \
+             always @(posedge r) s <= 1'b0;\nendmodule",
+            pulse("q", "      ")
+        ),
+        // DC レベルの立ち上がりごとにパルスを出す
+        "sfq_dcsfq" => format!(
+            "module sfq_dcsfq #(parameter DELAY = 5, PW = 2) (input a, output reg q);\n  \
+             initial q = 1'b0;\n  \
+             always @(posedge a) begin\n{}\n  end\nendmodule",
+            pulse("q", "    ")
+        ),
+        // パルスごとに出力の DC レベルを反転する
+        "sfq_sfqdc" => "module sfq_sfqdc #(parameter DELAY = 5) (input a, output reg q);\n  \
+                        initial q = 1'b0;\n  \
+                        always @(posedge a) q <= #DELAY !q;\nendmodule"
+            .to_string(),
+        "sfq_zero_async" => {
+            "module sfq_zero_async (output q);\n  assign q = 1'b0;\nendmodule".to_string()
+        }
+        "sfq_zero_sync" => {
+            "module sfq_zero_sync (input clk, output q);\n  assign q = 1'b0;\nendmodule".to_string()
+        }
+        // クロックごとにパルスを出す
+        "sfq_one_sync" => format!(
+            "module sfq_one_sync #(parameter DELAY = 8, PW = 2) (input clk, output reg q);\n  \
+             initial q = 1'b0;\n  \
+             always @(posedge clk) begin\n{}\n  end\nendmodule",
+            pulse("q", "    ")
+        ),
+        _ => unreachable!(),
+    };
+    return body;
+}
+
+// n 出力の SPLIT: q1, ..., qn
+fn splitter(kind: &str, n: usize) -> String {
+    let qs: Vec<String> = (1..=n).map(|i| format!("q{}", i)).collect();
+    let ports: Vec<String> = qs.iter().map(|q| format!("output reg {}", q)).collect();
+    let inits: Vec<String> = qs.iter().map(|q| format!("{} = 1'b0;", q)).collect();
+    let pulses: Vec<String> = qs.iter().map(|q| pulse(q, "    ")).collect();
+    return format!(
+        "module {} #(parameter DELAY = 5, PW = 2) (input a, {});\n  \
+         initial begin {} end\n  \
+         always @(posedge a) begin\n{}\n  end\nendmodule",
+        kind,
+        ports.join(", "),
+        inits.join(" "),
+        pulses.join("\n")
+    );
+}
+
+// 多入力の MERGE
+fn merger(kind: &str, inputs: &[&str]) -> String {
+    let ports: Vec<String> = inputs.iter().map(|a| format!("input {}", a)).collect();
+    let events: Vec<String> = inputs.iter().map(|a| format!("posedge {}", a)).collect();
+    return format!(
+        "module {} #(parameter DELAY = 7, PW = 2) ({}, output reg q);\n  \
+         initial q = 1'b0;\n  \
+         always @({}) begin\n{}\n  end\nendmodule",
+        kind,
+        ports.join(", "),
+        events.join(" or "),
+        pulse("q", "    ")
+    );
+}
+
+// 1 入力のクロック付きゲート: クロックで内部状態から出力し, 状態をリセット
+fn clocked1(kind: &str, cond: &str) -> String {
+    return format!(
+        "module {} #(parameter DELAY = 8, PW = 2) (input a, input clk, output reg q);\n  \
+         reg sa = 1'b0;\n  \
+         initial q = 1'b0;\n  \
+         always @(posedge a) sa <= 1'b1;\n  \
+         always @(posedge clk) begin\n    if ({}) begin\n{}\n    end\n    sa <= 1'b0;\n  end\nendmodule",
+        kind,
+        cond,
+        pulse("q", "      ")
+    );
+}
+
+// 2 入力のクロック付きゲート
+fn clocked2(kind: &str, cond: &str) -> String {
+    return format!(
+        "module {} #(parameter DELAY = 9, PW = 2) (input a, input b, input clk, output reg q);\n  \
+         reg sa = 1'b0, sb = 1'b0;\n  \
+         initial q = 1'b0;\n  \
+         always @(posedge a) sa <= 1'b1;\n  \
+         always @(posedge b) sb <= 1'b1;\n  \
+         always @(posedge clk) begin\n    if ({}) begin\n{}\n    end\n    sa <= 1'b0;\n    sb <= 1'b0;\n  end\nendmodule",
+        kind,
+        cond,
+        pulse("q", "      ")
+    );
+}
+
+// クロックのない到着時刻のゲート. cond_a, cond_b は各入力のパルスで出力する条件
+fn arrival(kind: &str, cond_a: Option<&str>, cond_b: Option<&str>) -> String {
+    let fire = |cond: Option<&str>| match cond {
+        Some(cond) => format!(
+            "    if ({}) begin\n{}\n    end\n",
+            cond,
+            pulse("q", "      ")
+        ),
+        None => String::new(),
+    };
+    return format!(
+        "module {} #(parameter DELAY = 7, PW = 2) (input a, input b, output reg q);\n  \
+         reg sa = 1'b0, sb = 1'b0;\n  \
+         initial q = 1'b0;\n  \
+         always @(posedge a) begin\n{}    \
+         if (sb) begin sa <= 1'b0; sb <= 1'b0; end else sa <= 1'b1;\n  end\n  \
+         always @(posedge b) begin\n{}    \
+         if (sa) begin sa <= 1'b0; sb <= 1'b0; end else sb <= 1'b1;\n  end\nendmodule",
+        kind,
+        fire(cond_a),
+        fire(cond_b)
+    );
+}
+
+// ゲートに対応するモデル名 (インスタンスを作らないものは None)
+fn model_name(gate: &Gate) -> Option<&'static str> {
+    return match gate {
+        Gate::Jtl { .. } => Some("sfq_jtl"),
+        Gate::Split { .. } => Some("sfq_split"),
+        Gate::Merge { .. } => Some("sfq_merge"),
+        Gate::Split3 { .. } => Some("sfq_split3"),
+        Gate::Split4 { .. } => Some("sfq_split4"),
+        Gate::Merge3 { .. } => Some("sfq_merge3"),
+        Gate::Merge4 { .. } => Some("sfq_merge4"),
+        Gate::And { .. } => Some("sfq_and2"),
+        Gate::Or { .. } => Some("sfq_or2"),
+        Gate::Xor { .. } => Some("sfq_xor"),
+        Gate::Xnor { .. } => Some("sfq_xnor"),
+        Gate::Not { .. } => Some("sfq_not"),
+        Gate::Dff { .. } => Some("sfq_dff"),
+        Gate::Ndro { .. } => Some("sfq_ndro"),
+        Gate::DffR { .. } => Some("sfq_dffr"),
+        Gate::DffRSync { .. } => Some("sfq_dffr_sync"),
+        Gate::DffC { .. } => Some("sfq_dffc"),
+        Gate::NdroC { .. } => Some("sfq_ndroc"),
+        Gate::Buff { .. } => Some("sfq_buff"),
+        Gate::FirstArrival { .. } => Some("sfq_first_arrival"),
+        Gate::LastArrival { .. } => Some("sfq_last_arrival"),
+        Gate::Inhibit { .. } => Some("sfq_inhibit"),
+        Gate::Tff { .. } => Some("sfq_tff"),
+        Gate::Tff2 { .. } => Some("sfq_tff2"),
+        Gate::TffR { .. } => Some("sfq_tffr"),
+        Gate::DcSfq { .. } => Some("sfq_dcsfq"),
+        Gate::SfqDc { .. } => Some("sfq_sfqdc"),
+        Gate::ZeroAsync { .. } => Some("sfq_zero_async"),
+        Gate::ZeroSync { .. } => Some("sfq_zero_sync"),
+        Gate::OneSync { .. } => Some("sfq_one_sync"),
+        _ => None,
+    };
+}
+
+impl Backend for BehavioralVerilog {
+    fn generate_netlist(c: &Netlist) -> String {
+        return Self::generate_design(&[c]);
+    }
+
+    // モデルは全体で 1 度だけ出力する
+    fn generate_design(netlists: &[&Netlist]) -> String {
+        return Self::try_generate_design(netlists).unwrap_or_else(|e| panic!("{}", e.red()));
+    }
+}
+
+impl BehavioralVerilog {
+    // 振る舞いモデルのないゲートがあればパニックせずにエラーを返す
+    pub fn try_generate<
+        const N_I: usize,
+        const N_CI: usize,
+        const N_O: usize,
+        const N_CO: usize,
+    >(
+        circuit: &Circuit<N_I, N_CI, N_O, N_CO>,
+    ) -> Result<String, String> {
+        return Self::try_generate_design(&[&circuit.netlist]);
+    }
+
+    pub fn try_generate_design(netlists: &[&Netlist]) -> Result<String, String> {
+        let mut res = Vec::new();
+
+        /* ------------------- models ------------------- */
+        res.push("`timescale 1ps / 1fs".to_string());
+        let models: BTreeSet<&str> = netlists
+            .iter()
+            .flat_map(|c| c.gates.iter())
+            .filter_map(model_name)
+            .collect();
+        for kind in models {
+            res.push(model(kind));
+            res.push(String::new());
+        }
+
+        /* ------------------- modules ------------------- */
+        let modules: Vec<String> = netlists
+            .iter()
+            .map(|c| module(c))
+            .collect::<Result<_, _>>()?;
+        res.push(modules.join("\n\n"));
+
+        return Ok(res.join("\n"));
+    }
+}
+
+// 構造記述のモジュール
+fn module(c: &Netlist) -> Result<String, String> {
+    let mut res = Vec::new();
+
+    /* ------------------- header ------------------- */
+    let in_ports: Vec<&str> = c
+        .inputs
+        .iter()
+        .chain(c.counter_outputs.iter())
+        .map(|s| s.as_str())
+        .collect();
+    let out_ports: Vec<&str> = c
+        .outputs
+        .iter()
+        .chain(c.counter_inputs.iter())
+        .map(|s| s.as_str())
+        .collect();
+    let ports: Vec<&str> = [in_ports.clone(), out_ports.clone()].concat();
+    res.push(format!("module {} ({});", ident(&c.name), idents(&ports)));
+    if !in_ports.is_empty() {
+        res.push(format!("input {};", idents(&in_ports)));
+    }
+    if !out_ports.is_empty() {
+        res.push(format!("output {};", idents(&out_ports)));
+    }
+
+    let wires: Vec<&str> = c
+        .wire_names
+        .values()
+        .map(|s| s.as_str())
+        .filter(|s| !ports.contains(s)) // ポートのwireは除外
+        .collect::<BTreeSet<&str>>() // 重複削除, ソート
+        .into_iter()
+        .collect();
+    if !wires.is_empty() {
+        res.push(format!("wire {};", idents(&wires)));
+    }
+
+    /* ------------------- body ------------------- */
+    for gate in c.gates.iter() {
+        let s = match gate {
+            Gate::Jtl { name, a, q } => gate_string!(c, name, [a => a, q => q], "sfq_jtl"),
+            Gate::Split { name, a, q1, q2 } => {
+                gate_string!(c, name, [a => a, q1 => q1, q2 => q2], "sfq_split")
+            }
+            Gate::Merge { name, a, b, q } => {
+                gate_string!(c, name, [a => a, b => b, q => q], "sfq_merge")
+            }
+            Gate::Split3 {
+                name,
+                a,
+                q1,
+                q2,
+                q3,
+            } => {
+                gate_string!(c, name, [a => a, q1 => q1, q2 => q2, q3 => q3], "sfq_split3")
+            }
+            Gate::Split4 {
+                name,
+                a,
+                q1,
+                q2,
+                q3,
+                q4,
+            } => {
+                gate_string!(c, name, [a => a, q1 => q1, q2 => q2, q3 => q3, q4 => q4], "sfq_split4")
+            }
+            Gate::Merge3 {
+                name,
+                a,
+                b,
+                c: x,
+                q,
+            } => {
+                gate_string!(c, name, [a => a, b => b, c => x, q => q], "sfq_merge3")
+            }
+            Gate::Merge4 {
+                name,
+                a,
+                b,
+                c: x,
+                d,
+                q,
+            } => {
+                gate_string!(c, name, [a => a, b => b, c => x, d => d, q => q], "sfq_merge4")
+            }
+            Gate::And { name, a, b, clk, q } => {
+                gate_string!(c, name, [a => a, b => b, clk => clk, q => q], "sfq_and2")
+            }
+            Gate::Or { name, a, b, clk, q } => {
+                gate_string!(c, name, [a => a, b => b, clk => clk, q => q], "sfq_or2")
+            }
+            Gate::Xor { name, a, b, clk, q } => {
+                gate_string!(c, name, [a => a, b => b, clk => clk, q => q], "sfq_xor")
+            }
+            Gate::Xnor { name, a, b, clk, q } => {
+                gate_string!(c, name, [a => a, b => b, clk => clk, q => q], "sfq_xnor")
+            }
+            Gate::Not { name, a, clk, q } => {
+                gate_string!(c, name, [a => a, clk => clk, q => q], "sfq_not")
+            }
+            Gate::Dff { name, a, clk, q } => {
+                gate_string!(c, name, [a => a, clk => clk, q => q], "sfq_dff")
+            }
+            Gate::Ndro { name, a, b, clk, q } => {
+                gate_string!(c, name, [a => a, b => b, clk => clk, q => q], "sfq_ndro")
+            }
+            Gate::DffR { name, a, r, clk, q } => {
+                gate_string!(c, name, [a => a, r => r, clk => clk, q => q], "sfq_dffr")
+            }
+            Gate::DffRSync { name, a, r, clk, q } => {
+                gate_string!(c, name, [a => a, r => r, clk => clk, q => q], "sfq_dffr_sync")
+            }
+            Gate::DffC {
+                name,
+                a,
+                clk,
+                q1,
+                q2,
+            } => {
+                gate_string!(c, name, [a => a, clk => clk, q1 => q1, q2 => q2], "sfq_dffc")
+            }
+            Gate::NdroC {
+                name,
+                a,
+                b,
+                clk,
+                q1,
+                q2,
+            } => {
+                gate_string!(c, name, [a => a, b => b, clk => clk, q1 => q1, q2 => q2], "sfq_ndroc")
+            }
+            Gate::Buff { name, a, q } => gate_string!(c, name, [a => a, q => q], "sfq_buff"),
+            Gate::FirstArrival { name, a, b, q } => {
+                gate_string!(c, name, [a => a, b => b, q => q], "sfq_first_arrival")
+            }
+            Gate::LastArrival { name, a, b, q } => {
+                gate_string!(c, name, [a => a, b => b, q => q], "sfq_last_arrival")
+            }
+            Gate::Inhibit { name, a, b, q } => {
+                gate_string!(c, name, [a => a, b => b, q => q], "sfq_inhibit")
+            }
+            Gate::Tff { name, a, q } => gate_string!(c, name, [a => a, q => q], "sfq_tff"),
+            Gate::Tff2 { name, a, q1, q2 } => {
+                gate_string!(c, name, [a => a, q1 => q1, q2 => q2], "sfq_tff2")
+            }
+            Gate::TffR { name, a, r, q } => {
+                gate_string!(c, name, [a => a, r => r, q => q], "sfq_tffr")
+            }
+            Gate::DcSfq { name, a, q } => gate_string!(c, name, [a => a, q => q], "sfq_dcsfq"),
+            Gate::SfqDc { name, a, q } => gate_string!(c, name, [a => a, q => q], "sfq_sfqdc"),
+            Gate::ZeroAsync { name, q } => gate_string!(c, name, [q => q], "sfq_zero_async"),
+            Gate::ZeroSync { name, clk, q } => {
+                gate_string!(c, name, [clk => clk, q => q], "sfq_zero_sync")
+            }
+            Gate::OneSync { name, clk, q } => {
+                gate_string!(c, name, [clk => clk, q => q], "sfq_one_sync")
+            }
+            Gate::Terminate { name: _, a: _ } => continue,
+            Gate::Subcircuit {
+                name,
+                inputs,
+                outputs,
+                circuit,
+            } => {
+                let ports: Vec<&str> = inputs
+                    .iter()
+                    .chain(outputs.iter())
+                    .map(|wid| c.wire_names.get(wid).unwrap().as_str())
+                    .collect();
+                format!(
+                    "{} {} ({});",
+                    ident(&circuit.name),
+                    ident(name),
+                    idents(&ports)
+                )
+            }
+            _ => {
+                return Err(format!(
+                    "`{}` has a gate without a behavioral model!",
+                    c.name
+                ));
+            }
+        };
+        res.push(s);
+    }
+
+    /* ------------------- footer ------------------- */
+    res.push("endmodule".to_string());
+
+    return Ok(res.join("\n"));
+}
+
+impl BehavioralVerilog {
+    // Circuit をテストするテストベンチを生成する
+    pub fn testbench<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>(
+        circuit: &Circuit<N_I, N_CI, N_O, N_CO>,
+        tb: &VerilogTestbench,
+    ) -> String {
+        let c = &circuit.netlist;
+        let in_ports: Vec<&str> = c.input_ports().map(|s| s.as_str()).collect();
+        let out_ports: Vec<&str> = c.output_ports().map(|s| s.as_str()).collect();
+
+        let mut res = Vec::new();
+        res.push("`timescale 1ps / 1fs".to_string());
+        let tb_name = ident(&format!("{}_tb", c.name));
+        res.push(format!("module {};", tb_name));
+        for port in in_ports.iter() {
+            res.push(format!("reg {} = 1'b0;", ident(port)));
+        }
+        for port in out_ports.iter() {
+            res.push(format!("wire {};", ident(port)));
+        }
+        let connections: Vec<String> = in_ports
+            .iter()
+            .chain(out_ports.iter())
+            .map(|p| format!(".{}({})", ident(p), ident(p)))
+            .collect();
+        res.push(format!(
+            "{} dut ({});",
+            ident(&c.name),
+            connections.join(", ")
+        ));
+        res.push(String::new());
+
+        /* ------------------- stimuli ------------------- */
+        for (port, times) in tb.stimuli.iter() {
+            assert!(
+                in_ports.contains(&port.as_str()),
+                "{}",
+                format!("Stimulus port `{}` is not an input of `{}`!", port, c.name).red()
+            );
+            res.push("initial begin".to_string());
+            let mut now = 0;
+            for &t in times.iter() {
+                assert!(
+                    t >= now,
+                    "{}",
+                    format!(
+                        "Pulses for `{}` must be sorted and {} ps apart!",
+                        port, tb.pw
+                    )
+                    .red()
+                );
+                res.push(format!("  #{} {} = 1'b1;", t - now, ident(port)));
+                res.push(format!("  #{} {} = 1'b0;", tb.pw, ident(port)));
+                now = t + tb.pw;
+            }
+            res.push("end".to_string());
+        }
+
+        /* ------------------- monitor ------------------- */
+        // SFQDC の出力は DC レベルの変化を表示する
+        let dc_ports = c.dc_ports();
+        for port in out_ports.iter() {
+            if dc_ports.contains(port) {
+                res.push(format!(
+                    "always @({}) if ($time > 0) $display(\"%0t {} %b\", $time, {});",
+                    ident(port),
+                    port,
+                    ident(port)
+                ));
+            } else {
+                res.push(format!(
+                    "always @(posedge {}) $display(\"%0t {}\", $time);",
+                    ident(port),
+                    port
+                ));
+            }
+        }
+        res.push("initial begin".to_string());
+        if let Some(file) = &tb.dump {
+            res.push(format!("  $dumpfile(\"{}\");", file));
+            res.push(format!("  $dumpvars(0, {});", tb_name));
+        }
+        res.push(format!("  #{} $finish;", tb.stop));
+        res.push("end".to_string());
+        res.push("endmodule".to_string());
+
+        return res.join("\n");
+    }
+}
+
+// テストベンチの設定 (時間の単位は ps)
+#[derive(Debug, Clone)]
+pub struct VerilogTestbench {
+    stimuli: Vec<(String, Vec<u64>)>,
+    pw: u64,
+    stop: u64,
+    dump: Option<String>,
+}
+
+impl Default for VerilogTestbench {
+    fn default() -> Self {
+        Self {
+            stimuli: Vec::new(),
+            pw: 2,
+            stop: 1000,
+            dump: None,
+        }
+    }
+}
+
+impl VerilogTestbench {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    // 入力ポートに times (ps) でパルスを入れる
+    pub fn pulses(mut self, port: &str, times: &[u64]) -> Self {
+        self.stimuli.push((port.to_string(), times.to_vec()));
+        return self;
+    }
+
+    // 入力ポートに period (ps) ごとに cycles 回パルスを入れる
+    pub fn clock(self, port: &str, offset: u64, period: u64, cycles: u64) -> Self {
+        let times: Vec<u64> = (0..cycles).map(|i| offset + i * period).collect();
+        return self.pulses(port, &times);
+    }
+
+    pub fn stop(mut self, stop: u64) -> Self {
+        self.stop = stop;
+        return self;
+    }
+
+    // 波形を VCD ファイルに出力する
+    pub fn dump(mut self, file: &str) -> Self {
+        self.dump = Some(file.to_string());
+        return self;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BehavioralVerilog, VerilogTestbench};
+    use crate::backends::Backend;
+    use crate::circuit::Circuit;
+    use crate::gate::Gate;
+
+    fn dff() -> Circuit<2, 0, 1, 0> {
+        let (mut c, [a, clk], [], [q], []) = Circuit::create(["a", "clk"], [], ["q"], [], "top");
+        let x = c.dff(a, clk);
+        c.unify(x, q);
+        return c;
+    }
+
+    #[test]
+    fn snapshot() {
+        let expected = "\
+`timescale 1ps / 1fs
+module sfq_dff #(parameter DELAY = 8, PW = 2) (input a, input clk, output reg q);
+  reg sa = 1'b0;
+  initial q = 1'b0;
+  always @(posedge a) sa <= 1'b1;
+  always @(posedge clk) begin
+    if (sa) begin
+      q <= #DELAY 1'b1;
+      q <= #(DELAY + PW) 1'b0;
+    end
+    sa <= 1'b0;
+  end
+endmodule
+
+module top (a, clk, q);
+input a, clk;
+output q;
+sfq_dff XDFF1 (.a(a), .clk(clk), .q(q));
+endmodule";
+        assert_eq!(BehavioralVerilog::generate(&dff()), expected);
+    }
+
+    // パルスは前のパルスが終わってからの相対時間で書く
+    #[test]
+    fn testbench_timing() {
+        let tb = VerilogTestbench::new()
+            .pulses("a", &[10, 50])
+            .clock("clk", 30, 40, 2)
+            .stop(200);
+        let src = BehavioralVerilog::testbench(&dff(), &tb);
+        let expected = "\
+initial begin
+  #10 a = 1'b1;
+  #2 a = 1'b0;
+  #38 a = 1'b1;
+  #2 a = 1'b0;
+end
+initial begin
+  #30 clk = 1'b1;
+  #2 clk = 1'b0;
+  #38 clk = 1'b1;
+  #2 clk = 1'b0;
+end
+always @(posedge q) $display(\"%0t q\", $time);
+initial begin
+  #200 $finish;
+end
+endmodule";
+        assert!(src.ends_with(expected), "{}", src);
+        assert!(
+            src.contains("top dut (.a(a), .clk(clk), .q(q));"),
+            "{}",
+            src
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn overlapping_stimuli() {
+        let tb = VerilogTestbench::new().pulses("a", &[10, 11]);
+        BehavioralVerilog::testbench(&dff(), &tb);
+    }
+
+    #[test]
+    fn unsupported_gate() {
+        let mut c = dff();
+        c.netlist.gates.push(Gate::_Reserved);
+        assert_eq!(
+            BehavioralVerilog::try_generate(&c),
+            Err("`top` has a gate without a behavioral model!".to_string())
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+
+use super::Backend;
+use crate::circuit::Circuit;
+use crate::gate::Gate;
+use crate::netlist::Netlist;
+
+// Graphviz の DOT 形式で回路図を出力する
+// サブサーキットは箱として描画し, generate_expanded() ではクラスタとして展開する
+pub struct Dot;
+
+impl Backend for Dot {
+    fn generate_netlist(c: &Netlist) -> String {
+        return render(c, false);
+    }
+}
+
+impl Dot {
+    pub fn generate_expanded<
+        const N_I: usize,
+        const N_CI: usize,
+        const N_O: usize,
+        const N_CO: usize,
+    >(
+        circuit: &Circuit<N_I, N_CI, N_O, N_CO>,
+    ) -> String {
+        return render(&circuit.netlist, true);
+    }
+}
+
+fn render(c: &Netlist, expand: bool) -> String {
+    let mut res = Vec::new();
+
+    /* ------------------- header ------------------- */
+    res.push(format!("digraph \"{}\" {{", c.name));
+    res.push("  rankdir=LR;".to_string());
+    res.push("  node [fontname=\"Helvetica\"];".to_string());
+    res.push("  edge [fontname=\"Helvetica\", fontsize=10];".to_string());
+    for port in c.input_ports().chain(c.output_ports()) {
+        res.push(format!(
+            "  \"port/{}\" [shape=ellipse, style=bold, label=\"{}\"];",
+            port, port
+        ));
+    }
+
+    /* ------------------- body ------------------- */
+    render_body(c, "", expand, "  ", &mut res);
+
+    /* ------------------- footer ------------------- */
+    res.push("}".to_string());
+
+    return res.join("\n");
+}
+
+// ノード形状
+fn shape(gate: &Gate) -> &'static str {
+    return match gate {
+        Gate::Split { .. } | Gate::Split3 { .. } | Gate::Split4 { .. } => "triangle",
+        Gate::Merge { .. } | Gate::Merge3 { .. } | Gate::Merge4 { .. } => "invtriangle",
+        Gate::Jtl { .. } | Gate::Buff { .. } => "cds",
+        Gate::Dff { .. }
+        | Gate::Ndro { .. }
+        | Gate::DffR { .. }
+        | Gate::DffRSync { .. }
+        | Gate::DffC { .. }
+        | Gate::NdroC { .. } => "box, peripheries=2",
+        Gate::ZeroAsync { .. } | Gate::ZeroSync { .. } | Gate::OneSync { .. } => "circle",
+        Gate::Terminate { .. } => "octagon",
+        Gate::Subcircuit { .. } => "box3d",
+        _ => "box",
+    };
+}
+
+// prefix 以下に netlist のゲートと配線を出力する
+fn render_body(c: &Netlist, prefix: &str, expand: bool, indent: &str, res: &mut Vec<String>) {
+    /* ------------------- nodes ------------------- */
+    for gate in c.gates.iter() {
+        let id = format!("{}{}", prefix, gate.name());
+        match gate {
+            Gate::Subcircuit { circuit, .. } if expand => {
+                res.push(format!("{}subgraph \"cluster_{}\" {{", indent, id));
+                res.push(format!(
+                    "{}  label=\"{} ({})\";",
+                    indent,
+                    gate.kind(),
+                    gate.name()
+                ));
+                res.push(format!("{}  style=rounded;", indent));
+                for port in circuit.input_ports().chain(circuit.output_ports()) {
+                    res.push(format!(
+                        "{}  \"{}/port/{}\" [shape=plaintext, label=\"{}\"];",
+                        indent, id, port, port
+                    ));
+                }
+                let child_prefix = format!("{}/", id);
+                render_body(
+                    circuit,
+                    &child_prefix,
+                    expand,
+                    &format!("{}  ", indent),
+                    res,
+                );
+                res.push(format!("{}}}", indent));
+            }
+            _ => {
+                res.push(format!(
+                    "{}\"{}\" [shape={}, label=\"{}\\n{}\"];",
+                    indent,
+                    id,
+                    shape(gate),
+                    gate.kind(),
+                    gate.name()
+                ));
+            }
+        }
+    }
+
+    /* ------------------- edges ------------------- */
+    // 配線名 -> ドライバ (ノード, ピン)
+    let mut drivers: HashMap<&str, (String, &str)> = HashMap::new();
+    for port in c.input_ports() {
+        drivers.insert(port, (format!("{}port/{}", prefix, port), ""));
+    }
+    for gate in c.gates.iter() {
+        for (pin, wid) in gate.outputs() {
+            drivers.insert(c.wire_name(&wid), endpoint(gate, prefix, pin, expand));
+        }
+    }
+
+    // レシーバ (配線名, ノード, ピン, ピン名を表示するか)
+    let mut receivers: Vec<(&str, (String, &str), bool)> = Vec::new();
+    for gate in c.gates.iter() {
+        let inputs = gate.inputs();
+        let show_pin = inputs.len() > 1 && !(expand && matches!(gate, Gate::Subcircuit { .. }));
+        for (pin, wid) in inputs {
+            receivers.push((
+                c.wire_name(&wid),
+                endpoint(gate, prefix, pin, expand),
+                show_pin,
+            ));
+        }
+    }
+    for port in c.output_ports() {
+        receivers.push((port, (format!("{}port/{}", prefix, port), ""), false));
+    }
+
+    let clocks = c.clock_nets();
+    for (net, (node, pin), show_pin) in receivers {
+        let Some((driver, _)) = drivers.get(net) else {
+            continue;
+        };
+        let mut attrs = vec![format!("label=\"{}\"", net)];
+        if show_pin {
+            attrs.push(format!("headlabel=\"{}\"", pin));
+        }
+        if clocks.contains(net) {
+            attrs.push("style=dashed, color=blue, fontcolor=blue".to_string());
+        }
+        // CounterWire 由来の配線は赤の太線で描画する
+        if c.is_counter_net(net) {
+            attrs.push("penwidth=2, color=red, fontcolor=red".to_string());
+        }
+        res.push(format!(
+            "{}\"{}\" -> \"{}\" [{}];",
+            indent,
+            driver,
+            node,
+            attrs.join(", ")
+        ));
+    }
+}
+
+// ゲートのピンに対応するノード
+// 展開したサブサーキットではクラスタ内のポートノードになる
+fn endpoint<'a>(gate: &Gate, prefix: &str, pin: &'a str, expand: bool) -> (String, &'a str) {
+    return match gate {
+        Gate::Subcircuit { name, .. } if expand => {
+            (format!("{}{}/port/{}", prefix, name, pin), pin)
+        }
+        _ => (format!("{}{}", prefix, gate.name()), pin),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dot;
+    use crate::backends::Backend;
+    use crate::circuit::Circuit;
+    use crate::components::{Arithmetic, Clocking, Sequential};
+
+    #[test]
+    fn snapshot() {
+        let (mut c, [a, clk], [], [q], []) = Circuit::create(["a", "clk"], [], ["q"], [], "top");
+        let x = c.dff(a, clk);
+        c.unify(x, q);
+        let expected = r#"digraph "top" {
+  rankdir=LR;
+  node [fontname="Helvetica"];
+  edge [fontname="Helvetica", fontsize=10];
+  "port/a" [shape=ellipse, style=bold, label="a"];
+  "port/clk" [shape=ellipse, style=bold, label="clk"];
+  "port/q" [shape=ellipse, style=bold, label="q"];
+  "XDFF1" [shape=box, peripheries=2, label="DFF\nXDFF1"];
+  "port/a" -> "XDFF1" [label="a", headlabel="a"];
+  "port/clk" -> "XDFF1" [label="clk", headlabel="clk", style=dashed, color=blue, fontcolor=blue];
+  "XDFF1" -> "port/q" [label="q"];
+}"#;
+        assert_eq!(Dot::generate(&c), expected);
+    }
+
+    // サブサーキットは箱, generate_expanded() ではクラスタになる
+    #[test]
+    fn subcircuits() {
+        let adder: Circuit<3, 0, 2, 0> = Arithmetic::ripple_carry_adder();
+        let boxed = Dot::generate(&adder);
+        assert!(boxed.contains("shape=box3d"), "{}", boxed);
+        assert!(!boxed.contains("subgraph"), "{}", boxed);
+
+        let expanded = Dot::generate_expanded(&adder);
+        assert!(!expanded.contains("shape=box3d"), "{}", expanded);
+        assert!(expanded.contains("subgraph \"cluster_XHA"), "{}", expanded);
+        assert!(
+            expanded.contains("/port/a\" [shape=plaintext"),
+            "{}",
+            expanded
+        );
+    }
+
+    // CounterWire 由来の配線は赤で描く
+    #[test]
+    fn counter_nets() {
+        let concurrent = Dot::generate(&Sequential::shift_register(2, Clocking::Concurrent));
+        let counter_flow = Dot::generate(&Sequential::shift_register(2, Clocking::CounterFlow));
+        assert!(!concurrent.contains("color=red"), "{}", concurrent);
+        assert!(counter_flow.contains("color=red"), "{}", counter_flow);
+    }
+}
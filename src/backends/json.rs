@@ -0,0 +1,304 @@
+use serde_json::{Map, Value, json};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use super::Backend;
+use crate::circuit::Circuit;
+use crate::design::hierarchy;
+use crate::error::ParseError;
+use crate::gate::Gate;
+use crate::id::WireID;
+use crate::netlist::Netlist;
+
+// JSON 形式のネットリスト. スキーマは docs/src/json.md を参照
+pub struct Json;
+
+const FORMAT: &str = "rust_sfq";
+const VERSION: u64 = 1;
+
+impl Backend for Json {
+    fn generate_netlist(c: &Netlist) -> String {
+        // サブサーキットを先に並べる
+        let circuits = hierarchy(c);
+        let netlists: Vec<&Netlist> = circuits.iter().map(|n| n.as_ref()).collect();
+        return Self::generate_design(&netlists);
+    }
+
+    // 最後の回路をトップとする
+    fn generate_design(netlists: &[&Netlist]) -> String {
+        let doc = json!({
+            "format": FORMAT,
+            "version": VERSION,
+            "top": netlists.last().map(|n| n.name.as_str()),
+            "circuits": netlists.iter().map(|n| to_value(n)).collect::<Vec<Value>>(),
+        });
+        return serde_json::to_string_pretty(&doc).unwrap();
+    }
+}
+
+impl Json {
+    // JSON を読み込み, 検査済みの Circuit を返す
+    pub fn parse<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>(
+        src: &str,
+    ) -> Result<Circuit<N_I, N_CI, N_O, N_CO>, ParseError> {
+        let netlist = Self::parse_netlist(src)?;
+        return Circuit::from_netlist(netlist).map_err(ParseError::new);
+    }
+
+    // ポート数が分からない場合は Netlist として読み込む
+    pub fn parse_netlist(src: &str) -> Result<Netlist, ParseError> {
+        let doc: Value =
+            serde_json::from_str(src).map_err(|e| ParseError::at(e.line(), e.to_string()))?;
+
+        if doc["format"] != FORMAT || doc["version"] != VERSION {
+            return Err(ParseError::new(format!(
+                "Not a {} netlist of version {}!",
+                FORMAT, VERSION
+            )));
+        }
+        let top = doc["top"]
+            .as_str()
+            .ok_or_else(|| ParseError::new("`top` must be a string!"))?;
+        let mut defs: HashMap<&str, &Value> = HashMap::new();
+        for def in doc["circuits"]
+            .as_array()
+            .ok_or_else(|| ParseError::new("`circuits` must be an array!"))?
+        {
+            let name = def["name"]
+                .as_str()
+                .ok_or_else(|| ParseError::new("Circuit `name` must be a string!"))?;
+            if defs.insert(name, def).is_some() {
+                return Err(ParseError::new(format!(
+                    "Circuit `{}` is defined twice!",
+                    name
+                )));
+            }
+        }
+
+        let mut loaded = HashMap::new();
+        let netlist = load(top, &defs, &mut loaded, &mut Vec::new())?;
+        return Ok(Arc::unwrap_or_clone(netlist));
+    }
+}
+
+fn to_value(c: &Netlist) -> Value {
+    let gates: Vec<Value> = c
+        .gates
+        .iter()
+        .map(|gate| match gate {
+            Gate::Subcircuit {
+                name,
+                inputs,
+                outputs,
+                circuit,
+            } => json!({
+                "type": "SUBCIRCUIT",
+                "name": name,
+                "circuit": circuit.name,
+                "inputs": inputs.iter().map(|w| c.wire_name(w)).collect::<Vec<&str>>(),
+                "outputs": outputs.iter().map(|w| c.wire_name(w)).collect::<Vec<&str>>(),
+            }),
+            _ => {
+                let pins: Map<String, Value> = gate
+                    .inputs()
+                    .into_iter()
+                    .chain(gate.outputs())
+                    .map(|(pin, wid)| (pin.to_string(), json!(c.wire_name(&wid))))
+                    .collect();
+                json!({
+                    "type": gate.kind(),
+                    "name": gate.name(),
+                    "pins": pins,
+                })
+            }
+        })
+        .collect();
+
+    let mut counter_nets: Vec<&str> = c.counter_wires.iter().map(|w| c.wire_name(w)).collect();
+    counter_nets.sort();
+    counter_nets.dedup();
+
+    return json!({
+        "name": c.name,
+        "inputs": c.inputs,
+        "counter_inputs": c.counter_inputs,
+        "outputs": c.outputs,
+        "counter_outputs": c.counter_outputs,
+        "counter_nets": counter_nets,
+        "gates": gates,
+    });
+}
+
+// 名前付きの回路を (サブサーキットも含めて) 読み込む
+fn load(
+    name: &str,
+    defs: &HashMap<&str, &Value>,
+    loaded: &mut HashMap<String, Arc<Netlist>>,
+    stack: &mut Vec<String>,
+) -> Result<Arc<Netlist>, ParseError> {
+    if let Some(netlist) = loaded.get(name) {
+        return Ok(netlist.clone());
+    }
+    if stack.iter().any(|s| s == name) {
+        return Err(ParseError::new(format!(
+            "Circuit `{}` instantiates itself!",
+            name
+        )));
+    }
+    let def = defs
+        .get(name)
+        .ok_or_else(|| ParseError::new(format!("Circuit `{}` is not defined!", name)))?;
+    stack.push(name.to_string());
+
+    let err = |msg: String| ParseError::new(format!("In circuit `{}`: {}", name, msg));
+    let strings = |v: &Value, key: &str| -> Result<Vec<String>, ParseError> {
+        return v[key]
+            .as_array()
+            .and_then(|a| a.iter().map(|s| s.as_str().map(String::from)).collect())
+            .ok_or_else(|| err(format!("`{}` must be an array of strings!", key)));
+    };
+
+    let mut netlist = Netlist {
+        name: name.to_string(),
+        inputs: strings(def, "inputs")?,
+        counter_inputs: strings(def, "counter_inputs")?,
+        outputs: strings(def, "outputs")?,
+        counter_outputs: strings(def, "counter_outputs")?,
+        wire_names: HashMap::new(),
+        counter_wires: HashSet::new(),
+        gates: Vec::new(),
+    };
+
+    // 配線名ごとに 1 つの WireID を割り当てる
+    let mut ids: HashMap<String, WireID> = HashMap::new();
+    let mut wire = |net: &str, netlist: &mut Netlist| -> WireID {
+        if let Some(wid) = ids.get(net) {
+            return *wid;
+        }
+        let wid = WireID(ids.len() as u32 + 1);
+        ids.insert(net.to_string(), wid);
+        netlist.wire_names.insert(wid, net.to_string());
+        return wid;
+    };
+    let ports: Vec<String> = netlist
+        .input_ports()
+        .chain(netlist.output_ports())
+        .cloned()
+        .collect();
+    for port in ports.iter() {
+        wire(port, &mut netlist);
+    }
+
+    let gates = def["gates"]
+        .as_array()
+        .ok_or_else(|| err("`gates` must be an array!".to_string()))?;
+    for g in gates {
+        let kind = g["type"]
+            .as_str()
+            .ok_or_else(|| err("Gate `type` must be a string!".to_string()))?;
+        let gate_name = g["name"]
+            .as_str()
+            .ok_or_else(|| err("Gate `name` must be a string!".to_string()))?
+            .to_string();
+        let gate = if kind == "SUBCIRCUIT" {
+            let child = g["circuit"]
+                .as_str()
+                .ok_or_else(|| err(format!("`circuit` of `{}` must be a string!", gate_name)))?;
+            let circuit = load(child, defs, loaded, stack)?;
+            let inputs: Vec<WireID> = strings(g, "inputs")?
+                .iter()
+                .map(|net| wire(net, &mut netlist))
+                .collect();
+            let outputs: Vec<WireID> = strings(g, "outputs")?
+                .iter()
+                .map(|net| wire(net, &mut netlist))
+                .collect();
+            Gate::Subcircuit {
+                name: gate_name,
+                inputs,
+                outputs,
+                circuit,
+            }
+        } else {
+            let (inputs, outputs) = Gate::pin_names(kind)
+                .ok_or_else(|| err(format!("Unknown gate type `{}`!", kind)))?;
+            let mut pins = Vec::new();
+            for pin in inputs.iter().chain(outputs.iter()) {
+                let net = g["pins"][*pin]
+                    .as_str()
+                    .ok_or_else(|| err(format!("Pin `{}` of `{}` is missing!", pin, gate_name)))?;
+                pins.push(wire(net, &mut netlist));
+            }
+            Gate::from_pins(kind, gate_name, &pins).unwrap()
+        };
+        netlist.gates.push(gate);
+    }
+
+    for net in strings(def, "counter_nets")? {
+        let wid = *ids
+            .get(&net)
+            .ok_or_else(|| err(format!("Counter net `{}` does not exist!", net)))?;
+        netlist.counter_wires.insert(wid);
+    }
+
+    netlist.check().map_err(err)?;
+
+    stack.pop();
+    let netlist = Arc::new(netlist);
+    loaded.insert(name.to_string(), netlist.clone());
+    return Ok(netlist);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Json;
+    use crate::backends::Backend;
+    use crate::circuit::Circuit;
+    use crate::components::{Arithmetic, Clocking, Sequential};
+    use crate::equivalence::EquivalenceChecker;
+
+    // サブサーキットを使う回路と反転クロックの回路が, 読み込み直しても等価であること
+    #[test]
+    fn round_trip() {
+        let adder: Circuit<3, 0, 2, 0> = Arithmetic::ripple_carry_adder();
+        let parsed = Json::parse::<3, 0, 2, 0>(&Json::generate(&adder)).unwrap();
+        assert!(parsed.netlist.same_as(&adder.netlist));
+        let res = EquivalenceChecker::new().check(&parsed, &adder);
+        assert!(res.unwrap().is_proven());
+
+        let sr = Sequential::shift_register(3, Clocking::CounterFlow);
+        let parsed = Json::parse::<2, 0, 1, 0>(&Json::generate(&sr)).unwrap();
+        assert!(!parsed.netlist.counter_wires.is_empty());
+        let res = EquivalenceChecker::new().check(&parsed, &sr);
+        assert!(res.unwrap().is_proven());
+    }
+
+    #[test]
+    fn malformed() {
+        let adder: Circuit<3, 0, 2, 0> = Arithmetic::ripple_carry_adder();
+        let src = Json::generate(&adder);
+        let err = |src: &str| Json::parse_netlist(src).unwrap_err();
+
+        assert_eq!(err("{\n  \"format\": ").line(), Some(2));
+        let other_version = src.replacen("\"version\": 1", "\"version\": 2", 1);
+        assert!(err(&other_version).message().contains("version"));
+        let unknown_top = src.replacen("\"top\": \"RCA1\"", "\"top\": \"RCA3\"", 1);
+        assert_eq!(
+            err(&unknown_top).message(),
+            "Circuit `RCA3` is not defined!"
+        );
+        let unknown_gate = src.replacen("\"type\": \"SPLIT\"", "\"type\": \"SPLIT9\"", 1);
+        assert!(
+            err(&unknown_gate)
+                .message()
+                .contains("Unknown gate type `SPLIT9`!")
+        );
+        let missing_child = src.replacen("\"name\": \"HA\"", "\"name\": \"HB\"", 1);
+        assert_eq!(
+            err(&missing_child).message(),
+            "Circuit `HA` is not defined!"
+        );
+        // ポートの数が型と合わない
+        assert!(Json::parse::<2, 0, 2, 0>(&src).is_err());
+    }
+}
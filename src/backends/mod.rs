@@ -1,13 +1,39 @@
+mod behavioral_verilog;
+mod dot;
+mod json;
 mod rsfqlib_spice;
 mod rsfqlib_verilog;
+mod rsfqlib_wrspice;
+mod structural;
+mod svg;
 
 use crate::circuit::Circuit;
+use crate::netlist::Netlist;
 
+pub use behavioral_verilog::{BehavioralVerilog, VerilogTestbench};
+pub use dot::Dot;
+pub use json::Json;
 pub use rsfqlib_spice::RsfqlibSpice;
 pub use rsfqlib_verilog::RsfqlibVerilog;
+pub use rsfqlib_wrspice::{RsfqlibWrspice, WrspiceDeck};
+pub use svg::Svg;
 
 pub trait Backend {
     fn generate<const N_I: usize, const N_CO: usize, const N_O: usize, const N_CI: usize>(
         circuit: &Circuit<N_I, N_CO, N_O, N_CI>,
-    ) -> String;
+    ) -> String {
+        return Self::generate_netlist(&circuit.netlist);
+    }
+
+    fn generate_netlist(netlist: &Netlist) -> String;
+
+    // 下位の回路から順に並んだ階層全体を 1 つのファイルにする
+    // 既定では各回路の出力を並べる
+    fn generate_design(netlists: &[&Netlist]) -> String {
+        return netlists
+            .iter()
+            .map(|c| Self::generate_netlist(c))
+            .collect::<Vec<String>>()
+            .join("\n\n");
+    }
 }
@@ -1,6 +1,9 @@
 use super::Backend;
+use super::structural::{self, Conns, Instance, Module};
 use crate::circuit::Circuit;
+use crate::error::ParseError;
 use crate::gate::Gate;
+use crate::netlist::Netlist;
 
 pub struct RsfqlibSpice;
 
@@ -15,9 +18,7 @@ macro_rules! gate_string {
 }
 
 impl Backend for RsfqlibSpice {
-    fn generate<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>(
-        c: &Circuit<N_I, N_CI, N_O, N_CO>,
-    ) -> String {
+    fn generate_netlist(c: &Netlist) -> String {
         let mut res = Vec::new();
 
         /* ------------------- header ------------------- */
@@ -40,6 +41,44 @@ impl Backend for RsfqlibSpice {
                 Gate::Jtl { name, a, q } => gate_string!(c, name, [a, q], "JTL"),
                 Gate::Split { name, a, q1, q2 } => gate_string!(c, name, [a, q1, q2], "SPLIT"),
                 Gate::Merge { name, a, b, q } => gate_string!(c, name, [a, b, q], "MERGE"),
+                Gate::Split3 {
+                    name,
+                    a,
+                    q1,
+                    q2,
+                    q3,
+                } => {
+                    gate_string!(c, name, [a, q1, q2, q3], "SPLIT3")
+                }
+                Gate::Split4 {
+                    name,
+                    a,
+                    q1,
+                    q2,
+                    q3,
+                    q4,
+                } => {
+                    gate_string!(c, name, [a, q1, q2, q3, q4], "SPLIT4")
+                }
+                Gate::Merge3 {
+                    name,
+                    a,
+                    b,
+                    c: x,
+                    q,
+                } => {
+                    gate_string!(c, name, [a, b, x, q], "MERGE3")
+                }
+                Gate::Merge4 {
+                    name,
+                    a,
+                    b,
+                    c: x,
+                    d,
+                    q,
+                } => {
+                    gate_string!(c, name, [a, b, x, d, q], "MERGE4")
+                }
                 Gate::And { name, a, b, clk, q } => gate_string!(c, name, [a, b, clk, q], "AND2"),
                 Gate::Or { name, a, b, clk, q } => gate_string!(c, name, [a, b, clk, q], "OR2"),
                 Gate::Xor { name, a, b, clk, q } => gate_string!(c, name, [a, b, clk, q], "XOR"),
@@ -47,8 +86,41 @@ impl Backend for RsfqlibSpice {
                 Gate::Not { name, a, clk, q } => gate_string!(c, name, [a, clk, q], "NOT"),
                 Gate::Dff { name, a, clk, q } => gate_string!(c, name, [a, clk, q], "DFF"),
                 Gate::Ndro { name, a, b, clk, q } => gate_string!(c, name, [a, b, clk, q], "NDRO"),
+                Gate::DffR { name, a, r, clk, q } => gate_string!(c, name, [a, r, clk, q], "DFFR"),
+                Gate::DffRSync { name, a, r, clk, q } => {
+                    gate_string!(c, name, [a, r, clk, q], "DFFR_SYNC")
+                }
+                Gate::DffC {
+                    name,
+                    a,
+                    clk,
+                    q1,
+                    q2,
+                } => {
+                    gate_string!(c, name, [a, clk, q1, q2], "DFFC")
+                }
+                Gate::NdroC {
+                    name,
+                    a,
+                    b,
+                    clk,
+                    q1,
+                    q2,
+                } => {
+                    gate_string!(c, name, [a, b, clk, q1, q2], "NDROC")
+                }
                 Gate::Buff { name, a, q } => gate_string!(c, name, [a, q], "BUFF"),
+                Gate::FirstArrival { name, a, b, q } => gate_string!(c, name, [a, b, q], "FA"),
+                Gate::LastArrival { name, a, b, q } => gate_string!(c, name, [a, b, q], "LA"),
+                Gate::Inhibit { name, a, b, q } => gate_string!(c, name, [a, b, q], "INH"),
+                Gate::Tff { name, a, q } => gate_string!(c, name, [a, q], "TFF"),
+                Gate::Tff2 { name, a, q1, q2 } => gate_string!(c, name, [a, q1, q2], "TFF2"),
+                Gate::TffR { name, a, r, q } => gate_string!(c, name, [a, r, q], "TFFR"),
+                Gate::DcSfq { name, a, q } => gate_string!(c, name, [a, q], "DCSFQ"),
+                Gate::SfqDc { name, a, q } => gate_string!(c, name, [a, q], "SFQDC"),
                 Gate::ZeroAsync { name, q } => gate_string!(c, name, [q], "ALWAYS0_ASYNC_NOA"),
+                Gate::ZeroSync { name, clk, q } => gate_string!(c, name, [clk, q], "ALWAYS0_SYNC"),
+                Gate::OneSync { name, clk, q } => gate_string!(c, name, [clk, q], "ALWAYS1_SYNC"),
                 Gate::Terminate { name, a } => {
                     format!("R{} {} 0 2", name, c.wire_names.get(a).unwrap())
                 }
@@ -64,7 +136,7 @@ impl Backend for RsfqlibSpice {
                         .chain(outputs.iter())
                         .map(|wid| c.wire_names.get(wid).unwrap().as_str())
                         .collect();
-                    format!("{} {} {}", name, ports.join(" "), circuit)
+                    format!("{} {} {}", name, ports.join(" "), circuit.name)
                 }
                 _ => panic!("Unsupported Gate"),
             };
@@ -77,3 +149,162 @@ impl Backend for RsfqlibSpice {
         return res.join("\n");
     }
 }
+
+impl RsfqlibSpice {
+    // generate() の出力 (や同じ形の手書きネットリスト) を読み込む
+    // 他から使われていない .subckt をトップとし, 物理ポートは型パラメータの順に割り当てる
+    pub fn parse<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>(
+        src: &str,
+    ) -> Result<Circuit<N_I, N_CI, N_O, N_CO>, ParseError> {
+        return structural::typed(Self::parse_netlist(src)?);
+    }
+
+    // ポート数が分からない場合は Netlist として読み込む
+    // ポートの向きは内部の接続から推定し, すべて inputs / outputs になる
+    pub fn parse_netlist(src: &str) -> Result<Netlist, ParseError> {
+        // 継続行 (+ で始まる行) をつなげる
+        let mut lines: Vec<(usize, Vec<&str>)> = Vec::new();
+        for (i, raw) in src.lines().enumerate() {
+            let text = raw.trim();
+            if text.is_empty() || text.starts_with('*') {
+                continue;
+            }
+            match text.strip_prefix('+') {
+                Some(rest) if !lines.is_empty() => {
+                    lines.last_mut().unwrap().1.extend(rest.split_whitespace());
+                }
+                _ => lines.push((i + 1, text.split_whitespace().collect())),
+            }
+        }
+
+        let mut modules: Vec<Module> = Vec::new();
+        let mut current: Option<Module> = None;
+        for (line, tokens) in lines {
+            let head = tokens[0].to_lowercase();
+            match (&mut current, head.as_str()) {
+                (None, ".subckt") => {
+                    let name = tokens
+                        .get(1)
+                        .ok_or_else(|| ParseError::at(line, "Subcircuit name is missing!"))?;
+                    current = Some(Module {
+                        line,
+                        name: name.to_string(),
+                        ports: tokens[2..].iter().map(|s| s.to_string()).collect(),
+                        dirs: None,
+                        instances: Vec::new(),
+                    });
+                }
+                (Some(_), ".ends") => modules.push(current.take().unwrap()),
+                (Some(_), ".subckt") => {
+                    return Err(ParseError::at(line, "Nested `.subckt` is not supported!"));
+                }
+                // サブサーキットの外 (解析条件など) は読み飛ばす
+                (None, _) => {}
+                (Some(m), _) if head.starts_with('x') && tokens.len() >= 2 => {
+                    // パラメータ (key=value) は無視する
+                    let args: Vec<&str> = tokens
+                        .iter()
+                        .copied()
+                        .filter(|t| !t.contains('='))
+                        .collect();
+                    m.instances.push(Instance {
+                        line,
+                        name: args[0].to_string(),
+                        cell: args[args.len() - 1].to_string(),
+                        conns: Conns::Ordered(
+                            args[1..args.len() - 1]
+                                .iter()
+                                .map(|s| s.to_string())
+                                .collect(),
+                        ),
+                    });
+                }
+                // グラウンドへの抵抗は終端
+                (Some(m), _) if head.starts_with('r') && tokens.len() >= 3 && tokens[2] == "0" => {
+                    m.instances.push(Instance {
+                        line,
+                        name: tokens[0][1..].to_string(),
+                        cell: "TERMINATE".to_string(),
+                        conns: Conns::Ordered(vec![tokens[1].to_string()]),
+                    });
+                }
+                (Some(_), _) => {
+                    return Err(ParseError::at(
+                        line,
+                        format!("Element `{}` is not supported!", tokens[0]),
+                    ));
+                }
+            }
+        }
+        if let Some(m) = current {
+            return Err(ParseError::at(
+                m.line,
+                format!("`.ends` of `{}` is missing!", m.name),
+            ));
+        }
+
+        return structural::load(&modules, spice_kind, false);
+    }
+}
+
+// 終端は抵抗として読んだもの
+fn spice_kind(cell: &str) -> Option<&'static str> {
+    if cell == "TERMINATE" {
+        return Some("TERMINATE");
+    }
+    return structural::rsfqlib_kind(cell);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RsfqlibSpice;
+    use crate::circuit::Circuit;
+    use crate::components::{Arithmetic, Clocking, Sequential};
+    use crate::design::Design;
+    use crate::equivalence::EquivalenceChecker;
+
+    // サブサーキットを使う回路と反転クロックの回路が, 読み込み直しても等価であること
+    #[test]
+    fn round_trip() {
+        let adder: Circuit<3, 0, 2, 0> = Arithmetic::ripple_carry_adder();
+        let src = Design::new().add(&adder).generate::<RsfqlibSpice>();
+        let parsed = RsfqlibSpice::parse::<3, 0, 2, 0>(&src).unwrap();
+        let res = EquivalenceChecker::new().check(&parsed, &adder);
+        assert!(res.unwrap().is_proven());
+
+        let sr = Sequential::shift_register(3, Clocking::CounterFlow);
+        let src = Design::new().add(&sr).generate::<RsfqlibSpice>();
+        let parsed = RsfqlibSpice::parse::<2, 0, 1, 0>(&src).unwrap();
+        let res = EquivalenceChecker::new().check(&parsed, &sr);
+        assert!(res.unwrap().is_proven());
+    }
+
+    #[test]
+    fn malformed() {
+        let err = |src: &str| RsfqlibSpice::parse_netlist(src).unwrap_err();
+
+        let e = err(".subckt top a q\nXJTL1 a q THmitll_JTL\n");
+        assert_eq!(
+            (e.line(), e.message()),
+            (Some(1), "`.ends` of `top` is missing!")
+        );
+        let e = err(".subckt top a q\n.subckt inner a q\n.ends\n.ends\n");
+        assert_eq!(e.line(), Some(2));
+        let e = err(".subckt top a q\nL1 a q 1p\n.ends\n");
+        assert_eq!(e.message(), "Element `L1` is not supported!");
+        let e = err(".subckt top a q\nXFOO1 a q THmitll_FOO\n.ends\n");
+        assert_eq!(
+            (e.line(), e.message()),
+            (Some(2), "Unknown cell `THmitll_FOO`!")
+        );
+        let e = err(".subckt top a q\nXJTL1 a q THmitll_JTL\nXJTL1 q a THmitll_JTL\n.ends\n");
+        assert_eq!(e.line(), Some(3));
+        let e = err(".subckt top a q\nXHA1 a q HA\n.ends\n");
+        assert_eq!(e.message(), "Unknown cell `HA`!");
+        // ポートの数が型と合わない
+        assert!(
+            RsfqlibSpice::parse::<2, 0, 1, 0>(".subckt top a q\nXJTL1 a q THmitll_JTL\n.ends\n")
+                .is_err()
+        );
+    }
+}
@@ -1,24 +1,25 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
 
 use super::Backend;
+use super::structural::{self, Conns, Dir, Instance, Module};
 use crate::circuit::Circuit;
+use crate::error::ParseError;
 use crate::gate::Gate;
+use crate::netlist::Netlist;
 
 pub struct RsfqlibVerilog;
 
 //  (circuit, gate名, 引数WireIDリスト, ゲート名)
 macro_rules! gate_string {
     ($c:ident, $name:ident, [$($arg:ident),*], $gate:expr) => {
-        format!("THmitll_{}_v3p0_extracted {} ({});", $gate, $name,
-            vec![ $($c.wire_names.get($arg).unwrap().as_str(), )*].join(", ")
+        format!("THmitll_{}_v3p0_extracted {} ({});", $gate, ident($name),
+            vec![ $(ident($c.wire_names.get($arg).unwrap()), )*].join(", ")
         )
     };
 }
 
 impl Backend for RsfqlibVerilog {
-    fn generate<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>(
-        c: &Circuit<N_I, N_CI, N_O, N_CO>,
-    ) -> String {
+    fn generate_netlist(c: &Netlist) -> String {
         let mut res = Vec::new();
 
         /* ------------------- header ------------------- */
@@ -35,12 +36,13 @@ impl Backend for RsfqlibVerilog {
             .map(|s| s.as_str())
             .collect();
         let ports: Vec<&str> = [in_ports.clone(), out_ports.clone()].concat();
-        res.push(format!("module {} ({});", c.name, ports.join(", ")));
-        if in_ports.len() > 0 {
-            res.push(format!("input {};", in_ports.join(", ")));
+        res.push(format!("module {} ({});", ident(&c.name), idents(&ports)));
+        if !in_ports.is_empty() {
+            res.push(format!("input {};", idents(&in_ports)));
         }
-        if c.outputs.len() > 0 {
-            res.push(format!("output {};", out_ports.join(", ")));
+        // 反転入力も物理的には出力ポート
+        if !out_ports.is_empty() {
+            res.push(format!("output {};", idents(&out_ports)));
         }
 
         let wires: Vec<&str> = c
@@ -51,8 +53,8 @@ impl Backend for RsfqlibVerilog {
             .collect::<BTreeSet<&str>>() // 重複削除, ソート
             .into_iter()
             .collect();
-        if wires.len() > 0 {
-            res.push(format!("wire {};", wires.join(", ")));
+        if !wires.is_empty() {
+            res.push(format!("wire {};", idents(&wires)));
         }
 
         /* ------------------- body ------------------- */
@@ -61,6 +63,44 @@ impl Backend for RsfqlibVerilog {
                 Gate::Jtl { name, a, q } => gate_string!(c, name, [a, q], "JTL"),
                 Gate::Split { name, a, q1, q2 } => gate_string!(c, name, [a, q1, q2], "SPLIT"),
                 Gate::Merge { name, a, b, q } => gate_string!(c, name, [a, b, q], "MERGE"),
+                Gate::Split3 {
+                    name,
+                    a,
+                    q1,
+                    q2,
+                    q3,
+                } => {
+                    gate_string!(c, name, [a, q1, q2, q3], "SPLIT3")
+                }
+                Gate::Split4 {
+                    name,
+                    a,
+                    q1,
+                    q2,
+                    q3,
+                    q4,
+                } => {
+                    gate_string!(c, name, [a, q1, q2, q3, q4], "SPLIT4")
+                }
+                Gate::Merge3 {
+                    name,
+                    a,
+                    b,
+                    c: x,
+                    q,
+                } => {
+                    gate_string!(c, name, [a, b, x, q], "MERGE3")
+                }
+                Gate::Merge4 {
+                    name,
+                    a,
+                    b,
+                    c: x,
+                    d,
+                    q,
+                } => {
+                    gate_string!(c, name, [a, b, x, d, q], "MERGE4")
+                }
                 Gate::And { name, a, b, clk, q } => gate_string!(c, name, [a, b, clk, q], "AND2"),
                 Gate::Or { name, a, b, clk, q } => gate_string!(c, name, [a, b, clk, q], "OR2"),
                 Gate::Xor { name, a, b, clk, q } => gate_string!(c, name, [a, b, clk, q], "XOR"),
@@ -68,12 +108,45 @@ impl Backend for RsfqlibVerilog {
                 Gate::Not { name, a, clk, q } => gate_string!(c, name, [a, clk, q], "NOT"),
                 Gate::Dff { name, a, clk, q } => gate_string!(c, name, [a, clk, q], "DFF"),
                 Gate::Ndro { name, a, b, clk, q } => gate_string!(c, name, [a, b, clk, q], "NDRO"),
+                Gate::DffR { name, a, r, clk, q } => gate_string!(c, name, [a, r, clk, q], "DFFR"),
+                Gate::DffRSync { name, a, r, clk, q } => {
+                    gate_string!(c, name, [a, r, clk, q], "DFFR_SYNC")
+                }
+                Gate::DffC {
+                    name,
+                    a,
+                    clk,
+                    q1,
+                    q2,
+                } => {
+                    gate_string!(c, name, [a, clk, q1, q2], "DFFC")
+                }
+                Gate::NdroC {
+                    name,
+                    a,
+                    b,
+                    clk,
+                    q1,
+                    q2,
+                } => {
+                    gate_string!(c, name, [a, b, clk, q1, q2], "NDROC")
+                }
                 Gate::Buff { name, a, q } => gate_string!(c, name, [a, q], "BUFF"),
+                Gate::FirstArrival { name, a, b, q } => gate_string!(c, name, [a, b, q], "FA"),
+                Gate::LastArrival { name, a, b, q } => gate_string!(c, name, [a, b, q], "LA"),
+                Gate::Inhibit { name, a, b, q } => gate_string!(c, name, [a, b, q], "INH"),
+                Gate::Tff { name, a, q } => gate_string!(c, name, [a, q], "TFF"),
+                Gate::Tff2 { name, a, q1, q2 } => gate_string!(c, name, [a, q1, q2], "TFF2"),
+                Gate::TffR { name, a, r, q } => gate_string!(c, name, [a, r, q], "TFFR"),
+                Gate::DcSfq { name, a, q } => gate_string!(c, name, [a, q], "DCSFQ"),
+                Gate::SfqDc { name, a, q } => gate_string!(c, name, [a, q], "SFQDC"),
                 Gate::ZeroAsync { name, q } => format!(
                     "THmitll_ALWAYS0_ASYNC_NOA {} ({});",
-                    name,
-                    c.wire_names.get(q).unwrap()
+                    ident(name),
+                    ident(c.wire_names.get(q).unwrap())
                 ),
+                Gate::ZeroSync { name, clk, q } => gate_string!(c, name, [clk, q], "ALWAYS0_SYNC"),
+                Gate::OneSync { name, clk, q } => gate_string!(c, name, [clk, q], "ALWAYS1_SYNC"),
                 Gate::Terminate { name: _, a: _ } => String::new(),
                 Gate::Subcircuit {
                     name,
@@ -86,7 +159,12 @@ impl Backend for RsfqlibVerilog {
                         .chain(outputs.iter())
                         .map(|wid| c.wire_names.get(wid).unwrap().as_str())
                         .collect();
-                    format!("{} {} ({});", circuit, name, ports.join(", "))
+                    format!(
+                        "{} {} ({});",
+                        ident(&circuit.name),
+                        ident(name),
+                        idents(&ports)
+                    )
                 }
                 _ => panic!("Unsupported Gate"),
             };
@@ -99,3 +177,505 @@ impl Backend for RsfqlibVerilog {
         return res.join("\n");
     }
 }
+
+// Verilog の識別子として使えない名前 (階層名の / など) はエスケープする
+// エスケープされた識別子は \ で始まり空白で終わる
+pub(super) fn ident(name: &str) -> String {
+    let mut chars = name.chars();
+    let legal = match chars.next() {
+        Some(c) => {
+            (c.is_ascii_alphabetic() || c == '_')
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+        }
+        None => false,
+    };
+    if legal {
+        return name.to_string();
+    }
+    return format!("\\{} ", name);
+}
+
+pub(super) fn idents(names: &[&str]) -> String {
+    return names
+        .iter()
+        .map(|s| ident(s))
+        .collect::<Vec<String>>()
+        .join(", ");
+}
+
+impl RsfqlibVerilog {
+    // generate() の出力や外部ツールの構造記述 Verilog を読み込む
+    // 他から使われていない module をトップとし, 物理ポートは型パラメータの順に割り当てる
+    pub fn parse<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>(
+        src: &str,
+    ) -> Result<Circuit<N_I, N_CI, N_O, N_CO>, ParseError> {
+        return structural::typed(Self::parse_netlist(src)?);
+    }
+
+    // ポート数が分からない場合は Netlist として読み込む
+    pub fn parse_netlist(src: &str) -> Result<Netlist, ParseError> {
+        let tokens = tokenize(src)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let mut modules = Vec::new();
+        while parser.peek().is_some() {
+            modules.push(parser.module()?);
+        }
+        // Verilog には終端がないので, レシーバのない配線は終端する
+        return structural::load(&modules, structural::rsfqlib_kind, true);
+    }
+}
+
+/* ------------------- tokenizer ------------------- */
+
+// (行番号, トークン)
+fn tokenize(src: &str) -> Result<Vec<(usize, String)>, ParseError> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut res = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                if chars[i] == '\n' {
+                    line += 1;
+                }
+                i += 1;
+            }
+            i += 2;
+        } else if c.is_ascii_alphanumeric() || c == '_' || c == '$' {
+            while i < chars.len()
+                && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+            {
+                i += 1;
+            }
+            res.push((line, chars[start..i].iter().collect()));
+        } else if c == '\\' {
+            // エスケープされた識別子は空白まで
+            i += 1;
+            while i < chars.len() && !chars[i].is_whitespace() {
+                i += 1;
+            }
+            res.push((line, chars[start + 1..i].iter().collect()));
+        } else if "(),;.[]:#=".contains(c) {
+            i += 1;
+            res.push((line, c.to_string()));
+        } else {
+            return Err(ParseError::at(
+                line,
+                format!("Unexpected character `{}`!", c),
+            ));
+        }
+    }
+    return Ok(res);
+}
+
+/* ------------------- parser ------------------- */
+
+// バスの範囲 (msb, lsb)
+type Range = (i64, i64);
+
+struct Parser {
+    tokens: Vec<(usize, String)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        return self.tokens.get(self.pos).map(|(_, t)| t.as_str());
+    }
+
+    fn line(&self) -> usize {
+        return self
+            .tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map(|(l, _)| *l)
+            .unwrap_or(1);
+    }
+
+    fn next(&mut self) -> Result<String, ParseError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .ok_or_else(|| ParseError::at(self.line(), "Unexpected end of file!"))?;
+        self.pos += 1;
+        return Ok(token.1.clone());
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), ParseError> {
+        let line = self.line();
+        let token = self.next()?;
+        if token != expected {
+            return Err(ParseError::at(
+                line,
+                format!("`{}` is expected, but `{}` is found!", expected, token),
+            ));
+        }
+        return Ok(());
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            return true;
+        }
+        return false;
+    }
+
+    fn ident(&mut self) -> Result<String, ParseError> {
+        let line = self.line();
+        let token = self.next()?;
+        if token.len() == 1 && "(),;.[]:#=".contains(&token) {
+            return Err(ParseError::at(
+                line,
+                format!("Identifier is expected, but `{}` is found!", token),
+            ));
+        }
+        return Ok(token);
+    }
+
+    fn number(&mut self) -> Result<i64, ParseError> {
+        let line = self.line();
+        let token = self.next()?;
+        return token
+            .parse()
+            .map_err(|_| ParseError::at(line, format!("`{}` is not a number!", token)));
+    }
+
+    // [msb:lsb] があれば (msb, lsb)
+    fn range(&mut self) -> Result<Option<Range>, ParseError> {
+        if !self.eat("[") {
+            return Ok(None);
+        }
+        let msb = self.number()?;
+        self.expect(":")?;
+        let lsb = self.number()?;
+        self.expect("]")?;
+        return Ok(Some((msb, lsb)));
+    }
+
+    // 配線の参照: name または name[i]
+    fn net(&mut self, buses: &HashMap<String, Vec<String>>) -> Result<String, ParseError> {
+        let line = self.line();
+        let name = self.ident()?;
+        if self.eat("[") {
+            let index = self.number()?;
+            self.expect("]")?;
+            return Ok(format!("{}[{}]", name, index));
+        }
+        if buses.contains_key(&name) {
+            return Err(ParseError::at(
+                line,
+                format!("Connection to bus `{}` must select a bit!", name),
+            ));
+        }
+        return Ok(name);
+    }
+
+    fn module(&mut self) -> Result<Module, ParseError> {
+        let line = self.line();
+        self.expect("module")?;
+        let name = self.ident()?;
+
+        // ポート名 (バスは展開前), 方向, バスの各ビット
+        let mut ports: Vec<String> = Vec::new();
+        let mut dirs: HashMap<String, Dir> = HashMap::new();
+        let mut buses: HashMap<String, Vec<String>> = HashMap::new();
+
+        if self.eat("(") && !self.eat(")") {
+            let mut dir = None;
+            loop {
+                match self.peek() {
+                    Some("input") | Some("output") => {
+                        let (d, range) = self.declaration()?;
+                        dir = Some((d, range));
+                    }
+                    _ => {}
+                }
+                let port = self.ident()?;
+                if let Some((Some(d), range)) = dir {
+                    declare(&port, d, range, &mut dirs, &mut buses);
+                }
+                ports.push(port);
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+        self.expect(";")?;
+
+        let mut instances = Vec::new();
+        loop {
+            let line = self.line();
+            match self.peek() {
+                Some("endmodule") => {
+                    self.pos += 1;
+                    break;
+                }
+                Some("input") | Some("output") | Some("wire") => {
+                    let (d, range) = self.declaration()?;
+                    loop {
+                        let net = self.ident()?;
+                        match d {
+                            Some(d) => declare(&net, d, range, &mut dirs, &mut buses),
+                            None => {
+                                if let Some((msb, lsb)) = range {
+                                    buses.insert(net.clone(), bits(&net, msb, lsb));
+                                }
+                            }
+                        }
+                        if self.eat(";") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                Some("inout") | Some("assign") | Some("reg") => {
+                    return Err(ParseError::at(
+                        line,
+                        format!("`{}` is not supported!", self.peek().unwrap()),
+                    ));
+                }
+                Some(_) => instances.push(self.instance(&buses)?),
+                None => return Err(ParseError::at(line, "`endmodule` is missing!")),
+            }
+        }
+
+        // バスのポートをビットに展開する
+        let mut expanded = Vec::new();
+        for port in ports {
+            match buses.get(&port) {
+                Some(bits) => {
+                    let d = dirs.get(&port).copied();
+                    for bit in bits {
+                        if let Some(d) = d {
+                            dirs.insert(bit.clone(), d);
+                        }
+                        expanded.push(bit.clone());
+                    }
+                }
+                None => expanded.push(port),
+            }
+        }
+
+        return Ok(Module {
+            line,
+            name,
+            ports: expanded,
+            dirs: Some(dirs),
+            instances,
+        });
+    }
+
+    // input / output / wire と範囲. wire のみなら方向は None
+    fn declaration(&mut self) -> Result<(Option<Dir>, Option<Range>), ParseError> {
+        let dir = match self.next()?.as_str() {
+            "input" => Some(Dir::Input),
+            "output" => Some(Dir::Output),
+            _ => None,
+        };
+        if dir.is_some() {
+            self.eat("wire");
+        }
+        let range = self.range()?;
+        return Ok((dir, range));
+    }
+
+    fn instance(&mut self, buses: &HashMap<String, Vec<String>>) -> Result<Instance, ParseError> {
+        let line = self.line();
+        let cell = self.ident()?;
+        // パラメータは読み飛ばす
+        if self.eat("#") {
+            self.expect("(")?;
+            let mut depth = 1;
+            while depth > 0 {
+                match self.next()?.as_str() {
+                    "(" => depth += 1,
+                    ")" => depth -= 1,
+                    _ => {}
+                }
+            }
+        }
+        let name = self.ident()?;
+        self.expect("(")?;
+
+        let conns = if self.peek() == Some(".") {
+            let mut conns = Vec::new();
+            loop {
+                self.expect(".")?;
+                let pin = self.ident()?;
+                self.expect("(")?;
+                if self.peek() == Some(")") {
+                    return Err(ParseError::at(
+                        self.line(),
+                        format!("Pin `{}` of `{}` is not connected!", pin, name),
+                    ));
+                }
+                conns.push((pin, self.net(buses)?));
+                self.expect(")")?;
+                if !self.eat(",") {
+                    break;
+                }
+            }
+            Conns::Named(conns)
+        } else {
+            let mut args = Vec::new();
+            if self.peek() != Some(")") {
+                loop {
+                    args.push(self.net(buses)?);
+                    if !self.eat(",") {
+                        break;
+                    }
+                }
+            }
+            Conns::Ordered(args)
+        };
+        self.expect(")")?;
+        self.expect(";")?;
+
+        return Ok(Instance {
+            line,
+            name,
+            cell,
+            conns,
+        });
+    }
+}
+
+fn bits(name: &str, msb: i64, lsb: i64) -> Vec<String> {
+    let range: Vec<i64> = if msb >= lsb {
+        (lsb..=msb).rev().collect()
+    } else {
+        (msb..=lsb).collect()
+    };
+    return range.iter().map(|i| format!("{}[{}]", name, i)).collect();
+}
+
+fn declare(
+    name: &str,
+    dir: Dir,
+    range: Option<Range>,
+    dirs: &mut HashMap<String, Dir>,
+    buses: &mut HashMap<String, Vec<String>>,
+) {
+    dirs.insert(name.to_string(), dir);
+    if let Some((msb, lsb)) = range {
+        buses.insert(name.to_string(), bits(name, msb, lsb));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RsfqlibVerilog;
+    use crate::backends::Backend;
+    use crate::circuit::Circuit;
+    use crate::components::{Arithmetic, Clocking, Sequential};
+    use crate::design::Design;
+    use crate::equivalence::EquivalenceChecker;
+
+    // 出力が反転入力だけのモジュールも output を宣言し, 読み込み直せること
+    #[test]
+    fn counter_input_only() {
+        let (mut c, [a], [b], [], []) = Circuit::create(["a"], ["b"], [], [], "loopback");
+        let q = c.jtl(a);
+        c.unify(q, b);
+        let src = RsfqlibVerilog::generate(&c);
+        assert!(src.lines().any(|line| line == "output b;"), "{}", src);
+        let parsed = RsfqlibVerilog::parse::<1, 1, 0, 0>(&src).unwrap();
+        EquivalenceChecker::new().check(&parsed, &c).unwrap();
+    }
+
+    // 展開した回路の階層名 (XHalfAdder1/XSPLIT1 など) はエスケープして出力し, 読み込み直せること
+    #[test]
+    fn flattened_names() {
+        let (mut ha, [a, b, clk], [], [s, c], []) =
+            Circuit::create(["a", "b", "clk"], [], ["s", "c"], [], "HalfAdder");
+        let (a1, a2) = ha.split(a);
+        let (b1, b2) = ha.split(b);
+        let (clk1, clk2) = ha.split(clk);
+        let x = ha.xor(a1, b1, clk1);
+        let y = ha.and(a2, b2, clk2);
+        ha.unify(x, s);
+        ha.unify(y, c);
+
+        let (mut top, [a, b, clk], [], [s, c], []) =
+            Circuit::create(["a", "b", "clk"], [], ["s", "c"], [], "top");
+        let ([x, y], []) = top.subcircuit(&ha, [a, b, clk], []);
+        top.unify(x, s);
+        top.unify(y, c);
+
+        let flat = top.flatten();
+        let src = RsfqlibVerilog::generate(&flat);
+        assert!(src.contains("\\XHalfAdder1/"), "{}", src);
+        let parsed = RsfqlibVerilog::parse::<3, 0, 2, 0>(&src).unwrap();
+        EquivalenceChecker::new().check(&parsed, &top).unwrap();
+    }
+
+    // サブサーキットを使う回路と反転クロックの回路が, 読み込み直しても等価であること
+    #[test]
+    fn round_trip() {
+        let adder: Circuit<3, 0, 2, 0> = Arithmetic::ripple_carry_adder();
+        let src = Design::new().add(&adder).generate::<RsfqlibVerilog>();
+        let parsed = RsfqlibVerilog::parse::<3, 0, 2, 0>(&src).unwrap();
+        let res = EquivalenceChecker::new().check(&parsed, &adder);
+        assert!(res.unwrap().is_proven());
+
+        let sr = Sequential::shift_register(3, Clocking::CounterFlow);
+        let src = Design::new().add(&sr).generate::<RsfqlibVerilog>();
+        let parsed = RsfqlibVerilog::parse::<2, 0, 1, 0>(&src).unwrap();
+        let res = EquivalenceChecker::new().check(&parsed, &sr);
+        assert!(res.unwrap().is_proven());
+    }
+
+    #[test]
+    fn malformed() {
+        let err = |src: &str| RsfqlibVerilog::parse_netlist(src).unwrap_err();
+        let jtl = "THmitll_JTL_v3p0_extracted XJTL1 (a, q);";
+
+        let e = err("module top (a, q);\ninput a;\noutput q;\n@\nendmodule\n");
+        assert_eq!(
+            (e.line(), e.message()),
+            (Some(4), "Unexpected character `@`!")
+        );
+        let e = err(&format!(
+            "module top (a, q);\ninput a;\n{}\nendmodule\n",
+            jtl
+        ));
+        assert_eq!(e.message(), "Direction of port `q` is missing!");
+        let e = err(
+            "module top (a, q);\ninput a;\noutput q;\nTHmitll_JTL_v3p0_extracted XJTL1 (a, q)\n",
+        );
+        assert_eq!(e.line(), Some(4));
+        let e = err(
+            "module top (a, q);\ninput [1:0] a;\noutput q;\nTHmitll_JTL_v3p0_extracted XJTL1 (a, q);\nendmodule\n",
+        );
+        assert_eq!(e.message(), "Connection to bus `a` must select a bit!");
+        let e =
+            err("module top (a, q);\ninput a;\noutput q;\nTHmitll_FOO XFOO1 (a, q);\nendmodule\n");
+        assert_eq!(
+            (e.line(), e.message()),
+            (Some(4), "Unknown cell `THmitll_FOO`!")
+        );
+        assert_eq!(err("").message(), "No module is defined!");
+        // ポートの数が型と合わない
+        let src = format!(
+            "module top (a, q);\ninput a;\noutput q;\n{}\nendmodule\n",
+            jtl
+        );
+        assert!(RsfqlibVerilog::parse::<1, 0, 1, 0>(&src).is_ok());
+        assert!(RsfqlibVerilog::parse::<2, 0, 1, 0>(&src).is_err());
+    }
+}
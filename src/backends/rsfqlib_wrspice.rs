@@ -0,0 +1,468 @@
+use colored::Colorize;
+
+use super::Backend;
+use crate::circuit::Circuit;
+use crate::design::hierarchy;
+use crate::gate::Gate;
+use crate::netlist::Netlist;
+
+pub struct RsfqlibWrspice;
+
+//  (circuit, gate名, 引数WireIDリスト, ゲート名)
+macro_rules! gate_string {
+    ($c:ident, $name:ident, [$($arg:ident),*],$gate:expr) => {
+        vec![$name,
+            $($c.wire_names.get($arg).unwrap(), )*
+            concat!("THmitll_", $gate)
+        ].join(" ")
+    };
+}
+
+impl Backend for RsfqlibWrspice {
+    fn generate_netlist(c: &Netlist) -> String {
+        let mut res = Vec::new();
+
+        /* ------------------- header ------------------- */
+        res.push(format!("* {}", c.name));
+        res.push(format!(".subckt {} {}", c.name, port_names(c).join(" ")));
+
+        /* ------------------- body ------------------- */
+        for gate in c.gates.iter() {
+            let s = match gate {
+                Gate::Jtl { name, a, q } => gate_string!(c, name, [a, q], "JTL"),
+                Gate::Split { name, a, q1, q2 } => gate_string!(c, name, [a, q1, q2], "SPLIT"),
+                Gate::Merge { name, a, b, q } => gate_string!(c, name, [a, b, q], "MERGE"),
+                Gate::Split3 {
+                    name,
+                    a,
+                    q1,
+                    q2,
+                    q3,
+                } => {
+                    gate_string!(c, name, [a, q1, q2, q3], "SPLIT3")
+                }
+                Gate::Split4 {
+                    name,
+                    a,
+                    q1,
+                    q2,
+                    q3,
+                    q4,
+                } => {
+                    gate_string!(c, name, [a, q1, q2, q3, q4], "SPLIT4")
+                }
+                Gate::Merge3 {
+                    name,
+                    a,
+                    b,
+                    c: x,
+                    q,
+                } => {
+                    gate_string!(c, name, [a, b, x, q], "MERGE3")
+                }
+                Gate::Merge4 {
+                    name,
+                    a,
+                    b,
+                    c: x,
+                    d,
+                    q,
+                } => {
+                    gate_string!(c, name, [a, b, x, d, q], "MERGE4")
+                }
+                Gate::And { name, a, b, clk, q } => gate_string!(c, name, [a, b, clk, q], "AND2"),
+                Gate::Or { name, a, b, clk, q } => gate_string!(c, name, [a, b, clk, q], "OR2"),
+                Gate::Xor { name, a, b, clk, q } => gate_string!(c, name, [a, b, clk, q], "XOR"),
+                Gate::Xnor { name, a, b, clk, q } => gate_string!(c, name, [a, b, clk, q], "XNOR"),
+                Gate::Not { name, a, clk, q } => gate_string!(c, name, [a, clk, q], "NOT"),
+                Gate::Dff { name, a, clk, q } => gate_string!(c, name, [a, clk, q], "DFF"),
+                Gate::Ndro { name, a, b, clk, q } => gate_string!(c, name, [a, b, clk, q], "NDRO"),
+                Gate::DffR { name, a, r, clk, q } => gate_string!(c, name, [a, r, clk, q], "DFFR"),
+                Gate::DffRSync { name, a, r, clk, q } => {
+                    gate_string!(c, name, [a, r, clk, q], "DFFR_SYNC")
+                }
+                Gate::DffC {
+                    name,
+                    a,
+                    clk,
+                    q1,
+                    q2,
+                } => {
+                    gate_string!(c, name, [a, clk, q1, q2], "DFFC")
+                }
+                Gate::NdroC {
+                    name,
+                    a,
+                    b,
+                    clk,
+                    q1,
+                    q2,
+                } => {
+                    gate_string!(c, name, [a, b, clk, q1, q2], "NDROC")
+                }
+                Gate::Buff { name, a, q } => gate_string!(c, name, [a, q], "BUFF"),
+                Gate::FirstArrival { name, a, b, q } => gate_string!(c, name, [a, b, q], "FA"),
+                Gate::LastArrival { name, a, b, q } => gate_string!(c, name, [a, b, q], "LA"),
+                Gate::Inhibit { name, a, b, q } => gate_string!(c, name, [a, b, q], "INH"),
+                Gate::Tff { name, a, q } => gate_string!(c, name, [a, q], "TFF"),
+                Gate::Tff2 { name, a, q1, q2 } => gate_string!(c, name, [a, q1, q2], "TFF2"),
+                Gate::TffR { name, a, r, q } => gate_string!(c, name, [a, r, q], "TFFR"),
+                Gate::DcSfq { name, a, q } => gate_string!(c, name, [a, q], "DCSFQ"),
+                Gate::SfqDc { name, a, q } => gate_string!(c, name, [a, q], "SFQDC"),
+                Gate::ZeroAsync { name, q } => gate_string!(c, name, [q], "ALWAYS0_ASYNC_NOA"),
+                Gate::ZeroSync { name, clk, q } => gate_string!(c, name, [clk, q], "ALWAYS0_SYNC"),
+                Gate::OneSync { name, clk, q } => gate_string!(c, name, [clk, q], "ALWAYS1_SYNC"),
+                // WRspice では抵抗の値に単位を付ける
+                Gate::Terminate { name, a } => {
+                    format!("R{} {} 0 2ohm", name, c.wire_names.get(a).unwrap())
+                }
+
+                Gate::Subcircuit {
+                    name,
+                    inputs,
+                    outputs,
+                    circuit,
+                } => {
+                    let ports: Vec<&str> = inputs
+                        .iter()
+                        .chain(outputs.iter())
+                        .map(|wid| c.wire_names.get(wid).unwrap().as_str())
+                        .collect();
+                    format!("{} {} {}", name, ports.join(" "), circuit.name)
+                }
+                _ => panic!("Unsupported Gate"),
+            };
+            res.push(s);
+        }
+
+        /* ------------------- footer ------------------- */
+        res.push(format!(".ends {}", c.name));
+
+        return res.join("\n");
+    }
+}
+
+impl RsfqlibWrspice {
+    // Circuit をトップレベルとしてシミュレーション可能なデッキを生成する
+    pub fn deck<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>(
+        circuit: &Circuit<N_I, N_CI, N_O, N_CO>,
+        deck: &WrspiceDeck,
+    ) -> String {
+        let c = &circuit.netlist;
+        let mut res = Vec::new();
+
+        /* ------------------- header ------------------- */
+        res.push(format!("* WRspice deck for {}", c.name));
+        for path in deck.includes.iter() {
+            res.push(format!(".include {}", path));
+        }
+        res.push(deck.model.clone());
+        res.push(String::new());
+
+        /* ------------------- subcircuit ------------------- */
+        // 使われているサブサーキットも下位から順に含める
+        let circuits = hierarchy(c);
+        let netlists: Vec<&Netlist> = circuits.iter().map(|n| n.as_ref()).collect();
+        res.push(Self::generate_design(&netlists));
+        res.push(String::new());
+
+        /* ------------------- bias ------------------- */
+        if let Some((node, voltage)) = &deck.bias {
+            res.push("* bias".to_string());
+            res.push(format!(
+                "VBIAS {} 0 pwl(0 0 {}p {})",
+                node, deck.bias_ramp_ps, voltage
+            ));
+        }
+
+        /* ------------------- stimuli ------------------- */
+        res.push("* stimuli".to_string());
+        let dc_ports = c.dc_ports();
+        for (port, times) in deck.stimuli.iter() {
+            assert!(
+                c.input_ports().any(|p| p == port),
+                "{}",
+                format!("Stimulus port `{}` is not an input of `{}`!", port, c.name).red()
+            );
+            // DCSFQ につながるポートには SFQ パルスではなく DC 電流のステップを入れる
+            let pwl = if dc_ports.contains(port.as_str()) {
+                dc_pwl(times, &deck.dc_amplitude, deck.dc_width_ps)
+            } else {
+                sfq_pwl(times, &deck.pulse_amplitude)
+            };
+            res.push(format!("I{} 0 {} {}", port, port, pwl));
+        }
+
+        /* ------------------- instance ------------------- */
+        res.push("* circuit under test".to_string());
+        let ports = port_names(c);
+        res.push(format!("XDUT {} {}", ports.join(" "), c.name));
+        // 出力ポートは負荷抵抗で終端する
+        for port in c.output_ports() {
+            res.push(format!("RLOAD_{} {} 0 2ohm", port, port));
+        }
+        res.push(String::new());
+
+        /* ------------------- analysis ------------------- */
+        res.push(format!(".tran {} {}", deck.tran_step, deck.tran_stop));
+        res.push(".control".to_string());
+        if deck.control.is_empty() {
+            res.push("run".to_string());
+        } else {
+            res.extend(deck.control.iter().cloned());
+        }
+        res.push(".endc".to_string());
+
+        return res.join("\n");
+    }
+}
+
+// SPICE ヘッダのポート順: inputs, counter_outputs, outputs, counter_inputs
+fn port_names(c: &Netlist) -> Vec<&str> {
+    return c
+        .input_ports()
+        .chain(c.output_ports())
+        .map(|s| s.as_str())
+        .collect();
+}
+
+// 三角波の電流パルス列 (幅 2ps) を pwl で表す
+// 時刻は並べ替え, 0ps より前に始まるパルスは 0ps から立ち上げる. 重なるパルスは扱えない
+fn sfq_pwl(times: &[f64], amplitude: &str) -> String {
+    const HALF_WIDTH: f64 = 1.0;
+    let mut times = times.to_vec();
+    times.sort_by(f64::total_cmp);
+    times.dedup();
+    assert!(
+        times.first().is_none_or(|t| *t >= 0.0),
+        "{}",
+        "SFQ pulses must not be placed before 0ps!".red()
+    );
+    for pair in times.windows(2) {
+        assert!(
+            pair[1] - pair[0] >= 2.0 * HALF_WIDTH,
+            "{}",
+            format!(
+                "SFQ pulses at {}ps and {}ps are closer than the pulse width {}ps!",
+                pair[0],
+                pair[1],
+                2.0 * HALF_WIDTH
+            )
+            .red()
+        );
+    }
+
+    let mut points: Vec<(f64, &str)> = vec![(0.0, "0")];
+    for t in times {
+        // 前の点と同じ時刻から立ち上がるときは前の点を置き換える
+        let start = (t - HALF_WIDTH).max(0.0);
+        if points.last().unwrap().0 >= start {
+            points.pop();
+        }
+        points.push((start, "0"));
+        // 0ps のパルスは立ち上がりの点を持たない
+        if start == t {
+            points.pop();
+        }
+        points.push((t, amplitude));
+        points.push((t + HALF_WIDTH, "0"));
+    }
+    let points: Vec<String> = points
+        .iter()
+        .map(|(t, v)| format!("{}p {}", t, v))
+        .collect();
+    return format!("pwl({})", points.join(" "));
+}
+
+// 各時刻に立ち上がり, width_ps だけ保持してから戻る DC 電流 (立ち上がり, 立ち下がりは 5ps)
+fn dc_pwl(times: &[f64], amplitude: &str, width_ps: f64) -> String {
+    const EDGE: f64 = 5.0;
+    let mut times = times.to_vec();
+    times.sort_by(f64::total_cmp);
+    times.dedup();
+    assert!(
+        times.first().is_none_or(|t| *t >= 0.0),
+        "{}",
+        "DC pulses must not be placed before 0ps!".red()
+    );
+    for pair in times.windows(2) {
+        assert!(
+            pair[1] - pair[0] >= width_ps + 2.0 * EDGE,
+            "{}",
+            format!(
+                "DC pulses at {}ps and {}ps are closer than the pulse width {}ps!",
+                pair[0],
+                pair[1],
+                width_ps + 2.0 * EDGE
+            )
+            .red()
+        );
+    }
+
+    let mut points: Vec<(f64, &str)> = vec![(0.0, "0")];
+    for t in times {
+        // 前の点と同じ時刻から立ち上がるときは前の点を置き換える
+        let start = (t - EDGE).max(0.0);
+        if points.last().unwrap().0 >= start {
+            points.pop();
+        }
+        points.push((start, "0"));
+        // 0ps のパルスは立ち上がりの点を持たない
+        if start == t {
+            points.pop();
+        }
+        points.push((t, amplitude));
+        points.push((t + width_ps, amplitude));
+        points.push((t + width_ps + EDGE, "0"));
+    }
+    let points: Vec<String> = points
+        .iter()
+        .map(|(t, v)| format!("{}p {}", t, v))
+        .collect();
+    return format!("pwl({})", points.join(" "));
+}
+
+// WRspice デッキの設定
+#[derive(Debug, Clone)]
+pub struct WrspiceDeck {
+    includes: Vec<String>,
+    model: String,
+    bias: Option<(String, String)>,
+    bias_ramp_ps: f64,
+    stimuli: Vec<(String, Vec<f64>)>,
+    pulse_amplitude: String,
+    dc_amplitude: String,
+    dc_width_ps: f64,
+    tran_step: String,
+    tran_stop: String,
+    control: Vec<String>,
+}
+
+impl Default for WrspiceDeck {
+    fn default() -> Self {
+        Self {
+            includes: Vec::new(),
+            // MIT-LL SFQ5ee 相当の接合モデル
+            model: ".model jjmit jj(rtype=1, cct=1, icon=10m, vg=2.8m, delv=0.08m, \
+                    icrit=0.1m, r0=160, rn=16, cap=0.07p, force=1)"
+                .to_string(),
+            bias: None,
+            bias_ramp_ps: 10.0,
+            stimuli: Vec::new(),
+            pulse_amplitude: "600u".to_string(),
+            dc_amplitude: "600u".to_string(),
+            dc_width_ps: 10.0,
+            tran_step: "0.25p".to_string(),
+            tran_stop: "200p".to_string(),
+            control: Vec::new(),
+        }
+    }
+}
+
+impl WrspiceDeck {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    // セルライブラリなどの .include
+    pub fn include(mut self, path: &str) -> Self {
+        self.includes.push(path.to_string());
+        return self;
+    }
+
+    // 接合モデルの .model 行を置き換える
+    pub fn model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        return self;
+    }
+
+    // node に bias 電圧を ramp_ps かけて印加する
+    pub fn bias(mut self, node: &str, voltage: &str, ramp_ps: f64) -> Self {
+        self.bias = Some((node.to_string(), voltage.to_string()));
+        self.bias_ramp_ps = ramp_ps;
+        return self;
+    }
+
+    // 入力ポートに times (ps) で SFQ パルスを入れる
+    // DCSFQ につながるポートでは times は DC 電流の立ち上がりの時刻になる
+    pub fn pulses(mut self, port: &str, times: &[f64]) -> Self {
+        self.stimuli.push((port.to_string(), times.to_vec()));
+        return self;
+    }
+
+    pub fn pulse_amplitude(mut self, amplitude: &str) -> Self {
+        self.pulse_amplitude = amplitude.to_string();
+        return self;
+    }
+
+    // DC 電流のステップの振幅と保持時間 (ps)
+    pub fn dc_level(mut self, amplitude: &str, width_ps: f64) -> Self {
+        self.dc_amplitude = amplitude.to_string();
+        self.dc_width_ps = width_ps;
+        return self;
+    }
+
+    pub fn tran(mut self, step: &str, stop: &str) -> Self {
+        self.tran_step = step.to_string();
+        self.tran_stop = stop.to_string();
+        return self;
+    }
+
+    // .control ブロック内のコマンド (未指定なら run のみ)
+    pub fn control(mut self, command: &str) -> Self {
+        self.control.push(command.to_string());
+        return self;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dc_pwl, sfq_pwl};
+
+    #[test]
+    fn pulses_are_sorted_and_clamped() {
+        assert_eq!(
+            sfq_pwl(&[5.0, 0.5, 3.0, 5.0], "600u"),
+            "pwl(0p 0 0.5p 600u 1.5p 0 2p 0 3p 600u 4p 0 5p 600u 6p 0)"
+        );
+        assert_eq!(sfq_pwl(&[0.0], "600u"), "pwl(0p 600u 1p 0)");
+        assert_eq!(sfq_pwl(&[], "600u"), "pwl(0p 0)");
+    }
+
+    #[test]
+    #[should_panic]
+    fn overlapping_pulses() {
+        sfq_pwl(&[10.0, 11.0], "600u");
+    }
+
+    #[test]
+    #[should_panic]
+    fn negative_time() {
+        sfq_pwl(&[-1.0], "600u");
+    }
+
+    #[test]
+    fn dc_pulses_are_sorted_and_clamped() {
+        assert_eq!(
+            dc_pwl(&[30.0, 2.0], "1m", 10.0),
+            "pwl(0p 0 2p 1m 12p 1m 17p 0 25p 0 30p 1m 40p 1m 45p 0)"
+        );
+        assert_eq!(
+            dc_pwl(&[0.0, 20.0], "1m", 10.0),
+            "pwl(0p 1m 10p 1m 15p 0 20p 1m 30p 1m 35p 0)"
+        );
+        assert_eq!(dc_pwl(&[], "1m", 10.0), "pwl(0p 0)");
+    }
+
+    #[test]
+    #[should_panic]
+    fn overlapping_dc_pulses() {
+        dc_pwl(&[10.0, 29.0], "1m", 10.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn negative_dc_time() {
+        dc_pwl(&[-1.0], "1m", 10.0);
+    }
+}
@@ -0,0 +1,357 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::circuit::Circuit;
+use crate::error::ParseError;
+use crate::gate::Gate;
+use crate::id::WireID;
+use crate::netlist::Netlist;
+
+// SPICE / Verilog の構造記述 (モジュールとインスタンス) から Netlist を組み立てる
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Dir {
+    Input,
+    Output,
+}
+
+pub(crate) struct Module {
+    pub(crate) line: usize,
+    pub(crate) name: String,
+    pub(crate) ports: Vec<String>,
+    // 方向が書かれていない場合 (SPICE) は内部の接続から推定する
+    pub(crate) dirs: Option<HashMap<String, Dir>>,
+    pub(crate) instances: Vec<Instance>,
+}
+
+pub(crate) struct Instance {
+    pub(crate) line: usize,
+    pub(crate) name: String,
+    pub(crate) cell: String,
+    pub(crate) conns: Conns,
+}
+
+pub(crate) enum Conns {
+    Ordered(Vec<String>),
+    Named(Vec<(String, String)>),
+}
+
+// RSFQlib のセル名からゲートの種類を得る
+// SPICE は THmitll_AND2, Verilog は THmitll_AND2_v3p0_extracted の形
+pub(crate) fn rsfqlib_kind(cell: &str) -> Option<&'static str> {
+    let cell = cell.strip_prefix("THmitll_")?;
+    let cell = cell.strip_suffix("_v3p0_extracted").unwrap_or(cell);
+    return match cell {
+        "JTL" => Some("JTL"),
+        "SPLIT" => Some("SPLIT"),
+        "MERGE" => Some("MERGE"),
+        "SPLIT3" => Some("SPLIT3"),
+        "SPLIT4" => Some("SPLIT4"),
+        "MERGE3" => Some("MERGE3"),
+        "MERGE4" => Some("MERGE4"),
+        "AND2" => Some("AND"),
+        "OR2" => Some("OR"),
+        "XOR" => Some("XOR"),
+        "XNOR" => Some("XNOR"),
+        "NOT" => Some("NOT"),
+        "DFF" => Some("DFF"),
+        "NDRO" => Some("NDRO"),
+        "DFFR" => Some("DFFR"),
+        "DFFR_SYNC" => Some("DFFR_SYNC"),
+        "DFFC" => Some("DFFC"),
+        "NDROC" => Some("NDROC"),
+        "BUFF" => Some("BUFF"),
+        "FA" => Some("FIRST_ARRIVAL"),
+        "LA" => Some("LAST_ARRIVAL"),
+        "INH" => Some("INHIBIT"),
+        "TFF" => Some("TFF"),
+        "TFF2" => Some("TFF2"),
+        "TFFR" => Some("TFFR"),
+        "DCSFQ" => Some("DCSFQ"),
+        "SFQDC" => Some("SFQDC"),
+        "ALWAYS0_ASYNC_NOA" => Some("ZERO_ASYNC"),
+        "ALWAYS0_SYNC" => Some("ZERO_SYNC"),
+        "ALWAYS1_SYNC" => Some("ONE_SYNC"),
+        _ => None,
+    };
+}
+
+// インスタンスの中身: ゲートの種類またはサブサーキット
+enum Body {
+    Gate(&'static str),
+    Subcircuit(Arc<Netlist>),
+}
+
+// 読み込み済みのモジュールとそのポート (記述順, 方向付き)
+type Loaded = (Arc<Netlist>, Vec<(String, Dir)>);
+
+struct Loader<'a> {
+    modules: HashMap<&'a str, &'a Module>,
+    primitive: fn(&str) -> Option<&'static str>,
+    terminate_open: bool,
+    loaded: HashMap<String, Loaded>,
+    stack: Vec<String>,
+}
+
+// 他から使われていないモジュール (複数あれば最後のもの) をトップとして読み込む
+// terminate_open が true なら, レシーバのない配線を終端する
+pub(crate) fn load(
+    modules: &[Module],
+    primitive: fn(&str) -> Option<&'static str>,
+    terminate_open: bool,
+) -> Result<Netlist, ParseError> {
+    let mut loader = Loader {
+        modules: HashMap::new(),
+        primitive,
+        terminate_open,
+        loaded: HashMap::new(),
+        stack: Vec::new(),
+    };
+    for m in modules {
+        if loader.modules.insert(&m.name, m).is_some() {
+            return Err(ParseError::at(
+                m.line,
+                format!("Module `{}` is defined twice!", m.name),
+            ));
+        }
+    }
+    let used: HashSet<&str> = modules
+        .iter()
+        .flat_map(|m| m.instances.iter().map(|i| i.cell.as_str()))
+        .collect();
+    let top = modules
+        .iter()
+        .rev()
+        .find(|m| !used.contains(m.name.as_str()))
+        .or(modules.last())
+        .ok_or_else(|| ParseError::new("No module is defined!"))?;
+
+    let (netlist, _) = loader.load(&top.name, top.line)?;
+    return Ok(Arc::unwrap_or_clone(netlist));
+}
+
+// 物理ポートを型パラメータで inputs / counter_outputs, outputs / counter_inputs に分ける
+pub(crate) fn typed<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>(
+    mut netlist: Netlist,
+) -> Result<Circuit<N_I, N_CI, N_O, N_CO>, ParseError> {
+    if netlist.inputs.len() != N_I + N_CO || netlist.outputs.len() != N_O + N_CI {
+        return Err(ParseError::new(format!(
+            "Circuit `{}` has {} input and {} output ports, but {} and {} are expected!",
+            netlist.name,
+            netlist.inputs.len(),
+            netlist.outputs.len(),
+            N_I + N_CO,
+            N_O + N_CI
+        )));
+    }
+    netlist.counter_outputs = netlist.inputs.split_off(N_I);
+    netlist.counter_inputs = netlist.outputs.split_off(N_O);
+    // 逆向きの入力ポートは CounterWire
+    let counter: Vec<WireID> = netlist
+        .wire_names
+        .iter()
+        .filter(|(_, name)| netlist.counter_inputs.contains(name))
+        .map(|(wid, _)| *wid)
+        .collect();
+    netlist.counter_wires.extend(counter);
+    return Circuit::from_netlist(netlist).map_err(ParseError::new);
+}
+
+impl Loader<'_> {
+    fn load(&mut self, name: &str, line: usize) -> Result<Loaded, ParseError> {
+        if let Some(loaded) = self.loaded.get(name) {
+            return Ok(loaded.clone());
+        }
+        if self.stack.iter().any(|s| s == name) {
+            return Err(ParseError::at(
+                line,
+                format!("Module `{}` instantiates itself!", name),
+            ));
+        }
+        let m = *self
+            .modules
+            .get(name)
+            .ok_or_else(|| ParseError::at(line, format!("Module `{}` is not defined!", name)))?;
+        self.stack.push(name.to_string());
+
+        /* ------------------- instances ------------------- */
+        // (名前, 入力の配線, 出力の配線, 中身)
+        let mut gates: Vec<(String, Vec<String>, Vec<String>, Body)> = Vec::new();
+        let mut names = HashSet::new();
+        for inst in m.instances.iter() {
+            let err = |msg: String| ParseError::at(inst.line, msg);
+            if !names.insert(inst.name.as_str()) {
+                return Err(err(format!("Instance `{}` is defined twice!", inst.name)));
+            }
+            // ピン名と方向
+            let (pins, body): (Vec<(String, Dir)>, Body) = match (self.primitive)(&inst.cell) {
+                Some(kind) => {
+                    let (ins, outs) = Gate::pin_names(kind).unwrap();
+                    let pins = ins
+                        .iter()
+                        .map(|p| (p.to_string(), Dir::Input))
+                        .chain(outs.iter().map(|p| (p.to_string(), Dir::Output)))
+                        .collect();
+                    (pins, Body::Gate(kind))
+                }
+                None if self.modules.contains_key(inst.cell.as_str()) => {
+                    let (child, ports) = self.load(&inst.cell, inst.line)?;
+                    (ports, Body::Subcircuit(child))
+                }
+                None => return Err(err(format!("Unknown cell `{}`!", inst.cell))),
+            };
+
+            let nets: Vec<String> = match &inst.conns {
+                Conns::Ordered(args) => {
+                    if args.len() != pins.len() {
+                        return Err(err(format!(
+                            "`{}` has {} connections, but `{}` has {} pins!",
+                            inst.name,
+                            args.len(),
+                            inst.cell,
+                            pins.len()
+                        )));
+                    }
+                    args.clone()
+                }
+                Conns::Named(conns) => {
+                    let mut nets = Vec::new();
+                    for (pin, _) in pins.iter() {
+                        let net = conns.iter().find(|(p, _)| p == pin).ok_or_else(|| {
+                            err(format!(
+                                "Pin `{}` of `{}` is not connected!",
+                                pin, inst.name
+                            ))
+                        })?;
+                        nets.push(net.1.clone());
+                    }
+                    if let Some((p, _)) =
+                        conns.iter().find(|(p, _)| !pins.iter().any(|q| &q.0 == p))
+                    {
+                        return Err(err(format!("`{}` has no pin `{}`!", inst.cell, p)));
+                    }
+                    nets
+                }
+            };
+            let (mut ins, mut outs) = (Vec::new(), Vec::new());
+            for ((_, dir), net) in pins.iter().zip(nets) {
+                match dir {
+                    Dir::Input => ins.push(net),
+                    Dir::Output => outs.push(net),
+                }
+            }
+            gates.push((inst.name.clone(), ins, outs, body));
+        }
+
+        /* ------------------- ports ------------------- */
+        let driven: HashSet<&str> = gates
+            .iter()
+            .flat_map(|g| g.2.iter().map(|s| s.as_str()))
+            .collect();
+        let received: HashSet<&str> = gates
+            .iter()
+            .flat_map(|g| g.1.iter().map(|s| s.as_str()))
+            .collect();
+        let mut ports = Vec::new();
+        for port in m.ports.iter() {
+            let dir = match &m.dirs {
+                Some(dirs) => *dirs.get(port).ok_or_else(|| {
+                    ParseError::at(m.line, format!("Direction of port `{}` is missing!", port))
+                })?,
+                None if driven.contains(port.as_str()) => Dir::Output,
+                None if received.contains(port.as_str()) => Dir::Input,
+                None => {
+                    return Err(ParseError::at(
+                        m.line,
+                        format!("Port `{}` of `{}` is not connected!", port, name),
+                    ));
+                }
+            };
+            ports.push((port.clone(), dir));
+        }
+
+        // レシーバのない配線を終端する
+        if self.terminate_open {
+            let open: Vec<String> = ports
+                .iter()
+                .filter(|(_, d)| *d == Dir::Input)
+                .map(|(p, _)| p.as_str())
+                .chain(driven.iter().copied())
+                .filter(|net| {
+                    !received.contains(net)
+                        && !ports.iter().any(|(p, d)| p == net && *d == Dir::Output)
+                })
+                .map(String::from)
+                .collect();
+            let mut k = 0;
+            for net in open {
+                let gate_name = loop {
+                    k += 1;
+                    let gate_name = format!("XTERMINATE{}", k);
+                    if !names.contains(gate_name.as_str()) {
+                        break gate_name;
+                    }
+                };
+                gates.push((gate_name, vec![net], Vec::new(), Body::Gate("TERMINATE")));
+            }
+        }
+
+        /* ------------------- netlist ------------------- */
+        let mut netlist = Netlist {
+            name: name.to_string(),
+            inputs: ports
+                .iter()
+                .filter(|(_, d)| *d == Dir::Input)
+                .map(|(p, _)| p.clone())
+                .collect(),
+            counter_inputs: Vec::new(),
+            outputs: ports
+                .iter()
+                .filter(|(_, d)| *d == Dir::Output)
+                .map(|(p, _)| p.clone())
+                .collect(),
+            counter_outputs: Vec::new(),
+            wire_names: HashMap::new(),
+            counter_wires: HashSet::new(),
+            gates: Vec::new(),
+        };
+        // 配線名ごとに 1 つの WireID を割り当てる
+        let mut ids: HashMap<String, WireID> = HashMap::new();
+        let mut wire = |net: &str, netlist: &mut Netlist| -> WireID {
+            if let Some(wid) = ids.get(net) {
+                return *wid;
+            }
+            let wid = WireID(ids.len() as u32 + 1);
+            ids.insert(net.to_string(), wid);
+            netlist.wire_names.insert(wid, net.to_string());
+            return wid;
+        };
+        for (port, _) in ports.iter() {
+            wire(port, &mut netlist);
+        }
+        for (gate_name, ins, outs, body) in gates {
+            let inputs: Vec<WireID> = ins.iter().map(|n| wire(n, &mut netlist)).collect();
+            let outputs: Vec<WireID> = outs.iter().map(|n| wire(n, &mut netlist)).collect();
+            let gate = match body {
+                Body::Gate(kind) => {
+                    Gate::from_pins(kind, gate_name, &[inputs, outputs].concat()).unwrap()
+                }
+                Body::Subcircuit(circuit) => Gate::Subcircuit {
+                    name: gate_name,
+                    inputs,
+                    outputs,
+                    circuit,
+                },
+            };
+            netlist.gates.push(gate);
+        }
+        netlist
+            .check()
+            .map_err(|e| ParseError::at(m.line, format!("In module `{}`: {}", name, e)))?;
+
+        self.stack.pop();
+        let loaded = (Arc::new(netlist), ports);
+        self.loaded.insert(name.to_string(), loaded.clone());
+        return Ok(loaded);
+    }
+}
@@ -0,0 +1,649 @@
+use std::collections::{HashMap, HashSet};
+
+use super::Backend;
+use crate::gate::Gate;
+use crate::netlist::Netlist;
+
+// 回路を左から右へ段ごとに配置し, SVG の回路図として出力する
+pub struct Svg;
+
+const COL_W: f64 = 160.0; // 列の幅
+const GATE_W: f64 = 70.0; // ゲートの幅
+const PIN_GAP: f64 = 20.0; // ピンの間隔
+const ROW_GAP: f64 = 40.0; // 同じ列のノードの間隔
+const MARGIN: f64 = 40.0;
+
+enum Symbol<'a> {
+    Input,
+    Output,
+    Gate(&'a Gate),
+}
+
+struct Node<'a> {
+    symbol: Symbol<'a>,
+    label: &'a str,
+    inputs: Vec<(&'a str, &'a str)>, // (ピン名, 配線名)
+    outputs: Vec<(&'a str, &'a str)>,
+    layer: usize,
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+}
+
+impl Node<'_> {
+    // クロック以外の入力ピン
+    fn data_inputs(&self) -> usize {
+        return self.inputs.iter().filter(|(pin, _)| *pin != "clk").count();
+    }
+
+    // 入力ピンの座標. クロックは下辺から入る
+    fn input_pos(&self, idx: usize) -> (f64, f64) {
+        if self.inputs[idx].0 == "clk" {
+            return (self.x + self.w / 2.0, self.y + self.h);
+        }
+        let n = self.data_inputs();
+        let i = self.inputs[..idx]
+            .iter()
+            .filter(|(pin, _)| *pin != "clk")
+            .count();
+        return (
+            self.x,
+            (self.y + self.h * (i + 1) as f64 / (n + 1) as f64).round(),
+        );
+    }
+
+    fn output_pos(&self, idx: usize) -> (f64, f64) {
+        let n = self.outputs.len();
+        return (
+            self.x + self.w,
+            (self.y + self.h * (idx + 1) as f64 / (n + 1) as f64).round(),
+        );
+    }
+}
+
+impl Backend for Svg {
+    fn generate_netlist(c: &Netlist) -> String {
+        let (body, width, height) = render(c);
+        return document(&c.name, body, width, height);
+    }
+
+    // 各回路の図を縦に並べる
+    fn generate_design(netlists: &[&Netlist]) -> String {
+        let mut body = Vec::new();
+        let (mut width, mut height): (f64, f64) = (0.0, 0.0);
+        for c in netlists {
+            let (b, w, h) = render(c);
+            body.push(format!(
+                "<svg y=\"{}\" width=\"{}\" height=\"{}\">",
+                height, w, h
+            ));
+            body.extend(b);
+            body.push("</svg>".to_string());
+            width = width.max(w);
+            height += h;
+        }
+        let title = netlists.last().map(|c| c.name.as_str()).unwrap_or("");
+        return document(title, body, width, height);
+    }
+}
+
+// (要素, 幅, 高さ)
+fn render(c: &Netlist) -> (Vec<String>, f64, f64) {
+    let mut nodes = build_nodes(c);
+    let back_edges = assign_layers(&mut nodes);
+    place(&mut nodes, &back_edges);
+    return draw(c, &nodes, &back_edges);
+}
+
+fn document(title: &str, body: Vec<String>, width: f64, height: f64) -> String {
+    let mut res = Vec::new();
+    res.push(format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" font-family=\"Helvetica, sans-serif\">",
+        width, height, width, height
+    ));
+    res.push(format!("<title>{}</title>", escape(title)));
+    res.push("<rect width=\"100%\" height=\"100%\" fill=\"white\"/>".to_string());
+    res.extend(body);
+    res.push("</svg>".to_string());
+    return res.join("\n");
+}
+
+fn build_nodes(c: &Netlist) -> Vec<Node<'_>> {
+    let mut nodes = Vec::new();
+    let node = |symbol, label, inputs, outputs, w, h| Node {
+        symbol,
+        label,
+        inputs,
+        outputs,
+        layer: 0,
+        x: 0.0,
+        y: 0.0,
+        w,
+        h,
+    };
+    for port in c.input_ports() {
+        nodes.push(node(
+            Symbol::Input,
+            port.as_str(),
+            vec![],
+            vec![("", port.as_str())],
+            60.0,
+            24.0,
+        ));
+    }
+    for gate in c.gates.iter() {
+        let inputs: Vec<(&str, &str)> = gate
+            .inputs()
+            .into_iter()
+            .map(|(pin, wid)| (pin, c.wire_name(&wid)))
+            .collect();
+        let outputs: Vec<(&str, &str)> = gate
+            .outputs()
+            .into_iter()
+            .map(|(pin, wid)| (pin, c.wire_name(&wid)))
+            .collect();
+        let data = inputs.iter().filter(|(pin, _)| *pin != "clk").count();
+        let pins = data.max(outputs.len()) as f64;
+        let (w, h) = match gate {
+            Gate::Split { .. } | Gate::Merge { .. } => (30.0, 40.0),
+            Gate::Split3 { .. }
+            | Gate::Split4 { .. }
+            | Gate::Merge3 { .. }
+            | Gate::Merge4 { .. } => (30.0, pins * PIN_GAP),
+            Gate::Jtl { .. } | Gate::Buff { .. } => (40.0, 24.0),
+            Gate::ZeroAsync { .. } | Gate::Terminate { .. } => (24.0, 24.0),
+            _ => (GATE_W, (pins + 1.0) * PIN_GAP),
+        };
+        nodes.push(node(Symbol::Gate(gate), gate.name(), inputs, outputs, w, h));
+    }
+    for port in c.output_ports() {
+        nodes.push(node(
+            Symbol::Output,
+            port.as_str(),
+            vec![("", port.as_str())],
+            vec![],
+            60.0,
+            24.0,
+        ));
+    }
+    return nodes;
+}
+
+// (ドライバのノード, 出力ピン番号)
+fn drivers<'a>(nodes: &[Node<'a>]) -> HashMap<&'a str, (usize, usize)> {
+    let mut res = HashMap::new();
+    for (i, node) in nodes.iter().enumerate() {
+        for (j, (_, net)) in node.outputs.iter().enumerate() {
+            res.insert(*net, (i, j));
+        }
+    }
+    return res;
+}
+
+// クロック付きゲートの段ごとに列を決める. ループは DFS の後退辺を無視して切る
+// 段 s の非同期のセル (SPLIT, JTL など) は 1 つの列にまとめ, その右に段 s のクロック付きゲートを並べる
+// 戻り値は後退辺 (ドライバ, レシーバ) の集合
+fn assign_layers(nodes: &mut [Node]) -> HashSet<(usize, usize)> {
+    let drivers = drivers(nodes);
+    let preds: Vec<Vec<usize>> = nodes
+        .iter()
+        .map(|node| {
+            node.inputs
+                .iter()
+                .filter_map(|(_, net)| drivers.get(net).map(|(i, _)| *i))
+                .collect()
+        })
+        .collect();
+
+    // 後退辺の検出 (0: 未訪問, 1: 探索中, 2: 完了)
+    let mut state = vec![0u8; nodes.len()];
+    let mut back_edges = HashSet::new();
+    for root in 0..nodes.len() {
+        if state[root] != 0 {
+            continue;
+        }
+        let mut stack = vec![(root, 0)];
+        state[root] = 1;
+        while let Some((v, k)) = stack.pop() {
+            if k < preds[v].len() {
+                stack.push((v, k + 1));
+                let u = preds[v][k];
+                match state[u] {
+                    0 => {
+                        state[u] = 1;
+                        stack.push((u, 0));
+                    }
+                    1 => {
+                        back_edges.insert((u, v));
+                    }
+                    _ => {}
+                }
+            } else {
+                state[v] = 2;
+            }
+        }
+    }
+
+    // 後退辺を除いた DAG 上で, 入力から通るクロック付きゲートの最大数
+    let clocked: Vec<bool> = nodes
+        .iter()
+        .map(|node| {
+            matches!(node.symbol, Symbol::Gate(_))
+                && node.inputs.iter().any(|(pin, _)| *pin == "clk")
+        })
+        .collect();
+    let mut stage: Vec<Option<usize>> = vec![None; nodes.len()];
+    fn deepest(
+        v: usize,
+        preds: &[Vec<usize>],
+        clocked: &[bool],
+        back_edges: &HashSet<(usize, usize)>,
+        stage: &mut Vec<Option<usize>>,
+    ) -> usize {
+        if let Some(s) = stage[v] {
+            return s;
+        }
+        let mut s = 0;
+        for &u in preds[v].iter() {
+            if !back_edges.contains(&(u, v)) {
+                s = s.max(deepest(u, preds, clocked, back_edges, stage) + clocked[u] as usize);
+            }
+        }
+        stage[v] = Some(s);
+        return s;
+    }
+    for v in 0..nodes.len() {
+        deepest(v, &preds, &clocked, &back_edges, &mut stage);
+    }
+
+    // 入力ポートは最初の列, 出力ポートは最後の列に揃える
+    let mut layer: Vec<usize> = nodes
+        .iter()
+        .enumerate()
+        .map(|(v, node)| match node.symbol {
+            Symbol::Input => 0,
+            _ if clocked[v] => 2 * stage[v].unwrap() + 2,
+            _ => 2 * stage[v].unwrap() + 1,
+        })
+        .collect();
+    let last = nodes
+        .iter()
+        .zip(layer.iter())
+        .filter(|(node, _)| !matches!(node.symbol, Symbol::Output))
+        .map(|(_, l)| *l)
+        .max()
+        .unwrap_or(0);
+    for (node, l) in nodes.iter().zip(layer.iter_mut()) {
+        if matches!(node.symbol, Symbol::Output) {
+            *l = last + 1;
+        }
+    }
+
+    // 空いた列を詰める
+    let mut used: Vec<usize> = layer.clone();
+    used.sort();
+    used.dedup();
+    for (node, l) in nodes.iter_mut().zip(layer) {
+        node.layer = used.binary_search(&l).unwrap();
+    }
+    return back_edges;
+}
+
+// 列ごとにドライバの位置の重心順で並べて座標を決める
+// 同じ列のドライバ (非同期のセルの連鎖) からの配線はそのドライバのすぐ下に並べる
+fn place(nodes: &mut [Node], back_edges: &HashSet<(usize, usize)>) {
+    let drivers = drivers(nodes);
+    let n_layers = nodes.iter().map(|n| n.layer).max().unwrap_or(0) + 1;
+    let mut order: Vec<f64> = vec![0.0; nodes.len()];
+    for layer in 0..n_layers {
+        let mut members: Vec<usize> = (0..nodes.len())
+            .filter(|&i| nodes[i].layer == layer)
+            .collect();
+        // 後退辺を除けば同じ列の中の配線もループを作らないので, ドライバから順に決まる
+        let mut keys: HashMap<usize, f64> = HashMap::new();
+        while keys.len() < members.len() {
+            for &i in members.iter() {
+                if keys.contains_key(&i) {
+                    continue;
+                }
+                let mut ys = Vec::new();
+                let mut ready = true;
+                for (_, net) in nodes[i].inputs.iter() {
+                    let Some(&(d, _)) = drivers.get(net) else {
+                        continue;
+                    };
+                    if nodes[d].layer < layer {
+                        ys.push(order[d]);
+                    } else if nodes[d].layer == layer && !back_edges.contains(&(d, i)) {
+                        match keys.get(&d) {
+                            Some(k) => ys.push(k + 0.5),
+                            None => ready = false,
+                        }
+                    }
+                }
+                if ready {
+                    let key = if ys.is_empty() {
+                        i as f64
+                    } else {
+                        ys.iter().sum::<f64>() / ys.len() as f64
+                    };
+                    keys.insert(i, key);
+                }
+            }
+        }
+        members.sort_by(|a, b| keys[a].partial_cmp(&keys[b]).unwrap());
+
+        let mut y = MARGIN;
+        for &i in members.iter() {
+            let node = &mut nodes[i];
+            node.x = MARGIN + layer as f64 * COL_W + (GATE_W - node.w) / 2.0;
+            node.y = y;
+            order[i] = y + node.h / 2.0;
+            y += node.h + ROW_GAP;
+        }
+    }
+}
+
+fn escape(s: &str) -> String {
+    return s
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+}
+
+fn draw(
+    c: &Netlist,
+    nodes: &[Node],
+    back_edges: &HashSet<(usize, usize)>,
+) -> (Vec<String>, f64, f64) {
+    let width = nodes.iter().map(|n| n.x + n.w).fold(0.0, f64::max) + MARGIN;
+    let bottom = nodes.iter().map(|n| n.y + n.h).fold(0.0, f64::max);
+
+    let mut res = Vec::new();
+    let mut wires = Vec::new();
+
+    /* ------------------- wires ------------------- */
+    let drivers = drivers(nodes);
+    let clocks = c.clock_nets();
+    let mut n_back = 0;
+    for (i, node) in nodes.iter().enumerate() {
+        for (k, (_, net)) in node.inputs.iter().enumerate() {
+            let Some(&(d, j)) = drivers.get(net) else {
+                continue;
+            };
+            let (x1, y1) = nodes[d].output_pos(j);
+            let (x2, y2) = node.input_pos(k);
+            let to_clk = node.inputs[k].0 == "clk";
+            // 縦線が重ならないように配線ごとに少しずらす
+            let xm = x1 + 12.0 + ((d * 7 + j * 3) % 10) as f64 * 6.0;
+            let points = if back_edges.contains(&(d, i)) {
+                n_back += 1;
+                let yb = bottom + 20.0 + n_back as f64 * 8.0;
+                vec![
+                    (x1, y1),
+                    (xm, y1),
+                    (xm, yb),
+                    (x2 - 12.0, yb),
+                    (x2 - 12.0, y2),
+                    (x2, y2),
+                ]
+            } else if nodes[d].layer == node.layer {
+                // 同じ列のセルへは上の隙間を通って左から入る
+                let yg = node.y - ROW_GAP / 2.0;
+                vec![
+                    (x1, y1),
+                    (xm, y1),
+                    (xm, yg),
+                    (x2 - 12.0, yg),
+                    (x2 - 12.0, y2),
+                    (x2, y2),
+                ]
+            } else if to_clk {
+                let yc = y2 + 12.0;
+                vec![(x1, y1), (xm, y1), (xm, yc), (x2, yc), (x2, y2)]
+            } else {
+                vec![(x1, y1), (xm, y1), (xm, y2), (x2, y2)]
+            };
+            let style = if c.is_counter_net(net) {
+                "stroke=\"red\" stroke-width=\"2\""
+            } else if clocks.contains(net) {
+                "stroke=\"blue\" stroke-dasharray=\"4 2\""
+            } else {
+                "stroke=\"black\""
+            };
+            let points: Vec<String> = points.iter().map(|(x, y)| format!("{},{}", x, y)).collect();
+            wires.push(format!(
+                "<polyline points=\"{}\" fill=\"none\" {}/>",
+                points.join(" "),
+                style
+            ));
+            // 明示的なラベルのみ表示する
+            if !net.starts_with("_") {
+                wires.push(format!(
+                    "<text x=\"{}\" y=\"{}\" font-size=\"9\">{}</text>",
+                    x1 + 2.0,
+                    y1 - 3.0,
+                    escape(net)
+                ));
+            }
+        }
+    }
+
+    /* ------------------- symbols ------------------- */
+    let mut symbols = Vec::new();
+    for node in nodes.iter() {
+        symbols.push(symbol(node));
+    }
+
+    let height = bottom + 20.0 + n_back as f64 * 8.0 + MARGIN;
+    res.extend(wires);
+    res.extend(symbols);
+
+    return (res, width, height);
+}
+
+fn symbol(node: &Node) -> String {
+    let (x, y, w, h) = (node.x, node.y, node.w, node.h);
+    let (cx, cy) = (x + w / 2.0, y + h / 2.0);
+    let mut res = Vec::new();
+    let text = |x: f64, y: f64, size: u32, s: &str| {
+        format!(
+            "<text x=\"{}\" y=\"{}\" font-size=\"{}\" text-anchor=\"middle\" dominant-baseline=\"middle\">{}</text>",
+            x,
+            y,
+            size,
+            escape(s)
+        )
+    };
+    match node.symbol {
+        Symbol::Input | Symbol::Output => {
+            res.push(format!(
+                "<polygon points=\"{},{} {},{} {},{} {},{} {},{}\" fill=\"#eeeeee\" stroke=\"black\"/>",
+                x,
+                y,
+                x + w - 10.0,
+                y,
+                x + w,
+                cy,
+                x + w - 10.0,
+                y + h,
+                x,
+                y + h
+            ));
+            res.push(text(cx - 4.0, cy, 11, node.label));
+        }
+        Symbol::Gate(gate) => {
+            match gate {
+                Gate::Split { .. } | Gate::Split3 { .. } | Gate::Split4 { .. } => res.push(format!(
+                    "<polygon points=\"{},{} {},{} {},{} {},{}\" fill=\"white\" stroke=\"black\"/>",
+                    x,
+                    cy - 8.0,
+                    x + w,
+                    y,
+                    x + w,
+                    y + h,
+                    x,
+                    cy + 8.0
+                )),
+                Gate::Merge { .. } | Gate::Merge3 { .. } | Gate::Merge4 { .. } => res.push(format!(
+                    "<polygon points=\"{},{} {},{} {},{} {},{}\" fill=\"white\" stroke=\"black\"/>",
+                    x,
+                    y,
+                    x + w,
+                    cy - 8.0,
+                    x + w,
+                    cy + 8.0,
+                    x,
+                    y + h
+                )),
+                Gate::ZeroAsync { .. } => {
+                    res.push(format!(
+                        "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"white\" stroke=\"black\"/>",
+                        cx,
+                        cy,
+                        w / 2.0
+                    ));
+                    res.push(text(cx, cy, 11, "0"));
+                }
+                // 抵抗で接地
+                Gate::Terminate { .. } => {
+                    res.push(format!(
+                        "<polyline points=\"{},{} {},{} {},{} {},{} {},{} {},{}\" fill=\"none\" stroke=\"black\"/>",
+                        x,
+                        cy,
+                        x + 4.0,
+                        cy - 5.0,
+                        x + 10.0,
+                        cy + 5.0,
+                        x + 16.0,
+                        cy - 5.0,
+                        x + 20.0,
+                        cy,
+                        x + 20.0,
+                        cy + 8.0
+                    ));
+                    res.push(format!(
+                        "<path d=\"M{} {} h16 M{} {} h10 M{} {} h4\" stroke=\"black\"/>",
+                        x + 12.0,
+                        cy + 8.0,
+                        x + 15.0,
+                        cy + 11.0,
+                        x + 18.0,
+                        cy + 14.0
+                    ));
+                }
+                Gate::Subcircuit { .. } => {
+                    res.push(format!(
+                        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#f4f4ff\" stroke=\"black\"/>",
+                        x, y, w, h
+                    ));
+                    res.push(format!(
+                        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"black\"/>",
+                        x + 3.0,
+                        y + 3.0,
+                        w - 6.0,
+                        h - 6.0
+                    ));
+                    res.push(text(cx, cy, 11, gate.kind()));
+                }
+                _ => {
+                    res.push(format!(
+                        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"white\" stroke=\"black\"/>",
+                        x, y, w, h
+                    ));
+                    res.push(text(cx, cy, 11, gate.kind()));
+                }
+            }
+            // クロック入力の印
+            if node.inputs.iter().any(|(pin, _)| *pin == "clk") {
+                res.push(format!(
+                    "<polygon points=\"{},{} {},{} {},{}\" fill=\"black\"/>",
+                    cx - 5.0,
+                    y + h,
+                    cx + 5.0,
+                    y + h,
+                    cx,
+                    y + h - 6.0
+                ));
+            }
+            res.push(format!(
+                "<text x=\"{}\" y=\"{}\" font-size=\"8\" fill=\"gray\" text-anchor=\"middle\">{}</text>",
+                cx,
+                y - 3.0,
+                escape(node.label)
+            ));
+        }
+    }
+    return res.join("\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Svg, Symbol, assign_layers, build_nodes};
+    use crate::backends::Backend;
+    use crate::circuit::Circuit;
+    use crate::components::{Clocking, Sequential};
+    use crate::gate::Gate;
+
+    // 列はクロック付きゲートの段ごとで, クロックを配る SPLIT の連鎖は 1 列にまとまる
+    #[test]
+    fn columns_by_clocked_stage() {
+        let sr = Sequential::shift_register(3, Clocking::Concurrent);
+        let mut nodes = build_nodes(&sr.netlist);
+        assign_layers(&mut nodes);
+        let layers = |pred: &dyn Fn(&Symbol) -> bool| -> Vec<usize> {
+            let mut res: Vec<usize> = nodes
+                .iter()
+                .filter(|n| pred(&n.symbol))
+                .map(|n| n.layer)
+                .collect();
+            res.sort();
+            res.dedup();
+            return res;
+        };
+        assert_eq!(layers(&|s| matches!(s, Symbol::Input)), [0]);
+        assert_eq!(
+            layers(&|s| matches!(s, Symbol::Gate(Gate::Split { .. }))),
+            [1]
+        );
+        assert_eq!(
+            layers(&|s| matches!(s, Symbol::Gate(Gate::Dff { .. }))),
+            [2, 3, 4]
+        );
+        assert_eq!(layers(&|s| matches!(s, Symbol::Output)), [5]);
+    }
+
+    // 非同期のセルの連鎖は列を増やさない
+    #[test]
+    fn asynchronous_chain_shares_a_column() {
+        let (mut c, [a], [], [q], []) = Circuit::create(["a"], [], ["q"], [], "chain");
+        let x = c.jtl(a);
+        let x = c.jtl(x);
+        let x = c.jtl(x);
+        c.unify(x, q);
+        let mut nodes = build_nodes(&c.netlist);
+        assign_layers(&mut nodes);
+        let layers: Vec<usize> = nodes.iter().map(|n| n.layer).collect();
+        assert_eq!(layers, [0, 1, 1, 1, 2]);
+    }
+
+    // ループのある回路も描画できる
+    #[test]
+    fn feedback() {
+        let counter = Sequential::ripple_counter::<3>(3);
+        let svg = Svg::generate(&counter);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>"));
+        assert!(svg.contains(&format!("<title>{}</title>", counter.netlist.name)));
+        for gate in counter.netlist.gates.iter() {
+            if !matches!(gate, Gate::Terminate { .. }) {
+                assert!(
+                    svg.contains(&format!(">{}</text>", gate.name())),
+                    "{}",
+                    gate.name()
+                );
+            }
+        }
+    }
+}
@@ -1,21 +1,18 @@
 use colored::Colorize;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use twox_hash::XxHash32;
 
-use crate::gate::Gate;
+use crate::expr::{Expr, synthesize};
+use crate::gate::{Gate, gate_name};
 use crate::id::{CircuitID, WireID};
+use crate::netlist::Netlist;
+use crate::truth_table::TruthTable;
 use crate::wire::{CounterWire, HasWireID, Wire};
 
 pub struct Circuit<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize> {
-    pub(crate) name: String,
     id: CircuitID,
-    pub(crate) inputs: [String; N_I],
-    pub(crate) counter_inputs: [String; N_CI],
-    pub(crate) outputs: [String; N_O],
-    pub(crate) counter_outputs: [String; N_CO],
-
-    pub(crate) wire_names: HashMap<WireID, String>,
-    pub(crate) gates: Vec<Gate>,
+    pub(crate) netlist: Netlist,
 
     next_wire_id: u32,
     next_gate_id: u32,
@@ -31,7 +28,7 @@ macro_rules! define_gate_fn {
                 $arg.receive();
             )*
             // ゲート名, 出力 Wire の生成, drive
-            let gate_name = format!("X{}{}", stringify!($fn_name).to_uppercase(), self.generate_gate_id());
+            let gate_name = self.generate_gate_name(&stringify!($fn_name).to_uppercase());
             let q_name = format!("_{}_q", gate_name);
             let mut q = self.generate_wire(q_name);
             q.drive();
@@ -41,7 +38,7 @@ macro_rules! define_gate_fn {
                 $( $arg: $arg.wire_id(), )*
                 q: q.wire_id(),
             };
-            self.gates.push(gate);
+            self.netlist.gates.push(gate);
 
             return q;
         }
@@ -54,6 +51,44 @@ macro_rules! define_gate_fn {
     };
 }
 
+// 2出力ゲート関数定義用マクロ. 出力は (q1, q2) の順に返す
+macro_rules! define_gate_fn2 {
+    ($fn_name:ident, $fn_name_labeled:ident, $variant:ident, [$($arg:ident),*]) => {
+        pub fn $fn_name(&mut self, $(mut $arg: Wire),*) -> (Wire, Wire) {
+            // 入力 Wire のチェック, receive
+            $(
+                assert!($arg.circuit_id() == self.id);
+                $arg.receive();
+            )*
+            // ゲート名, 出力 Wire の生成, drive
+            let gate_name = self.generate_gate_name(&stringify!($fn_name).to_uppercase());
+            let q1_name = format!("_{}_q1", gate_name);
+            let q2_name = format!("_{}_q2", gate_name);
+            let mut q1 = self.generate_wire(q1_name);
+            let mut q2 = self.generate_wire(q2_name);
+            q1.drive();
+            q2.drive();
+            // ゲートの作成, 追加
+            let gate = Gate::$variant {
+                name: gate_name,
+                $( $arg: $arg.wire_id(), )*
+                q1: q1.wire_id(),
+                q2: q2.wire_id(),
+            };
+            self.netlist.gates.push(gate);
+
+            return (q1, q2);
+        }
+
+        pub fn $fn_name_labeled (&mut self, $($arg: Wire,)* label1: &str, label2: &str) -> (Wire, Wire) {
+            let (wire1, wire2) = self.$fn_name($($arg),*);
+            self.label(&wire1, label1);
+            self.label(&wire2, label2);
+            return (wire1, wire2);
+        }
+    };
+}
+
 impl<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>
     Circuit<N_I, N_CI, N_O, N_CO>
 {
@@ -69,29 +104,110 @@ impl<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>
         [CounterWire; N_CI],
         [CounterWire; N_O],
         [Wire; N_CO],
+    ) {
+        let (circuit, input_wires, counter_input_wires, output_wires, counter_output_wires) =
+            Self::create_ports(&inputs, &counter_inputs, &outputs, &counter_outputs, name);
+        return (
+            circuit,
+            input_wires.try_into().unwrap(),
+            counter_input_wires.try_into().unwrap(),
+            output_wires.try_into().unwrap(),
+            counter_output_wires.try_into().unwrap(),
+        );
+    }
+
+    // チップの入出力に使う create. 物理的な入力ポートに DCSFQ, 出力ポートに SFQDC をつなぎ,
+    // 変換後の SFQ 側の配線を create と同じ形で返す
+    pub fn create_chip(
+        inputs: [&str; N_I],
+        counter_inputs: [&str; N_CI],
+        outputs: [&str; N_O],
+        counter_outputs: [&str; N_CO],
+        name: &str,
+    ) -> (
+        Self,
+        [Wire; N_I],
+        [CounterWire; N_CI],
+        [CounterWire; N_O],
+        [Wire; N_CO],
+    ) {
+        let (mut circuit, input_wires, counter_input_wires, output_wires, counter_output_wires) =
+            Self::create(inputs, counter_inputs, outputs, counter_outputs, name);
+        let input_wires = input_wires.map(|a| circuit.dcsfq(a));
+        let counter_input_wires = counter_input_wires.map(|q| circuit.sfqdc_port(q));
+        let output_wires = output_wires.map(|q| circuit.sfqdc_port(q));
+        let counter_output_wires = counter_output_wires.map(|a| circuit.dcsfq(a));
+        return (
+            circuit,
+            input_wires,
+            counter_input_wires,
+            output_wires,
+            counter_output_wires,
+        );
+    }
+
+    // 出力ポートの前に SFQDC を置き, その入力を返す
+    fn sfqdc_port(&mut self, port: CounterWire) -> CounterWire {
+        let (wire, cwire) = self.gen_loop_unnamed();
+        let q = self.sfqdc(wire);
+        self.unify(q, port);
+        return cwire;
+    }
+
+    // ポート数を実行時に決める create. ポート数は型と一致しなくてもよいが,
+    // その場合は netlist を取り出して instantiate() などで使うこと
+    pub(crate) fn create_ports(
+        inputs: &[&str],
+        counter_inputs: &[&str],
+        outputs: &[&str],
+        counter_outputs: &[&str],
+        name: &str,
+    ) -> (
+        Self,
+        Vec<Wire>,
+        Vec<CounterWire>,
+        Vec<CounterWire>,
+        Vec<Wire>,
     ) {
         // 固定のシードでハッシュ化
         let cid: u32 = XxHash32::oneshot(0, name.as_bytes());
         let mut circuit = Self {
-            name: name.to_string(),
             id: CircuitID(cid),
-            inputs: inputs.map(|s| s.to_string()),
-            counter_inputs: counter_inputs.map(|s| s.to_string()),
-            outputs: outputs.map(|s| s.to_string()),
-            counter_outputs: counter_outputs.map(|s| s.to_string()),
-            wire_names: HashMap::new(),
-            gates: Vec::new(),
+            netlist: Netlist {
+                name: name.to_string(),
+                inputs: inputs.iter().map(|s| s.to_string()).collect(),
+                counter_inputs: counter_inputs.iter().map(|s| s.to_string()).collect(),
+                outputs: outputs.iter().map(|s| s.to_string()).collect(),
+                counter_outputs: counter_outputs.iter().map(|s| s.to_string()).collect(),
+                wire_names: HashMap::new(),
+                counter_wires: HashSet::new(),
+                gates: Vec::new(),
+            },
             next_wire_id: 1,
             next_gate_id: 1,
         };
 
         // 入出力に対応する Wire 生成
-        let mut input_wires = inputs.map(|s| circuit.generate_wire(s.to_string()));
-        let mut counter_input_wires =
-            counter_inputs.map(|s| circuit.generate_counter_wire(s.to_string()));
-        let mut output_wires = outputs.map(|s| circuit.generate_counter_wire(s.to_string()));
-        let mut counter_output_wires =
-            counter_outputs.map(|s| circuit.generate_wire(s.to_string()));
+        let mut input_wires: Vec<Wire> = inputs
+            .iter()
+            .map(|s| circuit.generate_wire(s.to_string()))
+            .collect();
+        let mut counter_input_wires: Vec<CounterWire> = counter_inputs
+            .iter()
+            .map(|s| circuit.generate_counter_wire(s.to_string()))
+            .collect();
+        let mut output_wires: Vec<CounterWire> = outputs
+            .iter()
+            .map(|s| circuit.generate_counter_wire(s.to_string()))
+            .collect();
+        // 出力ポートは順方向の配線
+        output_wires.iter().for_each(|w| {
+            circuit.netlist.counter_wires.remove(&w.wire_id());
+        });
+        let mut counter_output_wires: Vec<Wire> = counter_outputs
+            .iter()
+            .map(|s| circuit.generate_wire(s.to_string()))
+            .collect();
 
         // 初期条件の drive, receive
         input_wires.iter_mut().for_each(|w| w.drive());
@@ -108,15 +224,54 @@ impl<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>
         );
     }
 
+    // 読み込んだネットリストを型付けする
+    pub(crate) fn from_netlist(netlist: Netlist) -> Result<Self, String> {
+        let counts = [
+            netlist.inputs.len(),
+            netlist.counter_inputs.len(),
+            netlist.outputs.len(),
+            netlist.counter_outputs.len(),
+        ];
+        if counts != [N_I, N_CI, N_O, N_CO] {
+            return Err(format!(
+                "Port counts of `{}` are {:?}, but {:?} are expected!",
+                netlist.name,
+                counts,
+                [N_I, N_CI, N_O, N_CO]
+            ));
+        }
+        netlist.check()?;
+
+        let cid: u32 = XxHash32::oneshot(0, netlist.name.as_bytes());
+        let next_wire_id = netlist.wire_names.keys().map(|w| w.0).max().unwrap_or(0) + 1;
+        let next_gate_id = netlist.next_gate_id();
+        return Ok(Self {
+            id: CircuitID(cid),
+            netlist,
+            next_wire_id,
+            next_gate_id,
+        });
+    }
+
+    // サブサーキットをすべて展開した回路を返す
+    pub fn flatten(&self) -> Self {
+        return Self::from_netlist(self.netlist.flatten()).unwrap();
+    }
+
+    // 定数を受けるゲートを簡単化した回路を返す. サブサーキットは展開する
+    pub fn propagate_constants(&self) -> Result<Self, String> {
+        return Self::from_netlist(self.netlist.propagate_constants()?);
+    }
+
     fn generate_wire(&mut self, name: String) -> Wire {
         let wid = WireID(self.next_wire_id);
         self.next_wire_id += 1;
         assert!(
-            self.wire_names.values().all(|v| v != &name),
+            self.netlist.wire_names.values().all(|v| v != &name),
             "{}",
             format!("Wire `{}` is already exist!", name).red()
         );
-        self.wire_names.insert(wid, name);
+        self.netlist.wire_names.insert(wid, name);
         return Wire::new(wid, self.id);
     }
 
@@ -124,20 +279,36 @@ impl<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>
         let wid = WireID(self.next_wire_id);
         self.next_wire_id += 1;
         assert!(
-            self.wire_names.values().all(|v| v != &name),
+            self.netlist.wire_names.values().all(|v| v != &name),
             "{}",
             format!("Wire `{}` is already exist!", name).red()
         );
-        self.wire_names.insert(wid, name);
+        self.netlist.wire_names.insert(wid, name);
+        self.netlist.counter_wires.insert(wid);
         return CounterWire::new(wid, self.id);
     }
 
+    // 同じ名前を持つ (= 同じ配線に属する) すべての WireID の名前を変える
+    // 統合済みの配線に後から名前が付いても 1 つの配線のままになる
+    fn rename_net(&mut self, old: &str, new: &str) {
+        for name in self.netlist.wire_names.values_mut() {
+            if name == old {
+                *name = new.to_string();
+            }
+        }
+    }
+
     fn generate_gate_id(&mut self) -> u32 {
         let res = self.next_gate_id;
         self.next_gate_id += 1;
         return res;
     }
 
+    fn generate_gate_name(&mut self, kind: &str) -> String {
+        let id = self.generate_gate_id();
+        return gate_name(kind, id);
+    }
+
     // circuit.label(&wire, "hoge") でラベル付け
     #[allow(private_bounds)]
     pub fn label<T>(&mut self, wire: &T, label: &str)
@@ -145,7 +316,7 @@ impl<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>
         T: HasWireID,
     {
         assert!(wire.circuit_id() == self.id);
-        let old_name = self.wire_names.get(&wire.wire_id()).unwrap();
+        let old_name = self.netlist.wire_names.get(&wire.wire_id()).unwrap();
         assert!(
             old_name.starts_with("_"),
             "{}",
@@ -156,13 +327,16 @@ impl<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>
             "{}",
             format!("Label `{}` must not start with underscore!", label).red()
         );
-        self.wire_names.insert(wire.wire_id(), label.to_string());
+        let old_name = old_name.clone();
+        self.rename_net(&old_name, label);
     }
 
     //-------------------- Gate Functions ----------------------//
 
     define_gate_fn!(jtl, jtl_labeled, Jtl, [a]);
     define_gate_fn!(merge, merge_labeled, Merge, [a, b]);
+    define_gate_fn!(merge3, merge3_labeled, Merge3, [a, b, c]);
+    define_gate_fn!(merge4, merge4_labeled, Merge4, [a, b, c, d]);
     define_gate_fn!(and, and_labeled, And, [a, b, clk]);
     define_gate_fn!(or, or_labeled, Or, [a, b, clk]);
     define_gate_fn!(xor, xor_labeled, Xor, [a, b, clk]);
@@ -170,8 +344,42 @@ impl<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>
     define_gate_fn!(xnor, xnor_labeled, Xnor, [a, b, clk]);
     define_gate_fn!(dff, dff_labeled, Dff, [a, clk]);
     define_gate_fn!(ndro, ndro_labeled, Ndro, [a, b, clk]);
+    // r は dffr では届いた時点で, dffr_sync では次のクロックで a を打ち消す
+    define_gate_fn!(dffr, dffr_labeled, DffR, [a, r, clk]);
+    define_gate_fn!(dffr_sync, dffr_sync_labeled, DffRSync, [a, r, clk]);
+    // 相補出力. 状態が 1 なら q1, 0 なら q2 に出力する
+    define_gate_fn2!(dffc, dffc_labeled, DffC, [a, clk]);
+    define_gate_fn2!(ndroc, ndroc_labeled, NdroC, [a, b, clk]);
     define_gate_fn!(buff, buff_labeled, Buff, [a]);
     define_gate_fn!(zero_async, zero_async_labeled, ZeroAsync, []);
+    // クロックごとに 0 (パルスなし) と 1 (パルス) を出す定数
+    define_gate_fn!(zero_sync, zero_sync_labeled, ZeroSync, [clk]);
+    define_gate_fn!(one_sync, one_sync_labeled, OneSync, [clk]);
+    // DC レベルと SFQ パルスの変換. dcsfq は立ち上がりごとに, sfqdc はパルスごとにレベルを反転する
+    define_gate_fn!(dcsfq, dcsfq_labeled, DcSfq, [a]);
+    define_gate_fn!(sfqdc, sfqdc_labeled, SfqDc, [a]);
+    define_gate_fn!(first_arrival, first_arrival_labeled, FirstArrival, [a, b]);
+    define_gate_fn!(last_arrival, last_arrival_labeled, LastArrival, [a, b]);
+    define_gate_fn!(inhibit, inhibit_labeled, Inhibit, [a, b]);
+    define_gate_fn!(tff, tff_labeled, Tff, [a]);
+    define_gate_fn!(tffr, tffr_labeled, TffR, [a, r]);
+    // 相補出力の T フリップフロップ. 0 から 1 になるときに q1, 1 から 0 に戻るときに q2 に出力する
+    define_gate_fn2!(tff2, tff2_labeled, Tff2, [a]);
+
+    // 非同期のセル. パルスでは C 素子 (クロックなしの AND) が LAST_ARRIVAL,
+    // 反転 C 素子 (クロックなしの OR) が FIRST_ARRIVAL そのものなので, 同じゲートを置く
+    define_gate_fn!(c_element, c_element_labeled, LastArrival, [a, b]);
+    define_gate_fn!(inv_c_element, inv_c_element_labeled, FirstArrival, [a, b]);
+    define_gate_fn!(and_async, and_async_labeled, LastArrival, [a, b]);
+    define_gate_fn!(or_async, or_async_labeled, FirstArrival, [a, b]);
+
+    // JTL を n 段つないだ遅延線
+    pub fn delay(&mut self, mut a: Wire, n: usize) -> Wire {
+        for _ in 0..n {
+            a = self.jtl(a);
+        }
+        return a;
+    }
 
     pub fn split(&mut self, mut a: Wire) -> (Wire, Wire) {
         // 入力 Wire のチェック, receive
@@ -192,7 +400,7 @@ impl<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>
             q1: q1.wire_id(),
             q2: q2.wire_id(),
         };
-        self.gates.push(gate);
+        self.netlist.gates.push(gate);
 
         return (q1, q2);
     }
@@ -204,6 +412,67 @@ impl<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>
         return (wire1, wire2);
     }
 
+    // 3 出力の SPLIT
+    pub fn split3(&mut self, a: Wire) -> (Wire, Wire, Wire) {
+        let [q1, q2, q3] = self.split_n(a);
+        return (q1, q2, q3);
+    }
+
+    pub fn split3_labeled(
+        &mut self,
+        a: Wire,
+        label1: &str,
+        label2: &str,
+        label3: &str,
+    ) -> (Wire, Wire, Wire) {
+        let (wire1, wire2, wire3) = self.split3(a);
+        self.label(&wire1, label1);
+        self.label(&wire2, label2);
+        self.label(&wire3, label3);
+        return (wire1, wire2, wire3);
+    }
+
+    // 4 出力の SPLIT
+    pub fn split4(&mut self, a: Wire) -> (Wire, Wire, Wire, Wire) {
+        let [q1, q2, q3, q4] = self.split_n(a);
+        return (q1, q2, q3, q4);
+    }
+
+    pub fn split4_labeled(
+        &mut self,
+        a: Wire,
+        label1: &str,
+        label2: &str,
+        label3: &str,
+        label4: &str,
+    ) -> (Wire, Wire, Wire, Wire) {
+        let (wire1, wire2, wire3, wire4) = self.split4(a);
+        self.label(&wire1, label1);
+        self.label(&wire2, label2);
+        self.label(&wire3, label3);
+        self.label(&wire4, label4);
+        return (wire1, wire2, wire3, wire4);
+    }
+
+    // N 出力の SPLIT (SPLIT3, SPLIT4) を追加し, q1, ..., qN を返す
+    fn split_n<const N: usize>(&mut self, mut a: Wire) -> [Wire; N] {
+        assert!(a.circuit_id() == self.id);
+        a.receive();
+        let gate_name = self.generate_gate_name(&format!("SPLIT{}", N));
+        let qs: [Wire; N] = std::array::from_fn(|i| {
+            let mut q = self.generate_wire(format!("_{}_q{}", gate_name, i + 1));
+            q.drive();
+            q
+        });
+        let pins: Vec<WireID> = std::iter::once(a.wire_id())
+            .chain(qs.iter().map(|q| q.wire_id()))
+            .collect();
+        let gate = Gate::from_pins(&format!("SPLIT{}", N), gate_name, &pins).unwrap();
+        self.netlist.gates.push(gate);
+
+        return qs;
+    }
+
     pub fn terminate(&mut self, mut a: Wire) {
         assert!(a.circuit_id() == self.id);
         a.receive();
@@ -213,7 +482,7 @@ impl<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>
             name: gate_name,
             a: a.wire_id(),
         };
-        self.gates.push(gate);
+        self.netlist.gates.push(gate);
     }
 
     // Gate for CounterWire
@@ -231,7 +500,7 @@ impl<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>
             a: a.wire_id(),
             q: q.wire_id(),
         };
-        self.gates.push(gate);
+        self.netlist.gates.push(gate);
 
         return a;
     }
@@ -261,7 +530,7 @@ impl<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>
             q1: q1.wire_id(),
             q2: q2.wire_id(),
         };
-        self.gates.push(gate);
+        self.netlist.gates.push(gate);
 
         return (q2, a);
     }
@@ -296,7 +565,7 @@ impl<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>
             q1: q1.wire_id(),
             q2: q2.wire_id(),
         };
-        self.gates.push(gate);
+        self.netlist.gates.push(gate);
 
         return a;
     }
@@ -312,6 +581,70 @@ impl<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>
         return cwire;
     }
 
+    // q1(CounterWire)を受けとりq2, q3(Wire)とa(CounterWire)を返す
+    pub fn csplit3(&mut self, q1: CounterWire) -> (Wire, Wire, CounterWire) {
+        let ([q2, q3], a) = self.csplit_n(q1);
+        return (q2, q3, a);
+    }
+
+    pub fn csplit3_labeled(
+        &mut self,
+        q1: CounterWire,
+        label_q2: &str,
+        label_q3: &str,
+        label_a: &str,
+    ) -> (Wire, Wire, CounterWire) {
+        let (q2, q3, a) = self.csplit3(q1);
+        self.label(&q2, label_q2);
+        self.label(&q3, label_q3);
+        self.label(&a, label_a);
+        return (q2, q3, a);
+    }
+
+    // q1(CounterWire)を受けとりq2, q3, q4(Wire)とa(CounterWire)を返す
+    pub fn csplit4(&mut self, q1: CounterWire) -> (Wire, Wire, Wire, CounterWire) {
+        let ([q2, q3, q4], a) = self.csplit_n(q1);
+        return (q2, q3, q4, a);
+    }
+
+    pub fn csplit4_labeled(
+        &mut self,
+        q1: CounterWire,
+        label_q2: &str,
+        label_q3: &str,
+        label_q4: &str,
+        label_a: &str,
+    ) -> (Wire, Wire, Wire, CounterWire) {
+        let (q2, q3, q4, a) = self.csplit4(q1);
+        self.label(&q2, label_q2);
+        self.label(&q3, label_q3);
+        self.label(&q4, label_q4);
+        self.label(&a, label_a);
+        return (q2, q3, q4, a);
+    }
+
+    // M + 1 出力の SPLIT. q1(CounterWire)を受けとりq2, ..., q{M+1}(Wire)とa(CounterWire)を返す
+    fn csplit_n<const M: usize>(&mut self, mut q1: CounterWire) -> ([Wire; M], CounterWire) {
+        assert!(q1.circuit_id() == self.id);
+        q1.drive();
+        let gate_name = self.generate_gate_name(&format!("SPLIT{}", M + 1));
+        let qs: [Wire; M] = std::array::from_fn(|i| {
+            let mut q = self.generate_wire(format!("_{}_q{}", gate_name, i + 2));
+            q.drive();
+            q
+        });
+        let mut a = self.generate_counter_wire(format!("_{}_a", gate_name));
+        a.receive();
+        let pins: Vec<WireID> = [a.wire_id(), q1.wire_id()]
+            .into_iter()
+            .chain(qs.iter().map(|q| q.wire_id()))
+            .collect();
+        let gate = Gate::from_pins(&format!("SPLIT{}", M + 1), gate_name, &pins).unwrap();
+        self.netlist.gates.push(gate);
+
+        return (qs, a);
+    }
+
     pub fn cterminate(&mut self) -> CounterWire {
         let gate_name = format!("XTERMINATE{}", self.generate_gate_id());
         let a_name = format!("_{}_a", gate_name);
@@ -322,7 +655,7 @@ impl<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>
             name: gate_name,
             a: a.wire_id(),
         };
-        self.gates.push(gate);
+        self.netlist.gates.push(gate);
 
         return a;
     }
@@ -336,30 +669,55 @@ impl<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>
     pub fn subcircuit<const M_I: usize, const M_CI: usize, const M_O: usize, const M_CO: usize>(
         &mut self,
         circuit: &Circuit<M_I, M_CI, M_O, M_CO>,
-        mut inputs: [Wire; M_I],
-        mut counter_inputs: [CounterWire; M_CI],
+        inputs: [Wire; M_I],
+        counter_inputs: [CounterWire; M_CI],
     ) -> ([Wire; M_O], [CounterWire; M_CO]) {
+        let (outputs, counter_outputs) =
+            self.instantiate(&circuit.netlist, inputs.into(), counter_inputs.into());
+        return (
+            outputs.try_into().unwrap(),
+            counter_outputs.try_into().unwrap(),
+        );
+    }
+
+    // 型付けされていない回路のインスタンス. ポート数は呼び出し側が合わせる
+    pub(crate) fn instantiate(
+        &mut self,
+        netlist: &Netlist,
+        mut inputs: Vec<Wire>,
+        mut counter_inputs: Vec<CounterWire>,
+    ) -> (Vec<Wire>, Vec<CounterWire>) {
+        assert!(inputs.len() == netlist.inputs.len());
+        assert!(counter_inputs.len() == netlist.counter_inputs.len());
         // 入力Wireの処理
         assert!(inputs.iter().all(|w| w.circuit_id() == self.id));
         assert!(counter_inputs.iter().all(|w| w.circuit_id() == self.id));
         inputs.iter_mut().for_each(|w| w.receive());
         counter_inputs.iter_mut().for_each(|cw| cw.drive());
 
-        let gate_name = format!("X{}{}", circuit.name, self.generate_gate_id());
+        let gate_name = format!("X{}{}", netlist.name, self.generate_gate_id());
 
         // 出力Wireの生成
-        let output_wires = circuit.outputs.clone().map(|s| {
-            let wire_name = format!("_{}_{}", gate_name, s);
-            let mut wire = self.generate_wire(wire_name);
-            wire.drive();
-            wire
-        });
-        let counter_output_wires = circuit.counter_outputs.clone().map(|s| {
-            let wire_name = format!("_{}_{}", gate_name, s);
-            let mut cwire = self.generate_counter_wire(wire_name);
-            cwire.receive();
-            cwire
-        });
+        let output_wires: Vec<Wire> = netlist
+            .outputs
+            .iter()
+            .map(|port| {
+                let wire_name = format!("_{}_{}", gate_name, port);
+                let mut wire = self.generate_wire(wire_name);
+                wire.drive();
+                wire
+            })
+            .collect();
+        let counter_output_wires: Vec<CounterWire> = netlist
+            .counter_outputs
+            .iter()
+            .map(|port| {
+                let wire_name = format!("_{}_{}", gate_name, port);
+                let mut cwire = self.generate_counter_wire(wire_name);
+                cwire.receive();
+                cwire
+            })
+            .collect();
 
         // ゲートの生成
         let gate_inputs: Vec<WireID> = inputs
@@ -376,13 +734,67 @@ impl<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>
             name: gate_name,
             inputs: gate_inputs,
             outputs: gate_outputs,
-            circuit: circuit.name.clone(),
+            circuit: Arc::new(netlist.clone()),
         };
-        self.gates.push(gate);
+        self.netlist.gates.push(gate);
 
         return (output_wires, counter_output_wires);
     }
 
+    //-------------------- Synthesis ----------------------//
+
+    // 論理式をクロック付きゲートで構築する. names[i] の変数が inputs[i] に対応する
+    // すべての経路は同じ段数になるように DFF で遅延される
+    pub fn expr<const M: usize>(
+        &mut self,
+        expr: &Expr,
+        names: [&str; M],
+        inputs: [Wire; M],
+        clk: Wire,
+    ) -> Wire {
+        let [q] = self.exprs([expr], names, inputs, clk);
+        return q;
+    }
+
+    // 複数の論理式を共通の部分式を共有して構築する. 出力の段数はすべて同じ
+    pub fn exprs<const M: usize, const K: usize>(
+        &mut self,
+        exprs: [&Expr; K],
+        names: [&str; M],
+        inputs: [Wire; M],
+        clk: Wire,
+    ) -> [Wire; K] {
+        assert!(inputs.iter().all(|w| w.circuit_id() == self.id));
+        assert!(clk.circuit_id() == self.id);
+        let wires = synthesize(self, &exprs, &names, inputs.into(), clk);
+        return wires.try_into().unwrap();
+    }
+
+    // 真理値表を最小化して構築する. inputs, 戻り値は表の入出力の順
+    pub fn truth_table<const M: usize, const K: usize>(
+        &mut self,
+        table: &TruthTable,
+        inputs: [Wire; M],
+        clk: Wire,
+    ) -> [Wire; K] {
+        assert!(
+            table.inputs().len() == M && table.outputs().len() == K,
+            "{}",
+            format!(
+                "Truth table has {} inputs and {} outputs, but {} and {} are given!",
+                table.inputs().len(),
+                table.outputs().len(),
+                M,
+                K
+            )
+            .red()
+        );
+        let exprs = table.exprs();
+        let exprs: [&Expr; K] = std::array::from_fn(|i| &exprs[i]);
+        let names: [&str; M] = table.inputs().try_into().unwrap();
+        return self.exprs(exprs, names, inputs, clk);
+    }
+
     //-------------------- Wire Functions ----------------------//
 
     // 同一のidを持ったWireとCounterWireを生成する
@@ -394,6 +806,54 @@ impl<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>
         return (wire, cwire);
     }
 
+    // 自動命名の gen_loop
+    pub(crate) fn gen_loop_unnamed(&mut self) -> (Wire, CounterWire) {
+        let label = format!("_LOOP{}", self.next_wire_id);
+        return self.gen_loop(&label);
+    }
+
+    // n 本に分岐する SPLIT の木 (n = 0 なら終端する)
+    pub(crate) fn split_tree(&mut self, wire: Wire, n: usize) -> Vec<Wire> {
+        if n == 0 {
+            self.terminate(wire);
+            return Vec::new();
+        }
+        if n == 1 {
+            return vec![wire];
+        }
+        let (q1, q2) = self.split(wire);
+        let mut res = self.split_tree(q1, n / 2);
+        res.extend(self.split_tree(q2, n - n / 2));
+        return res;
+    }
+
+    // MERGE の木で 1 本にまとめる (空なら zero_async)
+    pub(crate) fn merge_tree(&mut self, mut wires: Vec<Wire>) -> Wire {
+        if wires.is_empty() {
+            return self.zero_async();
+        }
+        if wires.len() == 1 {
+            return wires.pop().unwrap();
+        }
+        let rest = wires.split_off(wires.len() / 2);
+        let a = self.merge_tree(wires);
+        let b = self.merge_tree(rest);
+        return self.merge(a, b);
+    }
+
+    // ラベルまたはポート名が付いているか
+    pub(crate) fn is_named<T>(&self, wire: &T) -> bool
+    where
+        T: HasWireID,
+    {
+        return !self.netlist.wire_name(&wire.wire_id()).starts_with("_");
+    }
+
+    // 名前が使われているか
+    pub(crate) fn has_net(&self, name: &str) -> bool {
+        return self.netlist.wire_names.values().any(|v| v == name);
+    }
+
     // Wire と CounterWire を統合
     pub fn unify(&mut self, mut wire: Wire, mut cwire: CounterWire) {
         assert!(wire.circuit_id() == self.id);
@@ -404,8 +864,8 @@ impl<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>
         cwire.drive();
 
         // 名前の解決
-        let name1 = self.wire_names.get(&wire.wire_id()).unwrap();
-        let name2 = self.wire_names.get(&cwire.wire_id()).unwrap();
+        let name1 = self.netlist.wire_names.get(&wire.wire_id()).unwrap();
+        let name2 = self.netlist.wire_names.get(&cwire.wire_id()).unwrap();
 
         let named1 = !name1.starts_with("_");
         let named2 = !name2.starts_with("_");
@@ -416,11 +876,12 @@ impl<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>
                 "{}",
                 format!("Conflict names in unify: `{}`, `{}`!", name1, name2).red()
             );
-            self.wire_names.insert(cwire.wire_id(), name1.clone());
         } else if named2 {
-            self.wire_names.insert(wire.wire_id(), name2.clone());
+            let (old, new) = (name1.clone(), name2.clone());
+            self.rename_net(&old, &new);
         } else {
-            self.wire_names.insert(cwire.wire_id(), name1.clone());
+            let (old, new) = (name2.clone(), name1.clone());
+            self.rename_net(&old, &new);
         }
     }
 
@@ -431,3 +892,33 @@ impl<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Circuit;
+    use crate::temporal::TemporalSimulator;
+    use crate::wire::Wire;
+
+    type Cell = fn(&mut Circuit<2, 0, 1, 0>, Wire, Wire) -> Wire;
+
+    // C 素子は両方, 反転 C 素子は先に届いたパルスで出力し, 両方届くと元に戻る
+    #[test]
+    fn c_elements() {
+        let cells: [(Cell, &str, [f64; 2]); 4] = [
+            (Circuit::c_element, "LAST_ARRIVAL", [3.0, 7.0]),
+            (Circuit::and_async, "LAST_ARRIVAL", [3.0, 7.0]),
+            (Circuit::inv_c_element, "FIRST_ARRIVAL", [1.0, 5.0]),
+            (Circuit::or_async, "FIRST_ARRIVAL", [1.0, 5.0]),
+        ];
+        for (cell, kind, expected) in cells {
+            let (mut c, [a, b], [], [y], []) = Circuit::create(["a", "b"], [], ["y"], [], "cell");
+            let q = cell(&mut c, a, b);
+            c.unify(q, y);
+            assert_eq!(c.netlist.gates[0].kind(), kind);
+            let outputs = TemporalSimulator::new()
+                .default_delay(0.0)
+                .run(&c, &[&[1.0, 7.0], &[3.0, 5.0]]);
+            assert_eq!(outputs, [expected.to_vec()]);
+        }
+    }
+}
@@ -0,0 +1,429 @@
+use super::{Structure, bus, cell, generate};
+use crate::circuit::Circuit;
+use crate::logic::Op;
+
+// 任意の幅の算術回路の生成器. 幅は型パラメータのポート数から決まる
+// ポートは a_0, a_1, ... のように LSB から番号を付け, クロック clk を最後の入力とする
+// すべての経路の段数は DFF でそろえてあり, 毎サイクル新しい入力を受け付ける
+pub struct Arithmetic;
+
+// 入力 a_i, b_i, clk のポート数 2 width + 1 から幅を求める
+// 生成器では const ブロックで呼び, ポート数の誤りをコンパイル時に見つける
+const fn operand_width(n_i: usize) -> usize {
+    assert!(
+        n_i >= 3 && n_i % 2 == 1,
+        "Inputs must be a_0, ..., b_0, ... and clk!"
+    );
+    return (n_i - 1) / 2;
+}
+
+// 出力が width + 1 個 (加算器, 減算器) のときの幅
+const fn sum_width(n_i: usize, n_o: usize) -> usize {
+    let width = operand_width(n_i);
+    assert!(n_o == width + 1, "Outputs must be width + 1 bits!");
+    return width;
+}
+
+// 出力が 2 width 個 (乗算器) のときの幅
+const fn product_width(n_i: usize, n_o: usize) -> usize {
+    let width = operand_width(n_i);
+    assert!(n_o == 2 * width, "Outputs must be 2 width bits!");
+    return width;
+}
+
+fn operands(width: usize) -> Vec<String> {
+    let mut res = bus("a", width);
+    res.extend(bus("b", width));
+    return res;
+}
+
+fn half_adder() -> Circuit<3, 0, 2, 0> {
+    return cell("HA", &["a", "b"], [("s", "a ^ b"), ("c", "a & b")]);
+}
+
+fn full_adder() -> Circuit<4, 0, 2, 0> {
+    return cell(
+        "FA",
+        &["a", "b", "cin"],
+        [("s", "a ^ b ^ cin"), ("cout", "a & b | cin & (a ^ b)")],
+    );
+}
+
+// 列ごとのビットを桁上げを伝えながら足す. 各列のビットは 2 つまで
+// 最上位の列からの桁上げは捨てる (0 であること)
+fn ripple(s: &mut Structure, columns: Vec<Vec<String>>) -> Vec<String> {
+    let ha = half_adder();
+    let fa = full_adder();
+    let mut res = Vec::new();
+    let mut carry: Option<String> = None;
+    for mut bits in columns {
+        bits.extend(carry.take());
+        let bits: Vec<&String> = bits.iter().collect();
+        match bits.len() {
+            0 => res.push(s.gate(Op::Zero, &[])),
+            1 => res.push(bits[0].clone()),
+            2 => {
+                let [sum, c] = s.instance(&ha, &bits);
+                res.push(sum);
+                carry = Some(c);
+            }
+            3 => {
+                let [sum, c] = s.instance(&fa, &bits);
+                res.push(sum);
+                carry = Some(c);
+            }
+            _ => unreachable!(),
+        }
+    }
+    return res;
+}
+
+// 部分積 a_i & b_j
+fn partial_product(s: &mut Structure, i: usize, j: usize) -> String {
+    return s.gate(Op::And, &[&format!("a_{}", i), &format!("b_{}", j)]);
+}
+
+// 桁上げ先行加算のセル: (g, p) = (a & b, a ^ b)
+fn pg() -> Circuit<3, 0, 2, 0> {
+    return cell("PG", &["a", "b"], [("g", "a & b"), ("p", "a ^ b")]);
+}
+
+// (g, p) = (gh, ph) o (gl, pl)
+fn black() -> Circuit<5, 0, 2, 0> {
+    return cell(
+        "BLACK",
+        &["gh", "ph", "gl", "pl"],
+        [("g", "gh | ph & gl"), ("p", "ph & pl")],
+    );
+}
+
+// 下位のグループが LSB から始まるときは g だけ求める
+fn grey() -> Circuit<4, 0, 1, 0> {
+    return cell("GREY", &["gh", "ph", "gl"], [("g", "gh | ph & gl")]);
+}
+
+// 並列プレフィックス加算器. combine の並びで (g, p) を LSB 側から集める
+fn prefix_adder<const N_I: usize, const N_O: usize>(
+    name: &str,
+    width: usize,
+    levels: Vec<Vec<(usize, usize)>>,
+) -> Circuit<N_I, 0, N_O, 0> {
+    let mut s = Structure::new(&operands(width));
+    let (a, b) = (bus("a", width), bus("b", width));
+    let (pg, black, grey) = (pg(), black(), grey());
+
+    let mut g: Vec<String> = Vec::new();
+    let mut p: Vec<String> = Vec::new();
+    for i in 0..width {
+        let [gi, pi] = s.instance(&pg, &[&a[i], &b[i]]);
+        g.push(gi);
+        p.push(pi);
+    }
+    let bit_p = p.clone();
+
+    // lo[i]: (g[i], p[i]) がまとめているビットの下端
+    let mut lo: Vec<usize> = (0..width).collect();
+    for level in levels {
+        // 同じ段の演算は前の段の値を読む
+        let (g_prev, p_prev, lo_prev) = (g.clone(), p.clone(), lo.clone());
+        for (i, j) in level {
+            assert!(lo_prev[i] == j + 1);
+            if lo_prev[j] == 0 {
+                let [gi] = s.instance(&grey, &[&g_prev[i], &p_prev[i], &g_prev[j]]);
+                g[i] = gi;
+            } else {
+                let [gi, pi] =
+                    s.instance(&black, &[&g_prev[i], &p_prev[i], &g_prev[j], &p_prev[j]]);
+                g[i] = gi;
+                p[i] = pi;
+            }
+            lo[i] = lo_prev[j];
+        }
+    }
+    assert!(lo.iter().all(|l| *l == 0));
+
+    // s_i = p_i ^ c_{i-1}
+    let mut outputs = vec![bit_p[0].clone()];
+    for i in 1..width {
+        outputs.push(s.gate(Op::Xor, &[&bit_p[i], &g[i - 1]]));
+    }
+    outputs.push(g[width - 1].clone());
+    s.outputs = outputs;
+
+    let mut ports = bus("s", width);
+    ports.push("cout".to_string());
+    return generate(name, &s, &ports);
+}
+
+// 2 ビットずつの比較結果 (gt, lt) を上位と下位からまとめる
+fn compare(s: &mut Structure, lo: usize, hi: usize) -> [String; 2] {
+    if hi - lo == 1 {
+        let cmp1: Circuit<3, 0, 2, 0> =
+            cell("CMP1", &["a", "b"], [("gt", "a & !b"), ("lt", "!a & b")]);
+        return s.instance(&cmp1, &[&format!("a_{}", lo), &format!("b_{}", lo)]);
+    }
+    let mid = (lo + hi) / 2;
+    let [gh, lh] = compare(s, mid, hi);
+    let [gl, ll] = compare(s, lo, mid);
+    let merge: Circuit<5, 0, 2, 0> = cell(
+        "CMPM",
+        &["gh", "lh", "gl", "ll"],
+        [("gt", "gh | !lh & gl"), ("lt", "lh | !gh & ll")],
+    );
+    return s.instance(&merge, &[&gh, &lh, &gl, &ll]);
+}
+
+impl Arithmetic {
+    // 桁上げ伝搬加算器: a + b = (s, cout)
+    // 入力 a_i, b_i, clk, 出力 s_i, cout. 下位ビットは HA, 他は FA のサブサーキット
+    pub fn ripple_carry_adder<const N_I: usize, const N_O: usize>() -> Circuit<N_I, 0, N_O, 0> {
+        let width = const { sum_width(N_I, N_O) };
+        let mut s = Structure::new(&operands(width));
+        let (a, b) = (bus("a", width), bus("b", width));
+        let mut columns: Vec<Vec<String>> = (0..width)
+            .map(|i| vec![a[i].clone(), b[i].clone()])
+            .collect();
+        columns.push(Vec::new());
+        s.outputs = ripple(&mut s, columns);
+
+        let mut ports = bus("s", width);
+        ports.push("cout".to_string());
+        return generate(&format!("RCA{}", width), &s, &ports);
+    }
+
+    // Kogge-Stone 加算器. ポートは ripple_carry_adder() と同じ
+    pub fn kogge_stone_adder<const N_I: usize, const N_O: usize>() -> Circuit<N_I, 0, N_O, 0> {
+        let width = const { sum_width(N_I, N_O) };
+        let mut levels = Vec::new();
+        let mut d = 1;
+        while d < width.max(1) {
+            levels.push((d..width).map(|i| (i, i - d)).collect());
+            d *= 2;
+        }
+        return prefix_adder(&format!("KSA{}", width), width, levels);
+    }
+
+    // Brent-Kung 加算器. ポートは ripple_carry_adder() と同じ
+    pub fn brent_kung_adder<const N_I: usize, const N_O: usize>() -> Circuit<N_I, 0, N_O, 0> {
+        let width = const { sum_width(N_I, N_O) };
+        let mut levels: Vec<Vec<(usize, usize)>> = Vec::new();
+        let mut d = 1;
+        while d < width {
+            levels.push(
+                (2 * d - 1..width)
+                    .step_by(2 * d)
+                    .map(|i| (i, i - d))
+                    .collect(),
+            );
+            d *= 2;
+        }
+        while d > 1 {
+            d /= 2;
+            let level: Vec<(usize, usize)> = (3 * d - 1..width)
+                .step_by(2 * d)
+                .map(|i| (i, i - d))
+                .collect();
+            if !level.is_empty() {
+                levels.push(level);
+            }
+        }
+        return prefix_adder(&format!("BKA{}", width), width, levels);
+    }
+
+    // 減算器: a - b = (d, bout). bout は a < b のとき 1
+    // 入力 a_i, b_i, clk, 出力 d_i, bout
+    pub fn subtractor<const N_I: usize, const N_O: usize>() -> Circuit<N_I, 0, N_O, 0> {
+        let width = const { sum_width(N_I, N_O) };
+        let mut s = Structure::new(&operands(width));
+        let (a, b) = (bus("a", width), bus("b", width));
+        let hs: Circuit<3, 0, 2, 0> = cell("HS", &["a", "b"], [("d", "a ^ b"), ("bout", "!a & b")]);
+        let fs: Circuit<4, 0, 2, 0> = cell(
+            "FS",
+            &["a", "b", "bin"],
+            [("d", "a ^ b ^ bin"), ("bout", "!a & b | bin & !(a ^ b)")],
+        );
+        let mut outputs = Vec::new();
+        let [d, mut borrow] = s.instance(&hs, &[&a[0], &b[0]]);
+        outputs.push(d);
+        for i in 1..width {
+            let [d, bout] = s.instance(&fs, &[&a[i], &b[i], &borrow]);
+            outputs.push(d);
+            borrow = bout;
+        }
+        outputs.push(borrow);
+        s.outputs = outputs;
+
+        let mut ports = bus("d", width);
+        ports.push("bout".to_string());
+        return generate(&format!("SUB{}", width), &s, &ports);
+    }
+
+    // 符号なしの比較器. 入力 a_i, b_i, clk, 出力 gt, eq, lt
+    pub fn comparator<const N_I: usize, const N_O: usize>() -> Circuit<N_I, 0, N_O, 0> {
+        let width = const {
+            assert!(N_O == 3, "Outputs must be gt, eq and lt!");
+            operand_width(N_I)
+        };
+        let mut s = Structure::new(&operands(width));
+        let [gt, lt] = compare(&mut s, 0, width);
+        let out: Circuit<3, 0, 3, 0> = cell(
+            "CMPO",
+            &["g", "l"],
+            [("gt", "g"), ("eq", "!(g | l)"), ("lt", "l")],
+        );
+        s.outputs = s.instance(&out, &[&gt, &lt]).to_vec();
+
+        let ports = ["gt", "eq", "lt"].map(|p| p.to_string());
+        return generate(&format!("CMP{}", width), &s, &ports);
+    }
+
+    // 配列乗算器: 部分積を 1 行ずつ桁上げ伝搬加算で足す
+    // 入力 a_i, b_i, clk, 出力 p_0, ..., p_{2 width - 1}
+    pub fn array_multiplier<const N_I: usize, const N_O: usize>() -> Circuit<N_I, 0, N_O, 0> {
+        let width = const { product_width(N_I, N_O) };
+        let mut s = Structure::new(&operands(width));
+        // 行 j の部分積は列 j..j + width
+        let row = |s: &mut Structure, j: usize| -> Vec<String> {
+            return (0..width).map(|i| partial_product(s, i, j)).collect();
+        };
+        let mut product: Vec<String> = Vec::new();
+        let mut acc: Vec<String> = row(&mut s, 0);
+        for j in 1..width {
+            product.push(acc.remove(0));
+            let pp = row(&mut s, j);
+            let mut cols: Vec<Vec<String>> = (0..width)
+                .map(|k| acc.get(k).into_iter().chain([&pp[k]]).cloned().collect())
+                .collect();
+            cols.push(Vec::new());
+            acc = ripple(&mut s, cols);
+        }
+        product.extend(acc);
+        while product.len() < 2 * width {
+            product.push(s.gate(Op::Zero, &[]));
+        }
+        s.outputs = product;
+
+        return generate(&format!("AMUL{}", width), &s, &bus("p", 2 * width));
+    }
+
+    // Wallace 木乗算器: 部分積を FA, HA で 2 行まで減らし, 桁上げ伝搬加算で足す
+    // ポートは array_multiplier() と同じ
+    pub fn wallace_multiplier<const N_I: usize, const N_O: usize>() -> Circuit<N_I, 0, N_O, 0> {
+        let width = const { product_width(N_I, N_O) };
+        let mut s = Structure::new(&operands(width));
+        let (ha, fa) = (half_adder(), full_adder());
+        let mut columns: Vec<Vec<String>> = vec![Vec::new(); 2 * width];
+        for i in 0..width {
+            for j in 0..width {
+                let pp = partial_product(&mut s, i, j);
+                columns[i + j].push(pp);
+            }
+        }
+        while columns.iter().any(|c| c.len() > 2) {
+            let mut next: Vec<Vec<String>> = vec![Vec::new(); 2 * width + 1];
+            for (k, bits) in columns.iter().enumerate() {
+                let mut rest: &[String] = bits;
+                while rest.len() >= 3 {
+                    let [sum, c] = s.instance(&fa, &[&rest[0], &rest[1], &rest[2]]);
+                    next[k].push(sum);
+                    next[k + 1].push(c);
+                    rest = &rest[3..];
+                }
+                if rest.len() == 2 && bits.len() > 2 {
+                    let [sum, c] = s.instance(&ha, &[&rest[0], &rest[1]]);
+                    next[k].push(sum);
+                    next[k + 1].push(c);
+                    rest = &[];
+                }
+                next[k].extend(rest.iter().cloned());
+            }
+            // 最上位からの桁上げは 0
+            next.truncate(2 * width);
+            columns = next;
+        }
+        s.outputs = ripple(&mut s, columns);
+
+        return generate(&format!("WMUL{}", width), &s, &bus("p", 2 * width));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Arithmetic, cell};
+    use crate::circuit::Circuit;
+    use crate::equivalence::EquivalenceChecker;
+
+    // パイプラインの状態が多いのでランダムシミュレーションで調べる
+    fn assert_equivalent<const N_I: usize, const N_O: usize>(
+        a: &Circuit<N_I, 0, N_O, 0>,
+        b: &Circuit<N_I, 0, N_O, 0>,
+    ) {
+        let checker = EquivalenceChecker::new().max_inputs(0).random(16, 200);
+        if let Err(e) = checker.check(a, b) {
+            panic!("{}", e);
+        }
+    }
+
+    #[test]
+    fn adders() {
+        let reference: Circuit<5, 0, 3, 0> = cell(
+            "ADD2",
+            &["a_0", "a_1", "b_0", "b_1"],
+            [
+                ("s_0", "a_0 ^ b_0"),
+                ("s_1", "a_1 ^ b_1 ^ a_0 & b_0"),
+                ("cout", "a_1 & b_1 | (a_1 ^ b_1) & a_0 & b_0"),
+            ],
+        );
+        assert_equivalent(&Arithmetic::ripple_carry_adder(), &reference);
+        assert_equivalent(&Arithmetic::kogge_stone_adder(), &reference);
+        assert_equivalent(&Arithmetic::brent_kung_adder(), &reference);
+
+        let rca: Circuit<11, 0, 6, 0> = Arithmetic::ripple_carry_adder();
+        assert_equivalent(&Arithmetic::kogge_stone_adder(), &rca);
+        assert_equivalent(&Arithmetic::brent_kung_adder(), &rca);
+    }
+
+    #[test]
+    fn subtractor_and_comparator() {
+        let sub: Circuit<5, 0, 3, 0> = cell(
+            "SUB2",
+            &["a_0", "a_1", "b_0", "b_1"],
+            [
+                ("d_0", "a_0 ^ b_0"),
+                ("d_1", "a_1 ^ b_1 ^ !a_0 & b_0"),
+                ("bout", "!a_1 & b_1 | !(a_1 ^ b_1) & !a_0 & b_0"),
+            ],
+        );
+        assert_equivalent(&Arithmetic::subtractor(), &sub);
+
+        let gt = "a_1 & !b_1 | !(a_1 ^ b_1) & a_0 & !b_0";
+        let lt = "!a_1 & b_1 | !(a_1 ^ b_1) & !a_0 & b_0";
+        let eq = "!(a_1 ^ b_1) & !(a_0 ^ b_0)";
+        let cmp: Circuit<5, 0, 3, 0> = cell(
+            "CMP2",
+            &["a_0", "a_1", "b_0", "b_1"],
+            [("gt", gt), ("eq", eq), ("lt", lt)],
+        );
+        assert_equivalent(&Arithmetic::comparator(), &cmp);
+    }
+
+    #[test]
+    fn multipliers() {
+        let reference: Circuit<5, 0, 4, 0> = cell(
+            "MUL2",
+            &["a_0", "a_1", "b_0", "b_1"],
+            [
+                ("p_0", "a_0 & b_0"),
+                ("p_1", "a_1 & b_0 ^ a_0 & b_1"),
+                ("p_2", "a_1 & b_1 & !(a_0 & b_0)"),
+                ("p_3", "a_1 & b_1 & a_0 & b_0"),
+            ],
+        );
+        assert_equivalent(&Arithmetic::array_multiplier(), &reference);
+        assert_equivalent(&Arithmetic::wallace_multiplier(), &reference);
+
+        let amul: Circuit<7, 0, 6, 0> = Arithmetic::array_multiplier();
+        assert_equivalent(&Arithmetic::wallace_multiplier(), &amul);
+    }
+}
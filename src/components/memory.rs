@@ -0,0 +1,261 @@
+use super::{Structure, assert_ports, bus, generate, generate_netlist};
+use crate::circuit::Circuit;
+use crate::logic::Op;
+use crate::wire::{CounterWire, Wire};
+
+// NDRO を記憶素子とするレジスタファイルとメモリの生成器
+// 書き込みと読み出しは clk に同期し, 読み出しはそれより前のサイクルの書き込みを反映する
+pub struct Memory;
+
+impl Memory {
+    // アドレスデコーダ: a_0, ..., a_{k-1}, en, clk -> w_0, ..., w_{words-1}
+    // en にパルスがあるサイクルに, アドレスの示す w_j にパルスを出す
+    pub fn decoder<const N_I: usize, const N_O: usize>(words: usize) -> Circuit<N_I, 0, N_O, 0> {
+        let (name, s, outputs) = decoder(words);
+        return generate(&name, &s, &outputs);
+    }
+
+    // 書き込み 1 ポート, 読み出し read_ports ポートのレジスタファイル
+    // wa_i, we, d_i, (ra{p}_i, re{p}) * read_ports, clk -> (q{p}_i) * read_ports
+    pub fn register_file<const N_I: usize, const N_O: usize>(
+        words: usize,
+        width: usize,
+        read_ports: usize,
+    ) -> Circuit<N_I, 0, N_O, 0> {
+        assert!(read_ports > 0, "Read ports must be positive!");
+        let k = address_bits(words);
+        let name = format!("RF{}X{}R{}", words, width, read_ports);
+        let mut inputs = bus("wa", k);
+        inputs.push("we".to_string());
+        inputs.extend(bus("d", width));
+        let mut outputs = Vec::new();
+        for p in 0..read_ports {
+            inputs.extend(bus(&format!("ra{}", p), k));
+            inputs.push(format!("re{}", p));
+            outputs.extend(bus(&format!("q{}", p), width));
+        }
+        inputs.push("clk".to_string());
+        assert_ports(&name, inputs.len(), outputs.len(), N_I, N_O);
+        let inputs: Vec<&str> = inputs.iter().map(|s| s.as_str()).collect();
+        let outputs: Vec<&str> = outputs.iter().map(|s| s.as_str()).collect();
+        let (mut c, input_wires, _, output_cwires, _) =
+            Circuit::create_ports(&inputs, &[], &outputs, &[], &name);
+
+        let mut wires = input_wires.into_iter();
+        let mut take = |n: usize| -> Vec<Wire> { wires.by_ref().take(n).collect() };
+        let write = (take(k), take(1).pop().unwrap());
+        let data = take(width);
+        let reads: Vec<(Vec<Wire>, Wire)> = (0..read_ports)
+            .map(|_| (take(k), take(1).pop().unwrap()))
+            .collect();
+        let clk = take(1).pop().unwrap();
+        let labels: Vec<String> = (0..read_ports).map(|p| format!("rwl{}", p)).collect();
+
+        let qs = array(&mut c, words, width, write, data, reads, &labels, clk);
+        for (q, cwire) in qs.into_iter().flatten().zip(output_cwires) {
+            c.unify(q, cwire);
+        }
+        return c;
+    }
+
+    // 読み書きでアドレスを共有するメモリ
+    // addr_i, we, re, d_i, clk -> q_i
+    pub fn ram<const N_I: usize, const N_O: usize>(
+        words: usize,
+        width: usize,
+    ) -> Circuit<N_I, 0, N_O, 0> {
+        let k = address_bits(words);
+        let name = format!("RAM{}X{}", words, width);
+        let mut inputs = bus("addr", k);
+        inputs.extend(["we".to_string(), "re".to_string()]);
+        inputs.extend(bus("d", width));
+        inputs.push("clk".to_string());
+        let outputs = bus("q", width);
+        assert_ports(&name, inputs.len(), outputs.len(), N_I, N_O);
+        let inputs: Vec<&str> = inputs.iter().map(|s| s.as_str()).collect();
+        let outputs: Vec<&str> = outputs.iter().map(|s| s.as_str()).collect();
+        let (mut c, mut wires, _, output_cwires, _) =
+            Circuit::create_ports(&inputs, &[], &outputs, &[], &name);
+
+        let clk = wires.pop().unwrap();
+        let data = wires.split_off(k + 2);
+        let re = wires.pop().unwrap();
+        let we = wires.pop().unwrap();
+        let (waddr, raddr): (Vec<Wire>, Vec<Wire>) = wires.into_iter().map(|a| c.split(a)).unzip();
+
+        let labels = ["rwl".to_string()];
+        let qs = array(
+            &mut c,
+            words,
+            width,
+            (waddr, we),
+            data,
+            vec![(raddr, re)],
+            &labels,
+            clk,
+        );
+        for (q, cwire) in qs.into_iter().flatten().zip(output_cwires) {
+            c.unify(q, cwire);
+        }
+        return c;
+    }
+}
+
+// words 語を区別するアドレスのビット数
+fn address_bits(words: usize) -> usize {
+    assert!(words > 0, "Words must be positive!");
+    return words.next_power_of_two().trailing_zeros() as usize;
+}
+
+// (回路名, 構造, 出力ポート名)
+fn decoder(words: usize) -> (String, Structure, Vec<String>) {
+    let k = address_bits(words);
+    let mut inputs = bus("a", k);
+    inputs.push("en".to_string());
+    let mut s = Structure::new(&inputs);
+    let en = &inputs[k];
+    s.outputs = if k == 0 {
+        // ワード線が段数 0 にならないように DFF を通す
+        vec![s.gate(Op::Dff, &[en])]
+    } else {
+        let lits: Vec<(String, String)> = inputs[..k]
+            .iter()
+            .map(|a| (a.clone(), s.gate(Op::Not, &[a])))
+            .collect();
+        let terms = minterms(&mut s, &lits, words);
+        terms.iter().map(|m| s.gate(Op::And, &[m, en])).collect()
+    };
+    return (format!("DEC{}", words), s, bus("w", words));
+}
+
+// lits = [(a_i, !a_i)] の最小項のうち先頭 count 個
+// 下位と上位のビットに分けて復号し, その組み合わせの AND をとる
+fn minterms(s: &mut Structure, lits: &[(String, String)], count: usize) -> Vec<String> {
+    if lits.len() == 1 {
+        return [lits[0].1.clone(), lits[0].0.clone()][..count].to_vec();
+    }
+    let half = lits.len() / 2;
+    let size = 1 << half;
+    let low = minterms(s, &lits[..half], count.min(size));
+    let high = minterms(s, &lits[half..], count.div_ceil(size));
+    return (0..count)
+        .map(|j| s.gate(Op::And, &[&low[j % size], &high[j / size]]))
+        .collect();
+}
+
+// 記憶セル: bl, blb, wl, rl, clk -> q
+// wl のパルスで bl なら NDRO をセット, blb ならリセットし, rl のパルスで読み出す
+fn memory_cell() -> Circuit<5, 0, 1, 0> {
+    let (mut c, [bl, blb, wl, rl, clk], [], [q], []) =
+        Circuit::create(["bl", "blb", "wl", "rl", "clk"], [], ["q"], [], "MEMCELL");
+    let (w1, w2) = c.split(wl);
+    let (k1, k2) = c.split(clk);
+    let set = c.and(bl, w1, k1);
+    let reset = c.and(blb, w2, k2);
+    let x = c.ndro(set, reset, rl);
+    c.unify(x, q);
+    return c;
+}
+
+// クロックを受ける配線. 最後に clk の SPLIT の木とつなぐ
+fn clock<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>(
+    c: &mut Circuit<N_I, N_CI, N_O, N_CO>,
+    sinks: &mut Vec<CounterWire>,
+) -> Wire {
+    let (wire, cwire) = c.gen_loop_unnamed();
+    sinks.push(cwire);
+    return wire;
+}
+
+fn delay<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>(
+    c: &mut Circuit<N_I, N_CI, N_O, N_CO>,
+    mut x: Wire,
+    n: usize,
+    sinks: &mut Vec<CounterWire>,
+) -> Wire {
+    for _ in 0..n {
+        let k = clock(c, sinks);
+        x = c.dff(x, k);
+    }
+    return x;
+}
+
+// 記憶セルの配列とデコーダを構築し, 読み出しポートごとの q_0, ..., q_{width-1} を返す
+// write, reads はそれぞれ (アドレス, イネーブル). labels は読み出しワード線の名前
+#[allow(clippy::too_many_arguments)]
+fn array<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>(
+    c: &mut Circuit<N_I, N_CI, N_O, N_CO>,
+    words: usize,
+    width: usize,
+    write: (Vec<Wire>, Wire),
+    data: Vec<Wire>,
+    reads: Vec<(Vec<Wire>, Wire)>,
+    labels: &[String],
+    clk: Wire,
+) -> Vec<Vec<Wire>> {
+    assert!(width > 0, "Width must be positive!");
+    let (name, s, outputs) = decoder(words);
+    let decoder = generate_netlist(&name, &s, &outputs);
+    let latency = decoder.latency();
+    let n_reads = reads.len();
+    let mut sinks = Vec::new();
+
+    // 書き込みワード線
+    let (mut args, we) = write;
+    args.push(we);
+    args.push(clock(c, &mut sinks));
+    let (wls, _) = c.instantiate(&decoder, args, Vec::new());
+    let mut wwls: Vec<Vec<Wire>> = Vec::new();
+    for (j, wl) in wls.into_iter().enumerate() {
+        c.label(&wl, &format!("wwl_{}", j));
+        wwls.push(c.split_tree(wl, width * n_reads));
+    }
+
+    // ビット線. ワード線と同じ段数だけ遅らせる
+    let mut bls: Vec<(Vec<Wire>, Vec<Wire>)> = Vec::new();
+    for (b, d) in data.into_iter().enumerate() {
+        let (d1, d2) = c.split(d);
+        let bl = delay(c, d1, latency, &mut sinks);
+        let blb = delay(c, d2, latency - 1, &mut sinks);
+        let k = clock(c, &mut sinks);
+        let blb = c.not(blb, k);
+        c.label(&bl, &format!("bl_{}", b));
+        c.label(&blb, &format!("blb_{}", b));
+        let bl = c.split_tree(bl, words * n_reads);
+        let blb = c.split_tree(blb, words * n_reads);
+        bls.push((bl, blb));
+    }
+
+    let cell = memory_cell();
+    let mut res = Vec::new();
+    for ((mut args, re), label) in reads.into_iter().zip(labels) {
+        args.push(re);
+        args.push(clock(c, &mut sinks));
+        let (rls, _) = c.instantiate(&decoder, args, Vec::new());
+        let mut bits: Vec<Vec<Wire>> = (0..width).map(|_| Vec::new()).collect();
+        for (j, rl) in rls.into_iter().enumerate() {
+            // 同じサイクルの書き込みがセルに届いてから読み出す
+            let rl = delay(c, rl, 1, &mut sinks);
+            c.label(&rl, &format!("{}_{}", label, j));
+            let mut rl = c.split_tree(rl, width);
+            for ((bl, blb), q) in bls.iter_mut().zip(bits.iter_mut()) {
+                let args = [
+                    bl.pop().unwrap(),
+                    blb.pop().unwrap(),
+                    wwls[j].pop().unwrap(),
+                    rl.pop().unwrap(),
+                    clock(c, &mut sinks),
+                ];
+                let ([x], []) = c.subcircuit(&cell, args, []);
+                q.push(x);
+            }
+        }
+        res.push(bits.into_iter().map(|q| c.merge_tree(q)).collect());
+    }
+
+    let clocks = c.split_tree(clk, sinks.len());
+    for (wire, cwire) in clocks.into_iter().zip(sinks) {
+        c.unify(wire, cwire);
+    }
+    return res;
+}
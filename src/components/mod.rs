@@ -0,0 +1,356 @@
+mod arithmetic;
+mod memory;
+mod race;
+mod sequential;
+
+use colored::Colorize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::circuit::Circuit;
+use crate::expr::{Expr, synthesize};
+use crate::logic::Op;
+use crate::netlist::Netlist;
+use crate::wire::Wire;
+
+pub use arithmetic::Arithmetic;
+pub use memory::Memory;
+pub use race::RaceLogic;
+pub use sequential::{Clocking, Sequential};
+
+#[derive(Debug, Clone)]
+enum Kind {
+    Gate(Op),
+    Circuit(Arc<Netlist>, usize), // (回路, 段数). 最後の入力ポートはクロック
+}
+
+#[derive(Debug, Clone)]
+struct Instance {
+    kind: Kind,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+}
+
+// ゲートとサブサーキットをつないだ部品の構造. 配線は名前で識別する
+// セルは入力側から順に加えること
+// build() で段数をそろえる DFF, 分岐の SPLIT, クロックの分配を加えて構築する
+#[derive(Debug, Clone)]
+pub(crate) struct Structure {
+    inputs: Vec<String>,
+    pub(crate) outputs: Vec<String>,
+    cells: Vec<Instance>,
+    n_temp: usize,
+}
+
+impl Structure {
+    pub(crate) fn new(inputs: &[String]) -> Self {
+        return Self {
+            inputs: inputs.to_vec(),
+            outputs: Vec::new(),
+            cells: Vec::new(),
+            n_temp: 0,
+        };
+    }
+
+    fn fresh(&mut self) -> String {
+        self.n_temp += 1;
+        return format!("$n{}", self.n_temp);
+    }
+
+    pub(crate) fn gate(&mut self, op: Op, inputs: &[&String]) -> String {
+        assert!(inputs.len() == op.arity());
+        let q = self.fresh();
+        self.cells.push(Instance {
+            kind: Kind::Gate(op),
+            inputs: inputs.iter().map(|s| s.to_string()).collect(),
+            outputs: vec![q.clone()],
+        });
+        return q;
+    }
+
+    // クロック以外の入力をつなぎ, 出力の配線を返す
+    pub(crate) fn instance<const N_I: usize, const N_O: usize>(
+        &mut self,
+        circuit: &Circuit<N_I, 0, N_O, 0>,
+        inputs: &[&String],
+    ) -> [String; N_O] {
+        assert!(inputs.len() + 1 == N_I);
+        let outputs: [String; N_O] = std::array::from_fn(|_| self.fresh());
+        self.cells.push(Instance {
+            kind: Kind::Circuit(Arc::new(circuit.netlist.clone()), circuit.netlist.latency()),
+            inputs: inputs.iter().map(|s| s.to_string()).collect(),
+            outputs: outputs.to_vec(),
+        });
+        return outputs;
+    }
+
+    // 段数をそろえた構造. 遅延は DFF のセルとして加え, 同じ配線の遅延は共有する
+    // 定数 (ZERO, ONE とそれだけから決まる配線) は段数を問わない
+    fn balanced(&self) -> Structure {
+        let mut res = Structure {
+            inputs: self.inputs.clone(),
+            outputs: Vec::new(),
+            cells: Vec::new(),
+            n_temp: self.n_temp,
+        };
+        let mut time: HashMap<String, Option<usize>> = HashMap::new();
+        for net in self.inputs.iter() {
+            time.insert(net.clone(), Some(0));
+        }
+        let mut delayed: HashMap<(String, usize), String> = HashMap::new();
+        let mut delay = |res: &mut Structure,
+                         time: &mut HashMap<String, Option<usize>>,
+                         net: &String,
+                         to: usize|
+         -> String {
+            let Some(from) = time[net] else {
+                return net.clone();
+            };
+            let mut q = net.clone();
+            for t in from + 1..=to {
+                q = match delayed.get(&(net.clone(), t)) {
+                    Some(d) => d.clone(),
+                    None => {
+                        let d = res.gate(Op::Dff, &[&q]);
+                        time.insert(d.clone(), Some(t));
+                        delayed.insert((net.clone(), t), d.clone());
+                        d
+                    }
+                };
+            }
+            return q;
+        };
+
+        for cell in self.cells.iter() {
+            let start = cell.inputs.iter().filter_map(|net| time[net]).max();
+            let latency = match &cell.kind {
+                Kind::Gate(op) if op.is_clocked() && op.arity() > 0 => 1,
+                Kind::Gate(_) => 0,
+                Kind::Circuit(_, latency) => *latency,
+            };
+            let inputs: Vec<String> = match start {
+                Some(start) => cell
+                    .inputs
+                    .iter()
+                    .map(|net| delay(&mut res, &mut time, net, start))
+                    .collect(),
+                None => cell.inputs.clone(),
+            };
+            for net in cell.outputs.iter() {
+                time.insert(net.clone(), start.map(|t| t + latency));
+            }
+            res.cells.push(Instance {
+                kind: cell.kind.clone(),
+                inputs,
+                outputs: cell.outputs.clone(),
+            });
+        }
+        let end = self.outputs.iter().filter_map(|net| time[net]).max();
+        res.outputs = match end {
+            Some(end) => self
+                .outputs
+                .iter()
+                .map(|net| delay(&mut res, &mut time, net, end))
+                .collect(),
+            None => self.outputs.clone(),
+        };
+        return res;
+    }
+
+    // Circuit 上に構築する. inputs は self.inputs に, 戻り値は self.outputs に対応する
+    pub(crate) fn build<
+        const N_I: usize,
+        const N_CI: usize,
+        const N_O: usize,
+        const N_CO: usize,
+    >(
+        &self,
+        circuit: &mut Circuit<N_I, N_CI, N_O, N_CO>,
+        inputs: Vec<Wire>,
+        clk: Wire,
+    ) -> Vec<Wire> {
+        let s = self.balanced();
+
+        // 各配線のファンアウト数
+        let mut fanout: HashMap<&str, usize> = HashMap::new();
+        for net in s
+            .cells
+            .iter()
+            .flat_map(|c| c.inputs.iter())
+            .chain(s.outputs.iter())
+        {
+            *fanout.entry(net).or_default() += 1;
+        }
+        let n_clocks = s
+            .cells
+            .iter()
+            .filter(|c| match &c.kind {
+                Kind::Gate(op) => op.is_clocked(),
+                Kind::Circuit(..) => true,
+            })
+            .count();
+        let mut clocks = circuit.split_tree(clk, n_clocks);
+
+        // 配線名 -> 未使用の分岐
+        let mut pool: HashMap<&str, Vec<Wire>> = HashMap::new();
+        for (net, wire) in s.inputs.iter().zip(inputs) {
+            let n = fanout.get(net.as_str()).copied().unwrap_or(0);
+            pool.insert(net, circuit.split_tree(wire, n));
+        }
+        for cell in s.cells.iter() {
+            let mut args: Vec<Wire> = cell
+                .inputs
+                .iter()
+                .map(|net| pool.get_mut(net.as_str()).unwrap().pop().unwrap())
+                .collect();
+            let outputs = match &cell.kind {
+                Kind::Gate(op) => {
+                    let mut a = args.into_iter();
+                    let mut arg = || a.next().unwrap();
+                    let q = match op {
+                        Op::Not => circuit.not(arg(), clocks.pop().unwrap()),
+                        Op::And => circuit.and(arg(), arg(), clocks.pop().unwrap()),
+                        Op::Or => circuit.or(arg(), arg(), clocks.pop().unwrap()),
+                        Op::Xor => circuit.xor(arg(), arg(), clocks.pop().unwrap()),
+                        Op::Xnor => circuit.xnor(arg(), arg(), clocks.pop().unwrap()),
+                        Op::Dff => circuit.dff(arg(), clocks.pop().unwrap()),
+                        Op::Zero => circuit.zero_async(),
+                        // 定数 1 はクロックごとにパルスを出す ONE_SYNC
+                        Op::One => circuit.one_sync(clocks.pop().unwrap()),
+                        Op::Buf => arg(),
+                    };
+                    vec![q]
+                }
+                Kind::Circuit(netlist, _) => {
+                    args.push(clocks.pop().unwrap());
+                    circuit.instantiate(netlist, args, Vec::new()).0
+                }
+            };
+            for (net, wire) in cell.outputs.iter().zip(outputs) {
+                let n = fanout.get(net.as_str()).copied().unwrap_or(0);
+                pool.insert(net, circuit.split_tree(wire, n));
+            }
+        }
+        return s
+            .outputs
+            .iter()
+            .map(|net| pool.get_mut(net.as_str()).unwrap().pop().unwrap())
+            .collect();
+    }
+}
+
+// a_0, ..., a_{width-1}
+pub(crate) fn bus(name: &str, width: usize) -> Vec<String> {
+    return (0..width).map(|i| format!("{}_{}", name, i)).collect();
+}
+
+// 生成した回路のポート数が型と一致するか
+pub(crate) fn assert_ports(name: &str, n_inputs: usize, n_outputs: usize, n_i: usize, n_o: usize) {
+    assert!(
+        n_inputs == n_i && n_outputs == n_o,
+        "{}",
+        format!(
+            "Circuit `{}` has {} inputs and {} outputs, but {} and {} are expected!",
+            name, n_inputs, n_outputs, n_i, n_o
+        )
+        .red()
+    );
+}
+
+// inputs (とクロック) を入力, structure.outputs を outputs として出力する回路
+pub(crate) fn generate<const N_I: usize, const N_O: usize>(
+    name: &str,
+    structure: &Structure,
+    outputs: &[String],
+) -> Circuit<N_I, 0, N_O, 0> {
+    assert_ports(name, structure.inputs.len() + 1, outputs.len(), N_I, N_O);
+    return construct(name, structure, outputs);
+}
+
+// ポート数を実行時に決める generate
+pub(crate) fn generate_netlist(name: &str, structure: &Structure, outputs: &[String]) -> Netlist {
+    return construct::<0, 0>(name, structure, outputs).netlist;
+}
+
+fn construct<const N_I: usize, const N_O: usize>(
+    name: &str,
+    structure: &Structure,
+    outputs: &[String],
+) -> Circuit<N_I, 0, N_O, 0> {
+    let input_names: Vec<&str> = structure
+        .inputs
+        .iter()
+        .map(|s| s.as_str())
+        .chain(["clk"])
+        .collect();
+    let output_names: Vec<&str> = outputs.iter().map(|s| s.as_str()).collect();
+    let (mut circuit, mut wires, _, output_cwires, _) =
+        Circuit::create_ports(&input_names, &[], &output_names, &[], name);
+    let clk = wires.pop().unwrap();
+    let wires = structure.build(&mut circuit, wires, clk);
+    for (mut wire, cwire) in wires.into_iter().zip(output_cwires) {
+        // 入力ポートなど名前付きの配線は JTL を挟んで出力ポートにつなぐ
+        if circuit.is_named(&wire) {
+            wire = circuit.jtl(wire);
+        }
+        circuit.unify(wire, cwire);
+    }
+    return circuit;
+}
+
+// 論理式で定義したセル. 入力ポートの最後にクロックを加える
+pub(crate) fn cell<const N_I: usize, const N_O: usize>(
+    name: &str,
+    inputs: &[&str],
+    outputs: [(&str, &str); N_O],
+) -> Circuit<N_I, 0, N_O, 0> {
+    let ports: Vec<&str> = inputs.iter().copied().chain(["clk"]).collect();
+    let (mut circuit, input_wires, _, output_cwires, _) = Circuit::create(
+        ports.try_into().unwrap(),
+        [],
+        outputs.map(|(port, _)| port),
+        [],
+        name,
+    );
+    let exprs: Vec<Expr> = outputs
+        .iter()
+        .map(|(_, src)| Expr::parse(src).unwrap())
+        .collect();
+    let exprs: Vec<&Expr> = exprs.iter().collect();
+    let mut wires: Vec<Wire> = input_wires.into();
+    let clk = wires.pop().unwrap();
+    let wires = synthesize(&mut circuit, &exprs, inputs, wires, clk);
+    for (wire, cwire) in wires.into_iter().zip(output_cwires) {
+        circuit.unify(wire, cwire);
+    }
+    return circuit;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Structure, generate};
+    use crate::circuit::Circuit;
+    use crate::equivalence::{EquivalenceChecker, Verdict};
+    use crate::logic::Op;
+
+    // 定数 1 はクロックをそのまま出すのではなく ONE_SYNC で作る
+    #[test]
+    fn constant_one() {
+        let mut s = Structure::new(&["a".to_string()]);
+        let a = "a".to_string();
+        let one = s.gate(Op::One, &[]);
+        s.outputs = vec![s.gate(Op::And, &[&a, &one])];
+        let c: Circuit<2, 0, 1, 0> = generate("ONE", &s, &["y".to_string()]);
+        assert!(c.netlist.gates.iter().any(|g| g.kind() == "ONE_SYNC"));
+
+        // latency() は ONE_SYNC も 1 段と数えるので, リセットから毎サイクル比べる
+        let (mut reference, [a, clk], [], [y], []) =
+            Circuit::create(["a", "clk"], [], ["y"], [], "DFF");
+        let q = reference.dff(a, clk);
+        reference.unify(q, y);
+        let res = EquivalenceChecker::new()
+            .latency(0, 0)
+            .check(&c, &reference);
+        assert_eq!(res, Ok(Verdict::Proven));
+    }
+}
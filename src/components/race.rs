@@ -0,0 +1,135 @@
+use super::{assert_ports, bus};
+use crate::circuit::Circuit;
+use crate::wire::Wire;
+
+// 値をパルスの到着時刻で表すレースロジックの生成器
+// すべてのセルの遅延が等しいものとして, 経路の段数を JTL の遅延線でそろえる
+pub struct RaceLogic;
+
+impl RaceLogic {
+    // 最も早い到着: x_0, ..., x_{n-1} -> q
+    pub fn min<const N_I: usize>(n: usize) -> Circuit<N_I, 0, 1, 0> {
+        return reduce(&format!("MIN{}", n), n, |c, a, b| c.first_arrival(a, b));
+    }
+
+    // 最も遅い到着: x_0, ..., x_{n-1} -> q
+    pub fn max<const N_I: usize>(n: usize) -> Circuit<N_I, 0, 1, 0> {
+        return reduce(&format!("MAX{}", n), n, |c, a, b| c.last_arrival(a, b));
+    }
+
+    // Batcher の奇偶マージソートによる整列回路: x_0, ..., x_{n-1} -> y_0, ..., y_{n-1}
+    // y_0 が最も早く, y_{n-1} が最も遅い到着になる
+    pub fn sorting_network<const N_I: usize, const N_O: usize>(
+        n: usize,
+    ) -> Circuit<N_I, 0, N_O, 0> {
+        assert!(n > 0, "Inputs must be positive!");
+        let name = format!("SORT{}", n);
+        assert_ports(&name, n, n, N_I, N_O);
+        let inputs = bus("x", n);
+        let outputs = bus("y", n);
+        let inputs: Vec<&str> = inputs.iter().map(|s| s.as_str()).collect();
+        let outputs: Vec<&str> = outputs.iter().map(|s| s.as_str()).collect();
+        let (mut c, wires, _, output_cwires, _) =
+            Circuit::create_ports(&inputs, &[], &outputs, &[], &name);
+
+        let cell = compare_exchange();
+        let mut wires: Vec<Option<Wire>> = wires.into_iter().map(Some).collect();
+        for layer in odd_even_merge_sort(n) {
+            let mut next: Vec<Option<Wire>> = (0..n).map(|_| None).collect();
+            for (i, j) in layer {
+                let args = [wires[i].take().unwrap(), wires[j].take().unwrap()];
+                let ([lo, hi], []) = c.subcircuit(&cell, args, []);
+                next[i] = Some(lo);
+                next[j] = Some(hi);
+            }
+            // 比較しない配線は SPLIT と FIRST_ARRIVAL / LAST_ARRIVAL の 2 段分遅らせる
+            for (w, x) in wires.iter_mut().zip(next.iter_mut()) {
+                if let Some(w) = w.take() {
+                    *x = Some(c.delay(w, 2));
+                }
+            }
+            wires = next;
+        }
+        for (wire, cwire) in wires.into_iter().zip(output_cwires) {
+            let mut wire = wire.unwrap();
+            if c.is_named(&wire) {
+                wire = c.jtl(wire);
+            }
+            c.unify(wire, cwire);
+        }
+        return c;
+    }
+}
+
+// 2 入力のセルの木で x_0, ..., x_{n-1} を 1 つにまとめる
+fn reduce<const N_I: usize>(
+    name: &str,
+    n: usize,
+    op: impl Fn(&mut Circuit<N_I, 0, 1, 0>, Wire, Wire) -> Wire,
+) -> Circuit<N_I, 0, 1, 0> {
+    assert!(n > 0, "Inputs must be positive!");
+    assert_ports(name, n, 1, N_I, 1);
+    let inputs = bus("x", n);
+    let inputs: Vec<&str> = inputs.iter().map(|s| s.as_str()).collect();
+    let (mut c, mut wires, _, mut output_cwires, _) =
+        Circuit::create_ports(&inputs, &[], &["q"], &[], name);
+
+    while wires.len() > 1 {
+        let mut next = Vec::new();
+        let mut rest = wires.into_iter();
+        while let Some(a) = rest.next() {
+            match rest.next() {
+                Some(b) => next.push(op(&mut c, a, b)),
+                // 余った配線はセル 1 段分遅らせる
+                None => next.push(c.delay(a, 1)),
+            }
+        }
+        wires = next;
+    }
+    let mut wire = wires.pop().unwrap();
+    if c.is_named(&wire) {
+        wire = c.jtl(wire);
+    }
+    c.unify(wire, output_cwires.pop().unwrap());
+    return c;
+}
+
+// a, b -> lo, hi (早い方, 遅い方)
+fn compare_exchange() -> Circuit<2, 0, 2, 0> {
+    let (mut c, [a, b], [], [lo, hi], []) =
+        Circuit::create(["a", "b"], [], ["lo", "hi"], [], "CAS");
+    let (a1, a2) = c.split(a);
+    let (b1, b2) = c.split(b);
+    let first = c.first_arrival(a1, b1);
+    let last = c.last_arrival(a2, b2);
+    c.unify(first, lo);
+    c.unify(last, hi);
+    return c;
+}
+
+// 比較器 (i, j) (i < j) の段ごとの列. 同じ段の比較器は配線を共有しない
+fn odd_even_merge_sort(n: usize) -> Vec<Vec<(usize, usize)>> {
+    let mut res = Vec::new();
+    let mut p = 1;
+    while p < n {
+        let mut k = p;
+        while k >= 1 {
+            let mut layer = Vec::new();
+            let mut j = k % p;
+            while j + k < n {
+                for i in 0..k.min(n - j - k) {
+                    if (i + j) / (2 * p) == (i + j + k) / (2 * p) {
+                        layer.push((i + j, i + j + k));
+                    }
+                }
+                j += 2 * k;
+            }
+            if !layer.is_empty() {
+                res.push(layer);
+            }
+            k /= 2;
+        }
+        p *= 2;
+    }
+    return res;
+}
@@ -0,0 +1,260 @@
+use super::{assert_ports, bus};
+use crate::circuit::Circuit;
+use crate::wire::Wire;
+
+// シフトレジスタのクロックを配る向き
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clocking {
+    Concurrent,  // データと同じ向き. 入力側の DFF から順にクロックが届く
+    CounterFlow, // データと逆向き. 出力側の DFF から順にクロックが届く
+}
+
+// シフトレジスタ, カウンタ, FIFO の生成器
+pub struct Sequential;
+
+impl Sequential {
+    // DFF を depth 段つないだシフトレジスタ: d, clk -> q
+    // クロックは SPLIT の鎖で各段に配る
+    pub fn shift_register(depth: usize, clocking: Clocking) -> Circuit<2, 0, 1, 0> {
+        assert!(depth > 0, "Depth must be positive!");
+        let name = match clocking {
+            Clocking::Concurrent => format!("SR{}", depth),
+            Clocking::CounterFlow => format!("SRCF{}", depth),
+        };
+        let (mut c, [d, clk], [], [q], []) = Circuit::create(["d", "clk"], [], ["q"], [], &name);
+
+        // 入力側の段から順にクロックを並べる
+        let mut clocks = Vec::new();
+        match clocking {
+            Clocking::Concurrent => {
+                let mut rest = clk;
+                for _ in 1..depth {
+                    let (k, r) = c.split(rest);
+                    clocks.push(k);
+                    rest = r;
+                }
+                clocks.push(rest);
+            }
+            Clocking::CounterFlow => {
+                // 後段から来るクロックを CounterWire で受け取る
+                let (k, mut upstream) = c.gen_loop_unnamed();
+                clocks.push(k);
+                for _ in 1..depth {
+                    let (k, u) = c.csplit(upstream);
+                    clocks.push(k);
+                    upstream = u;
+                }
+                c.unify(clk, upstream);
+            }
+        }
+
+        let mut x = d;
+        for k in clocks {
+            x = c.dff(x, k);
+        }
+        c.unify(x, q);
+        return c;
+    }
+
+    // TOGGLE を width 段つないだリップルカウンタ: a, clk -> q_0, ..., q_{width-1}
+    // a のパルスを数え, 各ビットが 1 の間は毎サイクル q_i にパルスを出す
+    // 桁上げは 1 段ごとに 1 サイクル遅れて伝わる
+    pub fn ripple_counter<const N_O: usize>(width: usize) -> Circuit<2, 0, N_O, 0> {
+        assert!(width > 0, "Width must be positive!");
+        let name = format!("CNT{}", width);
+        let outputs = bus("q", width);
+        assert_ports(&name, 2, outputs.len(), 2, N_O);
+        let outputs: Vec<&str> = outputs.iter().map(|s| s.as_str()).collect();
+        let (mut c, [a, clk], [], qs, []) =
+            Circuit::create(["a", "clk"], [], outputs.try_into().unwrap(), [], &name);
+
+        let (bits, carry) = toggles(&mut c, a, clk, width);
+        c.terminate(carry);
+        for (bit, q) in bits.into_iter().zip(qs) {
+            c.unify(bit, q);
+        }
+        return c;
+    }
+
+    // TOGGLE を stages 段つないだ分周器: a, clk -> q
+    // a の 2^stages 個のパルスごとに q にパルスを 1 つ出す
+    pub fn frequency_divider(stages: usize) -> Circuit<2, 0, 1, 0> {
+        assert!(stages > 0, "Stages must be positive!");
+        let name = format!("DIV{}", 1usize << stages);
+        let (mut c, [a, clk], [], [q], []) = Circuit::create(["a", "clk"], [], ["q"], [], &name);
+
+        let (bits, carry) = toggles(&mut c, a, clk, stages);
+        for bit in bits {
+            c.terminate(bit);
+        }
+        c.unify(carry, q);
+        return c;
+    }
+
+    // TFF を stages 段つないだ非同期の分周器: a -> q
+    // クロックは使わず, a の 2^stages 個のパルスごとに q にパルスを 1 つ出す
+    pub fn tff_divider(stages: usize) -> Circuit<1, 0, 1, 0> {
+        assert!(stages > 0, "Stages must be positive!");
+        let name = format!("TDIV{}", 1usize << stages);
+        let (mut c, [a], [], [q], []) = Circuit::create(["a"], [], ["q"], [], &name);
+
+        let mut x = a;
+        for _ in 0..stages {
+            x = c.tff(x);
+        }
+        c.unify(x, q);
+        return c;
+    }
+
+    // NDRO にデータを蓄える FIFO: d_0, ..., d_{width-1}, push, pop -> q_0, ..., q_{width-1}
+    // クロックは使わず, push のパルスで d を書き込み, pop のパルスで最も古いデータを q に読み出す
+    // d のパルスは push より先に届くこと. 満杯での push と空での pop は呼び出し側で避ける
+    pub fn fifo<const N_I: usize, const N_O: usize>(
+        width: usize,
+        depth: usize,
+    ) -> Circuit<N_I, 0, N_O, 0> {
+        assert!(width > 0, "Width must be positive!");
+        assert!(depth > 0, "Depth must be positive!");
+        let name = format!("FIFO{}X{}", width, depth);
+        let mut inputs = bus("d", width);
+        inputs.extend(["push".to_string(), "pop".to_string()]);
+        let outputs = bus("q", width);
+        assert_ports(&name, inputs.len(), outputs.len(), N_I, N_O);
+        let inputs: Vec<&str> = inputs.iter().map(|s| s.as_str()).collect();
+        let outputs: Vec<&str> = outputs.iter().map(|s| s.as_str()).collect();
+        let (mut c, input_wires, [], output_cwires, []) = Circuit::create(
+            inputs.try_into().unwrap(),
+            [],
+            outputs.try_into().unwrap(),
+            [],
+            &name,
+        );
+        let mut wires: Vec<Wire> = input_wires.into();
+        let pop = wires.pop().unwrap();
+        let push = wires.pop().unwrap();
+
+        let (push1, push2) = c.split(push);
+        let write = pointer(&mut c, push1, depth);
+        let read = pointer(&mut c, pop, depth);
+        let mut wclks = c.split_tree(push2, width * depth);
+        let mut data: Vec<Vec<Wire>> = wires.into_iter().map(|d| c.split_tree(d, depth)).collect();
+
+        let cell = storage();
+        let mut bits: Vec<Vec<Wire>> = (0..width).map(|_| Vec::new()).collect();
+        for (we, re) in write.into_iter().zip(read) {
+            let mut wes = c.split_tree(we, width);
+            let mut res = c.split_tree(re, width);
+            for (d, q) in data.iter_mut().zip(bits.iter_mut()) {
+                let args = [
+                    d.pop().unwrap(),
+                    wes.pop().unwrap(),
+                    wclks.pop().unwrap(),
+                    res.pop().unwrap(),
+                ];
+                let ([x], []) = c.subcircuit(&cell, args, []);
+                q.push(x);
+            }
+        }
+        for (q, cwire) in bits.into_iter().zip(output_cwires) {
+            let x = c.merge_tree(q);
+            c.unify(x, cwire);
+        }
+        return c;
+    }
+}
+
+// クロック同期の T フリップフロップ: t, clk -> q, c
+// 状態 s を s ^ t に更新し, 1 の間は毎サイクル q に, 1 から 0 に戻るときに c にパルスを出す
+fn toggle() -> Circuit<2, 0, 2, 0> {
+    let (mut c, [t, clk], [], [q, carry], []) =
+        Circuit::create(["t", "clk"], [], ["q", "c"], [], "TOGGLE");
+    let (t1, t2) = c.split(t);
+    let (k1, k2) = c.split(clk);
+    let (s, s_loop) = c.gen_loop("s");
+    let (s1, s2) = c.split(s);
+    let next = c.xor(s1, t1, k1);
+    let (next1, next2) = c.split(next);
+    c.unify(next1, s_loop);
+    c.unify(next2, q);
+    let x = c.and(s2, t2, k2);
+    c.unify(x, carry);
+    return c;
+}
+
+// TOGGLE を n 段つなぐ. (各段の q, 最後の段の c) を返す
+fn toggles<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>(
+    c: &mut Circuit<N_I, N_CI, N_O, N_CO>,
+    t: Wire,
+    clk: Wire,
+    n: usize,
+) -> (Vec<Wire>, Wire) {
+    let cell = toggle();
+    let mut clocks = c.split_tree(clk, n);
+    let mut t = t;
+    let mut res = Vec::new();
+    for _ in 0..n {
+        let ([q, carry], []) = c.subcircuit(&cell, [t, clocks.pop().unwrap()], []);
+        res.push(q);
+        t = carry;
+    }
+    return (res, t);
+}
+
+// FIFO の 1 ビット: d, we, wclk, re -> q
+// d と we がそろっていれば wclk で書き込み, re で読み出してから消去する
+fn storage() -> Circuit<4, 0, 1, 0> {
+    let (mut c, [d, we, wclk, re], [], [q], []) =
+        Circuit::create(["d", "we", "wclk", "re"], [], ["q"], [], "FIFOCELL");
+    let set = c.and(d, we, wclk);
+    let (r1, r2) = c.split(re);
+    let r2 = c.jtl(r2);
+    let x = c.ndro(set, r2, r1);
+    c.unify(x, q);
+    return c;
+}
+
+// 1-hot のポインタ. event のパルスごとに現在の位置の選択パルスを出し, 次の位置へ進む
+// NDRO の初期状態は 0 なので, 位置 0 はどの NDRO もトークンを持たない状態で表す
+fn pointer<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>(
+    c: &mut Circuit<N_I, N_CI, N_O, N_CO>,
+    event: Wire,
+    depth: usize,
+) -> Vec<Wire> {
+    let mut events = c.split_tree(event, depth);
+    let not_clk = events.pop().unwrap();
+    let mut sets = Vec::new();
+    let mut resets = Vec::new();
+    let mut tokens = Vec::new();
+    let mut selects = Vec::new();
+    for k in events {
+        let (a, a_loop) = c.gen_loop_unnamed();
+        let (b, b_loop) = c.gen_loop_unnamed();
+        let q = c.ndro(a, b, k);
+        let (q1, q2) = c.split(q);
+        sets.push(a_loop);
+        resets.push(b_loop);
+        tokens.push(q1);
+        selects.push(q2);
+    }
+    // どの NDRO も読み出されなければ位置 0
+    let any = c.merge_tree(tokens);
+    let first = c.not(any, not_clk);
+    selects.insert(0, first);
+
+    // 選択パルスで自身の NDRO を消去し, 次の位置の NDRO をセットする
+    let mut sets = sets.into_iter();
+    let mut resets = resets.into_iter();
+    let mut res = Vec::new();
+    for (j, sel) in selects.into_iter().enumerate() {
+        let n = 1 + usize::from(j > 0) + usize::from(j + 1 < depth);
+        let mut wires = c.split_tree(sel, n);
+        res.push(wires.pop().unwrap());
+        if j > 0 {
+            c.unify(wires.pop().unwrap(), resets.next().unwrap());
+        }
+        if j + 1 < depth {
+            c.unify(wires.pop().unwrap(), sets.next().unwrap());
+        }
+    }
+    return res;
+}
@@ -0,0 +1,500 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::gate::{Gate, gate_name};
+use crate::id::WireID;
+use crate::netlist::Netlist;
+
+// 配線に載る定数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Const {
+    Zero, // パルスが来ない
+    One,  // 毎サイクルパルスが来る
+}
+
+impl Netlist {
+    // 定数を受けるゲートを簡単化した回路. サブサーキットは展開する
+    // 書き換えはリセットからのサイクルごとの出力を変えないものに限る
+    // 配線のつながりが正しくない回路はエラーを返す
+    pub fn propagate_constants(&self) -> Result<Netlist, String> {
+        let mut res = self.flatten();
+        res.check()?;
+        let mut next_id = res.next_gate_id();
+        while let Some(gates) = Folder::new(&res).run(&mut next_id)? {
+            res.gates = gates;
+        }
+
+        // 使われなくなった配線を消す
+        let mut used: HashSet<WireID> = HashSet::new();
+        for gate in res.gates.iter() {
+            used.extend(gate.inputs().into_iter().map(|(_, wid)| wid));
+            used.extend(gate.outputs().into_iter().map(|(_, wid)| wid));
+        }
+        let ports: HashSet<String> = res
+            .input_ports()
+            .chain(res.output_ports())
+            .cloned()
+            .collect();
+        res.wire_names
+            .retain(|wid, name| used.contains(wid) || ports.contains(name));
+        let wire_names = &res.wire_names;
+        res.counter_wires.retain(|wid| wire_names.contains_key(wid));
+        res.check()?;
+        return Ok(res);
+    }
+}
+
+// 1 つのゲートの書き換え
+#[derive(Debug, Default)]
+struct Rewrite {
+    remove: Vec<usize>,                    // 取り除くゲート
+    add: Vec<(&'static str, Vec<WireID>)>, // 追加するゲートの種類と pin_names() の順のピン
+    connect: Option<(WireID, WireID)>,     // (x, y): y を受けていたところに x をつなぐ
+}
+
+impl Rewrite {
+    fn new(remove: &[usize]) -> Self {
+        return Self {
+            remove: remove.to_vec(),
+            ..Default::default()
+        };
+    }
+
+    fn add(&mut self, kind: &'static str, pins: &[WireID]) {
+        self.add.push((kind, pins.to_vec()));
+    }
+}
+
+// 1 回分の書き換え. 同じ回に書き換えたゲートに関わる書き換えは次の回に回す
+struct Folder<'a> {
+    netlist: &'a Netlist,
+    gates: Vec<Option<Gate>>,
+    added: Vec<Gate>,
+    touched: HashSet<usize>,
+    driver: HashMap<WireID, usize>,
+    receiver: HashMap<WireID, usize>,
+}
+
+impl<'a> Folder<'a> {
+    fn new(netlist: &'a Netlist) -> Self {
+        let mut driver = HashMap::new();
+        let mut receiver = HashMap::new();
+        for (g, gate) in netlist.gates.iter().enumerate() {
+            for (_, wid) in gate.inputs() {
+                receiver.insert(wid, g);
+            }
+            for (_, wid) in gate.outputs() {
+                driver.insert(wid, g);
+            }
+        }
+        return Self {
+            netlist,
+            gates: netlist.gates.iter().cloned().map(Some).collect(),
+            added: Vec::new(),
+            touched: HashSet::new(),
+            driver,
+            receiver,
+        };
+    }
+
+    // 書き換えたゲートの列. 何も書き換えなければ None
+    fn run(mut self, next_id: &mut u32) -> Result<Option<Vec<Gate>>, String> {
+        for g in 0..self.gates.len() {
+            if self.touched.contains(&g) {
+                continue;
+            }
+            if let Some(rewrite) = self.plan(g) {
+                self.apply(rewrite, next_id)?;
+            }
+        }
+        if self.touched.is_empty() {
+            return Ok(None);
+        }
+        return Ok(Some(
+            self.gates.into_iter().flatten().chain(self.added).collect(),
+        ));
+    }
+
+    // net の定数. SPLIT, JTL, BUFF を遡って定数のゲートを探す
+    fn value(&self, mut net: WireID) -> Option<Const> {
+        // 非同期ゲートのループに備えて遡る回数を制限する
+        for _ in 0..self.gates.len() {
+            let g = *self.driver.get(&net)?;
+            if self.touched.contains(&g) {
+                return None;
+            }
+            let gate = self.gates[g].as_ref()?;
+            match gate.kind() {
+                "ZERO_ASYNC" | "ZERO_SYNC" => return Some(Const::Zero),
+                "ONE_SYNC" => return Some(Const::One),
+                "SPLIT" | "SPLIT3" | "SPLIT4" | "JTL" | "BUFF" => net = gate.inputs()[0].1,
+                _ => return None,
+            }
+        }
+        return None;
+    }
+
+    fn is_port(&self, net: WireID, ports: impl Iterator<Item = &'a String>) -> bool {
+        let name = self.netlist.wire_name(&net);
+        return ports.into_iter().any(|p| p == name);
+    }
+
+    fn plan(&self, g: usize) -> Option<Rewrite> {
+        let gate = self.gates[g].as_ref()?;
+        let kind = gate.kind();
+        let ins: Vec<WireID> = gate.inputs().into_iter().map(|(_, wid)| wid).collect();
+        let outs: Vec<WireID> = gate.outputs().into_iter().map(|(_, wid)| wid).collect();
+        if kind == "TERMINATE" {
+            return self.plan_terminate(g, ins[0]);
+        }
+        let v: Vec<Option<Const>> = ins.iter().map(|wid| self.value(*wid)).collect();
+        let zero = |i: usize| v[i] == Some(Const::Zero);
+        let one = |i: usize| v[i] == Some(Const::One);
+        let mut rw = Rewrite::new(&[g]);
+
+        // 出力にパルスが出ないゲートは ZERO_ASYNC に置き換え, 入力をすべて終端する
+        let silent = match kind {
+            "ZERO_SYNC" => true,
+            "AND" | "LAST_ARRIVAL" => zero(0) || zero(1),
+            "DFFR" | "DFFR_SYNC" => zero(0) || one(1),
+            "JTL" | "BUFF" | "SPLIT" | "SPLIT3" | "SPLIT4" | "DCSFQ" | "SFQDC" | "TFF" | "TFF2"
+            | "TFFR" | "DFF" | "NDRO" | "INHIBIT" => zero(0),
+            _ => false,
+        };
+        if silent {
+            for q in outs.iter() {
+                rw.add("ZERO_ASYNC", &[*q]);
+            }
+            for a in ins.iter() {
+                rw.add("TERMINATE", &[*a]);
+            }
+            return Some(rw);
+        }
+
+        // 定数の入力を終端し, 残りの入力で同じ出力になるゲートに置き換える
+        let data: Vec<usize> = gate
+            .inputs()
+            .iter()
+            .enumerate()
+            .filter(|(_, (pin, _))| *pin != "clk")
+            .map(|(i, _)| i)
+            .collect();
+        let i = data
+            .iter()
+            .copied()
+            .find(|i| zero(*i))
+            .or_else(|| data.iter().copied().find(|i| one(*i)))?;
+        let clk = *ins.last().unwrap();
+        let rest: Vec<WireID> = (0..ins.len()).filter(|j| *j != i).map(|j| ins[j]).collect();
+        let mut terminated = ins[i];
+        match (kind, v[i].unwrap()) {
+            ("MERGE", Const::Zero) => rw.connect = Some((rest[0], outs[0])),
+            ("MERGE3", Const::Zero) => rw.add("MERGE", &[rest[0], rest[1], outs[0]]),
+            ("MERGE4", Const::Zero) => rw.add("MERGE3", &[rest[0], rest[1], rest[2], outs[0]]),
+            ("OR" | "XOR", Const::Zero) | ("AND", Const::One) => {
+                rw.add("DFF", &[rest[0], clk, outs[0]])
+            }
+            ("XNOR", Const::Zero) => rw.add("NOT", &[rest[0], clk, outs[0]]),
+            // 1 は残し, もう一方の入力を終端する
+            ("OR", Const::One) => {
+                rw.add("DFF", &[ins[i], clk, outs[0]]);
+                terminated = rest[0];
+            }
+            ("INHIBIT", Const::Zero) if i == 1 => rw.connect = Some((ins[0], outs[0])),
+            ("DFFR" | "DFFR_SYNC", Const::Zero) if i == 1 => rw.add("DFF", &[ins[0], clk, outs[0]]),
+            ("TFFR", Const::Zero) if i == 1 => rw.add("TFF", &[ins[0], outs[0]]),
+            ("NOT", Const::Zero) => rw.add("ONE_SYNC", &[clk, outs[0]]),
+            ("DFFC" | "NDROC", Const::Zero) if i == 0 => {
+                rw.add("ZERO_ASYNC", &[outs[0]]);
+                rw.add("ONE_SYNC", &[clk, outs[1]]);
+                if kind == "NDROC" {
+                    rw.add("TERMINATE", &[ins[1]]);
+                }
+            }
+            _ => return None,
+        }
+        rw.add("TERMINATE", &[terminated]);
+        return Some(rw);
+    }
+
+    // 出力が終端されたゲートを取り除く
+    fn plan_terminate(&self, g: usize, a: WireID) -> Option<Rewrite> {
+        let d = *self.driver.get(&a)?;
+        let gate = self.gates[d].as_ref()?;
+        let ins: Vec<WireID> = gate.inputs().into_iter().map(|(_, wid)| wid).collect();
+        let outs: Vec<WireID> = gate.outputs().into_iter().map(|(_, wid)| wid).collect();
+        let rest: Vec<WireID> = outs.iter().copied().filter(|q| *q != a).collect();
+        let mut rw = Rewrite::new(&[g, d]);
+        match gate.kind() {
+            _ if outs.len() == 1 => {
+                for x in ins.iter() {
+                    rw.add("TERMINATE", &[*x]);
+                }
+            }
+            "SPLIT" => rw.connect = Some((ins[0], rest[0])),
+            "SPLIT3" => rw.add("SPLIT", &[ins[0], rest[0], rest[1]]),
+            "SPLIT4" => rw.add("SPLIT3", &[ins[0], rest[0], rest[1], rest[2]]),
+            "TFF2" if a == outs[0] => rw.add("TFF", &[ins[0], outs[1]]),
+            // DFFC の q2 は状態が 0 のときに出る
+            "DFFC" if a == outs[0] => rw.add("NOT", &[ins[0], ins[1], outs[1]]),
+            "DFFC" => rw.add("DFF", &[ins[0], ins[1], outs[0]]),
+            "NDROC" if a == outs[1] => rw.add("NDRO", &[ins[0], ins[1], ins[2], outs[0]]),
+            _ => return None,
+        }
+        return Some(rw);
+    }
+
+    fn apply(&mut self, rw: Rewrite, next_id: &mut u32) -> Result<(), String> {
+        let missing = |net: &WireID, role: &str| {
+            format!("`{}` has no {}!", self.netlist.wire_name(net), role)
+        };
+        // connect でピンを付け替えるゲート. 入力ポートと出力ポートをつなぐときは None
+        let target = match rw.connect {
+            Some((x, y)) if self.is_port(y, self.netlist.output_ports()) => {
+                if self.is_port(x, self.netlist.input_ports()) {
+                    None
+                } else {
+                    Some(*self.driver.get(&x).ok_or_else(|| missing(&x, "driver"))?)
+                }
+            }
+            Some((_, y)) => Some(
+                *self
+                    .receiver
+                    .get(&y)
+                    .ok_or_else(|| missing(&y, "receiver"))?,
+            ),
+            None => None,
+        };
+        let mut involved = rw.remove.clone();
+        if let Some(t) = target {
+            if rw.remove.contains(&t) {
+                return Ok(());
+            }
+            involved.push(t);
+        }
+        if involved.iter().any(|g| self.touched.contains(g)) {
+            return Ok(());
+        }
+
+        for g in rw.remove.iter() {
+            self.gates[*g] = None;
+        }
+        let mut add = rw.add;
+        if let Some((x, y)) = rw.connect {
+            match target {
+                // 出力ポートは名前を変えられないので, x のドライバを y につなぐ
+                Some(t) if self.is_port(y, self.netlist.output_ports()) => {
+                    self.gates[t] = Some(replace_pin(self.gates[t].as_ref().unwrap(), x, y)?);
+                }
+                Some(t) => {
+                    self.gates[t] = Some(replace_pin(self.gates[t].as_ref().unwrap(), y, x)?);
+                }
+                None => add.push(("JTL", vec![x, y])),
+            }
+        }
+        for (kind, pins) in add {
+            let gate = Gate::from_pins(kind, gate_name(kind, *next_id), &pins)
+                .ok_or_else(|| format!("Pins of `{}` do not match!", kind))?;
+            *next_id += 1;
+            self.added.push(gate);
+        }
+        self.touched.extend(involved);
+        return Ok(());
+    }
+}
+
+fn replace_pin(gate: &Gate, old: WireID, new: WireID) -> Result<Gate, String> {
+    let pins: Vec<WireID> = gate
+        .inputs()
+        .into_iter()
+        .chain(gate.outputs())
+        .map(|(_, wid)| if wid == old { new } else { wid })
+        .collect();
+    return Gate::from_pins(gate.kind(), gate.name().to_string(), &pins)
+        .ok_or_else(|| format!("`{}` cannot be rewired!", gate.name()));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::Circuit;
+    use crate::equivalence::EquivalenceChecker;
+    use crate::wire::Wire;
+
+    type Gate1 = Circuit<2, 0, 1, 0>;
+
+    // a, clk -> q の回路
+    fn circuit(build: impl Fn(&mut Gate1, Wire, Wire) -> Wire) -> Gate1 {
+        let (mut c, [a, clk], [], [q], []) = Circuit::create(["a", "clk"], [], ["q"], [], "top");
+        let x = build(&mut c, a, clk);
+        c.unify(x, q);
+        return c;
+    }
+
+    fn kinds(c: &Gate1) -> Vec<&str> {
+        return c.netlist.gates.iter().map(|g| g.kind()).collect();
+    }
+
+    // kind のゲートが消え, リセットからのすべてのサイクルで元の回路と同じ出力になること
+    fn assert_folded(kind: &str, build: impl Fn(&mut Gate1, Wire, Wire) -> Wire) {
+        let c = circuit(build);
+        let folded = c.propagate_constants().unwrap();
+        assert!(
+            !kinds(&folded).contains(&kind),
+            "{}: {:?}",
+            kind,
+            kinds(&folded)
+        );
+        let res = EquivalenceChecker::new().latency(0, 0).check(&folded, &c);
+        assert!(res.is_ok(), "{}: {}", kind, res.unwrap_err());
+        assert!(res.unwrap().is_proven());
+    }
+
+    #[test]
+    fn clocked_gates() {
+        assert_folded("AND", |c, a, clk| {
+            let z = c.zero_async();
+            c.and(a, z, clk)
+        });
+        assert_folded("AND", |c, a, clk| {
+            let (k1, k2) = c.split(clk);
+            let one = c.one_sync(k1);
+            c.and(a, one, k2)
+        });
+        assert_folded("OR", |c, a, clk| {
+            let z = c.zero_async();
+            c.or(z, a, clk)
+        });
+        assert_folded("OR", |c, a, clk| {
+            let (k1, k2) = c.split(clk);
+            let one = c.one_sync(k1);
+            c.or(a, one, k2)
+        });
+        assert_folded("XOR", |c, a, clk| {
+            let (k1, k2) = c.split(clk);
+            let z = c.zero_sync(k1);
+            c.xor(a, z, k2)
+        });
+        assert_folded("XNOR", |c, a, clk| {
+            let z = c.zero_async();
+            c.xnor(a, z, clk)
+        });
+        assert_folded("NOT", |c, a, clk| {
+            c.terminate(a);
+            let z = c.zero_async();
+            c.not(z, clk)
+        });
+    }
+
+    #[test]
+    fn storage_cells() {
+        assert_folded("DFF", |c, a, clk| {
+            c.terminate(a);
+            let z = c.zero_async();
+            c.dff(z, clk)
+        });
+        assert_folded("NDRO", |c, a, clk| {
+            let z = c.zero_async();
+            c.ndro(z, a, clk)
+        });
+        assert_folded("DFFR", |c, a, clk| {
+            let z = c.zero_async();
+            c.dffr(a, z, clk)
+        });
+        assert_folded("DFFR", |c, a, clk| {
+            let (k1, k2) = c.split(clk);
+            let one = c.one_sync(k1);
+            c.dffr(a, one, k2)
+        });
+        assert_folded("DFFR_SYNC", |c, a, clk| {
+            let z = c.zero_async();
+            c.dffr_sync(a, z, clk)
+        });
+        assert_folded("DFFC", |c, a, clk| {
+            c.terminate(a);
+            let z = c.zero_async();
+            let (q1, q2) = c.dffc(z, clk);
+            c.terminate(q1);
+            q2
+        });
+        assert_folded("NDROC", |c, a, clk| {
+            let z = c.zero_async();
+            let (q1, q2) = c.ndroc(z, a, clk);
+            c.terminate(q2);
+            q1
+        });
+    }
+
+    #[test]
+    fn asynchronous_cells() {
+        assert_folded("MERGE", |c, a, clk| {
+            c.terminate(clk);
+            let z = c.zero_async();
+            c.merge(z, a)
+        });
+        assert_folded("MERGE3", |c, a, clk| {
+            c.terminate(clk);
+            let z1 = c.zero_async();
+            let z2 = c.zero_async();
+            c.merge3(a, z1, z2)
+        });
+        assert_folded("MERGE4", |c, a, clk| {
+            c.terminate(clk);
+            let z1 = c.zero_async();
+            let z2 = c.zero_async();
+            let z3 = c.zero_async();
+            c.merge4(z1, a, z2, z3)
+        });
+        assert_folded("SPLIT", |c, a, clk| {
+            let (x, y) = c.split(a);
+            c.terminate(y);
+            c.dff(x, clk)
+        });
+        assert_folded("TFF", |c, a, clk| {
+            c.terminate(a);
+            c.terminate(clk);
+            let z = c.zero_async();
+            c.tff(z)
+        });
+        assert_folded("TFF2", |c, a, clk| {
+            c.terminate(clk);
+            let (q1, q2) = c.tff2(a);
+            c.terminate(q1);
+            q2
+        });
+        assert_folded("TFFR", |c, a, clk| {
+            c.terminate(clk);
+            let z = c.zero_async();
+            c.tffr(a, z)
+        });
+    }
+
+    // 到着時刻で動くゲートはサイクルごとのシミュレーションで確かめられないので, 置き換えだけを見る
+    #[test]
+    fn race_logic() {
+        let la = circuit(|c, a, clk| {
+            c.terminate(clk);
+            let z = c.zero_async();
+            c.last_arrival(a, z)
+        });
+        assert_eq!(
+            kinds(&la.propagate_constants().unwrap()),
+            ["TERMINATE", "ZERO_ASYNC", "TERMINATE"]
+        );
+        let inh = circuit(|c, a, clk| {
+            c.terminate(clk);
+            let z = c.zero_async();
+            c.inhibit(a, z)
+        });
+        assert_eq!(
+            kinds(&inh.propagate_constants().unwrap()),
+            ["TERMINATE", "JTL"]
+        );
+    }
+
+    // 配線のつながりが正しくない回路はパニックせずにエラーになる
+    #[test]
+    fn invalid_netlist() {
+        let mut c = circuit(|c, a, clk| c.dff(a, clk));
+        c.netlist.gates.pop();
+        assert!(c.netlist.propagate_constants().is_err());
+    }
+}
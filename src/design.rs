@@ -0,0 +1,79 @@
+use colored::Colorize;
+use std::sync::Arc;
+
+use crate::backends::Backend;
+use crate::circuit::Circuit;
+use crate::gate::Gate;
+use crate::netlist::Netlist;
+
+// 回路の階層全体を持ち, 下位の回路から順に 1 つのファイルとして出力する
+// サブサーキットとして使われている回路は自動的に登録される
+#[derive(Debug, Clone, Default)]
+pub struct Design {
+    circuits: Vec<Arc<Netlist>>, // 下位の回路が先
+}
+
+impl Design {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    pub fn add<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>(
+        &mut self,
+        circuit: &Circuit<N_I, N_CI, N_O, N_CO>,
+    ) -> &mut Self {
+        return self.add_netlist(&circuit.netlist);
+    }
+
+    pub fn add_netlist(&mut self, netlist: &Netlist) -> &mut Self {
+        self.register(Arc::new(netlist.clone()));
+        return self;
+    }
+
+    // 子を先に登録する. 同名の回路は中身が同じ場合のみ 1 つにまとめる
+    fn register(&mut self, netlist: Arc<Netlist>) {
+        for gate in netlist.gates.iter() {
+            if let Gate::Subcircuit { circuit, .. } = gate {
+                self.register(circuit.clone());
+            }
+        }
+        if let Some(existing) = self.circuits.iter().find(|c| c.name == netlist.name) {
+            assert!(
+                existing.same_as(&netlist),
+                "{}",
+                format!(
+                    "Different circuits are registered with the same name `{}`!",
+                    netlist.name
+                )
+                .red()
+            );
+            return;
+        }
+        self.circuits.push(netlist);
+    }
+
+    // 登録された回路名 (下位の回路が先)
+    pub fn names(&self) -> Vec<&str> {
+        return self.circuits.iter().map(|c| c.name.as_str()).collect();
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Netlist> {
+        return self
+            .circuits
+            .iter()
+            .find(|c| c.name == name)
+            .map(|c| c.as_ref());
+    }
+
+    pub fn generate<B: Backend>(&self) -> String {
+        let netlists: Vec<&Netlist> = self.circuits.iter().map(|c| c.as_ref()).collect();
+        return B::generate_design(&netlists);
+    }
+}
+
+// netlist とその子孫を下位から順に並べる
+pub(crate) fn hierarchy(netlist: &Netlist) -> Vec<Arc<Netlist>> {
+    let mut design = Design::new();
+    design.add_netlist(netlist);
+    return design.circuits;
+}
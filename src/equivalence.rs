@@ -0,0 +1,429 @@
+use colored::Colorize;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use crate::circuit::Circuit;
+use crate::netlist::Netlist;
+use crate::simulator::Simulator;
+
+// 2 つの回路がサイクルごとに同じ出力を出すかを調べる
+// データ入力が少なければ到達可能な状態をすべて調べ, 多ければランダムな入力列で調べる
+// どちらで調べたかは Verdict で返す
+// 段数の違いは浅い回路の出力を遅らせてそろえ, パイプラインが埋まるまでの出力は比べない
+#[derive(Debug, Clone)]
+pub struct EquivalenceChecker {
+    max_inputs: usize,
+    max_states: usize,
+    cycles: usize,
+    trials: usize,
+    seed: u64,
+    latency: Option<[usize; 2]>,
+}
+
+impl Default for EquivalenceChecker {
+    fn default() -> Self {
+        Self {
+            max_inputs: 12,
+            max_states: 100_000,
+            cycles: 32,
+            trials: 1000,
+            seed: 1,
+            latency: None,
+        }
+    }
+}
+
+impl EquivalenceChecker {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    // 全探索するデータ入力数の上限
+    pub fn max_inputs(mut self, n: usize) -> Self {
+        self.max_inputs = n;
+        return self;
+    }
+
+    // 全探索で調べる状態数の上限. 超えたらランダムシミュレーションに切り替える
+    pub fn max_states(mut self, n: usize) -> Self {
+        self.max_states = n;
+        return self;
+    }
+
+    // ランダムシミュレーションの 1 回あたりのサイクル数と回数
+    pub fn random(mut self, cycles: usize, trials: usize) -> Self {
+        self.cycles = cycles;
+        self.trials = trials;
+        return self;
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        return self;
+    }
+
+    // 2 つの回路の段数. 指定しなければ入力から出力までのクロック付きゲートの段数を数える
+    pub fn latency(mut self, a: usize, b: usize) -> Self {
+        self.latency = Some([a, b]);
+        return self;
+    }
+
+    pub fn check<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>(
+        &self,
+        a: &Circuit<N_I, N_CI, N_O, N_CO>,
+        b: &Circuit<N_I, N_CI, N_O, N_CO>,
+    ) -> Result<Verdict, CheckError> {
+        return self.check_netlist(&a.netlist, &b.netlist);
+    }
+
+    // ポートは位置で対応付ける
+    pub fn check_netlist(&self, a: &Netlist, b: &Netlist) -> Result<Verdict, CheckError> {
+        assert!(
+            a.input_ports().count() == b.input_ports().count()
+                && a.output_ports().count() == b.output_ports().count(),
+            "{}",
+            format!("Ports of `{}` and `{}` do not match!", a.name, b.name).red()
+        );
+        let mut sims = [
+            Simulator::new(&a.flatten()).map_err(CheckError::Unsupported)?,
+            Simulator::new(&b.flatten()).map_err(CheckError::Unsupported)?,
+        ];
+        let latency = self.latency.unwrap_or_else(|| [a.latency(), b.latency()]);
+        let aligner = Aligner::new(latency);
+
+        // どちらかでクロックとして使われている入力は毎サイクルパルスを入れる
+        let clocks: Vec<bool> = sims[0]
+            .clock_ports()
+            .into_iter()
+            .zip(sims[1].clock_ports())
+            .map(|(c1, c2)| c1 || c2)
+            .collect();
+        let data: Vec<usize> = (0..clocks.len()).filter(|i| !clocks[*i]).collect();
+        let stimulus = |bits: &[bool]| -> Vec<bool> {
+            let mut res = clocks.clone();
+            for (i, bit) in data.iter().zip(bits) {
+                res[*i] = *bit;
+            }
+            return res;
+        };
+        let data_ports: Vec<&String> = data
+            .iter()
+            .map(|i| a.input_ports().nth(*i).unwrap())
+            .collect();
+        let mismatch = |sequence: Vec<Vec<bool>>, outputs: [Vec<bool>; 2]| -> CheckError {
+            return CheckError::Mismatch(Counterexample {
+                inputs: sequence
+                    .iter()
+                    .map(|bits| pulsed(data_ports.iter().copied(), bits))
+                    .collect(),
+                outputs: outputs.map(|bits| pulsed(a.output_ports(), &bits)),
+            });
+        };
+
+        if data.len() <= self.max_inputs {
+            match exhaustive(&mut sims, &aligner, data.len(), self.max_states, &stimulus) {
+                Some(Ok(())) => return Ok(Verdict::Proven),
+                Some(Err((sequence, outputs))) => return Err(mismatch(sequence, outputs)),
+                None => {}
+            }
+        }
+
+        let mut rng = XorShift(self.seed.max(1));
+        for _ in 0..self.trials {
+            sims.iter_mut().for_each(|s| s.reset());
+            let mut aligner = aligner.clone();
+            let mut sequence = Vec::new();
+            for _ in 0..self.cycles {
+                let bits: Vec<bool> = data.iter().map(|_| rng.next() & 1 == 1).collect();
+                let input = stimulus(&bits);
+                sequence.push(bits);
+                let outputs = aligner.align([sims[0].step(&input), sims[1].step(&input)]);
+                if let Some(outputs) = outputs
+                    && outputs[0] != outputs[1]
+                {
+                    return Err(mismatch(sequence, outputs));
+                }
+            }
+        }
+        return Ok(Verdict::Sampled {
+            cycles: self.cycles,
+            trials: self.trials,
+        });
+    }
+}
+
+// 段数の違う 2 つの回路の出力をそろえる
+// 浅い回路の出力を段数の差だけ遅らせ, 深い回路の段数のサイクルが過ぎるまでは比べない
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Aligner {
+    shallow: usize,               // 浅い回路の番号
+    offset: usize,                // 段数の差
+    fill: usize,                  // 深い回路の段数
+    history: VecDeque<Vec<bool>>, // 浅い回路の直近 offset サイクルの出力
+    cycle: usize,                 // 経過サイクル数 (fill で止める)
+}
+
+impl Aligner {
+    fn new([a, b]: [usize; 2]) -> Self {
+        return Self {
+            shallow: if a <= b { 0 } else { 1 },
+            offset: a.abs_diff(b),
+            fill: a.max(b),
+            history: VecDeque::new(),
+            cycle: 0,
+        };
+    }
+
+    // 1 サイクルの出力を受け取り, 比べる出力の組を返す. パイプラインが埋まるまでは None
+    fn align(&mut self, mut outputs: [Vec<bool>; 2]) -> Option<[Vec<bool>; 2]> {
+        let s = self.shallow;
+        self.history.push_back(std::mem::take(&mut outputs[s]));
+        if self.history.len() > self.offset {
+            outputs[s] = self.history.pop_front().unwrap();
+        }
+        if self.cycle < self.fill {
+            self.cycle += 1;
+            return None;
+        }
+        return Some(outputs);
+    }
+}
+
+// パルスの入ったポート名
+fn pulsed<'a>(ports: impl Iterator<Item = &'a String>, bits: &[bool]) -> Vec<String> {
+    return ports
+        .zip(bits)
+        .filter(|(_, bit)| **bit)
+        .map(|(p, _)| p.clone())
+        .collect();
+}
+
+type Trace = (Vec<Vec<bool>>, [Vec<bool>; 2]);
+
+// 2 つの回路の状態の組を幅優先で探索し, 最短の反例を返す
+// 状態数が max_states を超えたら None
+fn exhaustive(
+    sims: &mut [Simulator; 2],
+    aligner: &Aligner,
+    n_data: usize,
+    max_states: usize,
+    stimulus: &impl Fn(&[bool]) -> Vec<bool>,
+) -> Option<Result<(), Trace>> {
+    type State = ([Vec<[bool; 2]>; 2], Aligner);
+    // (状態, 親の番号, 親からの入力)
+    let mut nodes: Vec<(State, usize, Vec<bool>)> = Vec::new();
+    let mut seen: HashMap<State, usize> = HashMap::new();
+    sims.iter_mut().for_each(|s| s.reset());
+    let init: State = (
+        [sims[0].state.clone(), sims[1].state.clone()],
+        aligner.clone(),
+    );
+    seen.insert(init.clone(), 0);
+    nodes.push((init, 0, Vec::new()));
+
+    let mut next = 0;
+    while next < nodes.len() {
+        for pattern in 0..(1u64 << n_data) {
+            let bits: Vec<bool> = (0..n_data).map(|i| pattern >> i & 1 == 1).collect();
+            let input = stimulus(&bits);
+            for (sim, state) in sims.iter_mut().zip(nodes[next].0.0.iter()) {
+                sim.state = state.clone();
+            }
+            let mut aligner = nodes[next].0.1.clone();
+            let outputs = aligner.align([sims[0].step(&input), sims[1].step(&input)]);
+            if let Some(outputs) = outputs
+                && outputs[0] != outputs[1]
+            {
+                // 親をたどって入力列を復元する
+                let mut sequence = vec![bits];
+                let mut node = next;
+                while node != 0 {
+                    sequence.push(nodes[node].2.clone());
+                    node = nodes[node].1;
+                }
+                sequence.reverse();
+                return Some(Err((sequence, outputs)));
+            }
+            let state: State = ([sims[0].state.clone(), sims[1].state.clone()], aligner);
+            if !seen.contains_key(&state) {
+                if nodes.len() >= max_states {
+                    return None;
+                }
+                seen.insert(state.clone(), nodes.len());
+                nodes.push((state, next, bits));
+            }
+        }
+        next += 1;
+    }
+    return Some(Ok(()));
+}
+
+// 依存を増やさないための簡単な乱数
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        return self.0;
+    }
+}
+
+// 食い違いが見つからなかったときの調べ方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Proven,                                   // 到達可能な状態をすべて調べて等価と証明した
+    Sampled { cycles: usize, trials: usize }, // ランダムな入力列で食い違いが見つからなかった
+}
+
+impl Verdict {
+    pub fn is_proven(&self) -> bool {
+        return *self == Verdict::Proven;
+    }
+}
+
+// 等価性を確かめられなかった理由
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckError {
+    Mismatch(Counterexample), // 出力が食い違う
+    Unsupported(String),      // サイクル単位でシミュレーションできないゲートがある
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            CheckError::Mismatch(counterexample) => write!(f, "{}", counterexample),
+            CheckError::Unsupported(message) => write!(f, "{}", message),
+        };
+    }
+}
+
+// 出力が食い違う入力列
+// inputs はサイクルごとにパルスを入れたデータ入力ポート (クロックは毎サイクル入れる)
+// outputs は最後のサイクルに各回路がパルスを出した出力ポート (浅い回路は段数の差だけ前のサイクル)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counterexample {
+    inputs: Vec<Vec<String>>,
+    outputs: [Vec<String>; 2],
+}
+
+impl Counterexample {
+    pub fn inputs(&self) -> &[Vec<String>] {
+        return &self.inputs;
+    }
+
+    pub fn outputs(&self) -> &[Vec<String>; 2] {
+        return &self.outputs;
+    }
+}
+
+impl fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (cycle, ports) in self.inputs.iter().enumerate() {
+            writeln!(f, "cycle {}: {{{}}}", cycle, ports.join(", "))?;
+        }
+        return write!(
+            f,
+            "outputs differ: {{{}}} vs {{{}}}",
+            self.outputs[0].join(", "),
+            self.outputs[1].join(", ")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CheckError, EquivalenceChecker, Verdict};
+    use crate::circuit::Circuit;
+    use crate::components::{Arithmetic, Clocking, Sequential};
+    use crate::expr::Expr;
+
+    // a, b, clk -> y
+    fn gate(expr: &str) -> Circuit<3, 0, 1, 0> {
+        let (mut c, [a, b, clk], [], [y], []) =
+            Circuit::create(["a", "b", "clk"], [], ["y"], [], "gate");
+        let q = c.expr(&Expr::parse(expr).unwrap(), ["a", "b"], [a, b], clk);
+        c.unify(q, y);
+        return c;
+    }
+
+    #[test]
+    fn adders_of_different_latency() {
+        let rca: Circuit<9, 0, 5, 0> = Arithmetic::ripple_carry_adder();
+        let ksa: Circuit<9, 0, 5, 0> = Arithmetic::kogge_stone_adder();
+        assert_ne!(rca.netlist.latency(), ksa.netlist.latency());
+        // パイプラインの状態が多いのでランダムシミュレーションで調べる
+        let checker = EquivalenceChecker::new().max_inputs(0).random(16, 200);
+        let sampled = Verdict::Sampled {
+            cycles: 16,
+            trials: 200,
+        };
+        assert_eq!(checker.check(&rca, &ksa), Ok(sampled));
+        let res = checker.latency(0, 0).check(&rca, &ksa);
+        assert!(matches!(res, Err(CheckError::Mismatch(_))));
+    }
+
+    #[test]
+    fn shift_registers_of_different_depth() {
+        let a = Sequential::shift_register(1, Clocking::Concurrent);
+        let b = Sequential::shift_register(4, Clocking::CounterFlow);
+        assert_eq!(EquivalenceChecker::new().check(&a, &b), Ok(Verdict::Proven));
+        // 段数を 0 として比べると食い違う
+        let res = EquivalenceChecker::new().latency(0, 0).check(&a, &b);
+        assert!(matches!(res, Err(CheckError::Mismatch(_))));
+    }
+
+    #[test]
+    fn shortest_counterexample() {
+        let Err(CheckError::Mismatch(e)) =
+            EquivalenceChecker::new().check(&gate("a ^ b"), &gate("a | b"))
+        else {
+            panic!("`a ^ b` and `a | b` must differ!");
+        };
+        assert_eq!(e.inputs(), [vec!["a".to_string(), "b".to_string()], vec![]]);
+        assert_eq!(e.outputs(), &[vec![], vec!["y".to_string()]]);
+    }
+
+    #[test]
+    fn random_simulation_finds_mismatch() {
+        let checker = EquivalenceChecker::new().max_inputs(0);
+        let res = checker.check(&gate("!(a & b)"), &gate("!a | !b"));
+        assert_eq!(
+            res,
+            Ok(Verdict::Sampled {
+                cycles: 32,
+                trials: 1000
+            })
+        );
+        assert!(checker.check(&gate("a & b"), &gate("a & !b")).is_err());
+    }
+
+    #[test]
+    fn race_logic_is_unsupported() {
+        let (mut c, [a, b, clk], [], [y], []) =
+            Circuit::create(["a", "b", "clk"], [], ["y"], [], "fa");
+        let q = c.first_arrival(a, b);
+        c.unify(q, y);
+        c.terminate(clk);
+        let res = EquivalenceChecker::new().check(&c, &gate("a | b"));
+        assert!(matches!(res, Err(CheckError::Unsupported(_))));
+    }
+
+    // 状態数が上限を超えたらランダムシミュレーションに切り替え, そのことを返す
+    #[test]
+    fn state_limit_falls_back_to_sampling() {
+        let a = Sequential::shift_register(3, Clocking::Concurrent);
+        let b = Sequential::shift_register(3, Clocking::CounterFlow);
+        let checker = EquivalenceChecker::new();
+        assert!(checker.check(&a, &b).unwrap().is_proven());
+        let res = checker.max_states(2).random(8, 10).check(&a, &b);
+        assert_eq!(
+            res,
+            Ok(Verdict::Sampled {
+                cycles: 8,
+                trials: 10
+            })
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use std::fmt;
+
+// ネットリストの読み込みに失敗したときのエラー
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    line: Option<usize>,
+    message: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(message: impl Into<String>) -> Self {
+        return Self {
+            line: None,
+            message: message.into(),
+        };
+    }
+
+    pub(crate) fn at(line: usize, message: impl Into<String>) -> Self {
+        return Self {
+            line: Some(line),
+            message: message.into(),
+        };
+    }
+
+    // エラーの起きた行 (1 始まり)
+    pub fn line(&self) -> Option<usize> {
+        return self.line;
+    }
+
+    pub fn message(&self) -> &str {
+        return &self.message;
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        };
+    }
+}
+
+impl std::error::Error for ParseError {}
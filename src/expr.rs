@@ -0,0 +1,522 @@
+use colored::Colorize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+
+use crate::circuit::Circuit;
+use crate::error::ParseError;
+use crate::logic::{LogicNetwork, Op};
+use crate::wire::Wire;
+
+// 名前の付いた入力に対する論理式
+// Expr::parse() で文字列から, または演算子 &, |, ^, ! で組み立てる
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Expr {
+    Const(bool),
+    Var(String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Xor(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn var(name: &str) -> Self {
+        return Expr::Var(name.to_string());
+    }
+
+    pub fn constant(value: bool) -> Self {
+        return Expr::Const(value);
+    }
+
+    // 優先順位は Rust と同じく ! > & > ^ > |
+    // 変数は英字または _ で始まる英数字, 定数は 0 と 1. ~ も否定として使える
+    pub fn parse(src: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser {
+            chars: src.chars().collect(),
+            pos: 0,
+        };
+        let expr = parser.or()?;
+        parser.skip_spaces();
+        if parser.pos < parser.chars.len() {
+            return Err(parser.unexpected());
+        }
+        return Ok(expr);
+    }
+
+    // 現れる変数 (最初に現れた順)
+    pub fn vars(&self) -> Vec<&str> {
+        let mut res: Vec<&str> = Vec::new();
+        self.collect_vars(&mut res);
+        return res;
+    }
+
+    fn collect_vars<'a>(&'a self, res: &mut Vec<&'a str>) {
+        match self {
+            Expr::Const(_) => {}
+            Expr::Var(name) => {
+                if !res.contains(&name.as_str()) {
+                    res.push(name);
+                }
+            }
+            Expr::Not(a) => a.collect_vars(res),
+            Expr::And(a, b) | Expr::Or(a, b) | Expr::Xor(a, b) => {
+                a.collect_vars(res);
+                b.collect_vars(res);
+            }
+        }
+    }
+
+    // 変数の値を与えて評価する
+    pub fn eval(&self, value: &impl Fn(&str) -> bool) -> bool {
+        return match self {
+            Expr::Const(c) => *c,
+            Expr::Var(name) => value(name),
+            Expr::Not(a) => !a.eval(value),
+            Expr::And(a, b) => a.eval(value) && b.eval(value),
+            Expr::Or(a, b) => a.eval(value) || b.eval(value),
+            Expr::Xor(a, b) => a.eval(value) ^ b.eval(value),
+        };
+    }
+
+    // 演算子の結合の強さ
+    fn precedence(&self) -> u8 {
+        return match self {
+            Expr::Or(..) => 1,
+            Expr::Xor(..) => 2,
+            Expr::And(..) => 3,
+            _ => 4,
+        };
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // 結合の弱い部分式だけを括弧で囲む
+        let operand = |e: &Expr, min: u8| -> String {
+            if e.precedence() < min {
+                return format!("({})", e);
+            }
+            return e.to_string();
+        };
+        return match self {
+            Expr::Const(c) => write!(f, "{}", *c as u8),
+            Expr::Var(name) => write!(f, "{}", name),
+            Expr::Not(a) => write!(f, "!{}", operand(a, 4)),
+            Expr::And(a, b) => write!(f, "{} & {}", operand(a, 3), operand(b, 3)),
+            Expr::Xor(a, b) => write!(f, "{} ^ {}", operand(a, 2), operand(b, 2)),
+            Expr::Or(a, b) => write!(f, "{} | {}", operand(a, 1), operand(b, 1)),
+        };
+    }
+}
+
+impl Not for Expr {
+    type Output = Expr;
+    fn not(self) -> Expr {
+        return Expr::Not(Box::new(self));
+    }
+}
+
+impl BitAnd for Expr {
+    type Output = Expr;
+    fn bitand(self, rhs: Expr) -> Expr {
+        return Expr::And(Box::new(self), Box::new(rhs));
+    }
+}
+
+impl BitOr for Expr {
+    type Output = Expr;
+    fn bitor(self, rhs: Expr) -> Expr {
+        return Expr::Or(Box::new(self), Box::new(rhs));
+    }
+}
+
+impl BitXor for Expr {
+    type Output = Expr;
+    fn bitxor(self, rhs: Expr) -> Expr {
+        return Expr::Xor(Box::new(self), Box::new(rhs));
+    }
+}
+
+// 再帰下降パーサ
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn skip_spaces(&mut self) {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    // 空白を読み飛ばして c があれば読む
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_spaces();
+        if self.chars.get(self.pos) == Some(&c) {
+            self.pos += 1;
+            return true;
+        }
+        return false;
+    }
+
+    fn unexpected(&self) -> ParseError {
+        return match self.chars.get(self.pos) {
+            Some(c) => ParseError::new(format!(
+                "Unexpected `{}` at column {}!",
+                c,
+                self.pos + 1
+            )),
+            None => ParseError::new("Unexpected end of expression!"),
+        };
+    }
+
+    fn or(&mut self) -> Result<Expr, ParseError> {
+        let mut res = self.xor()?;
+        while self.eat('|') {
+            res = res | self.xor()?;
+        }
+        return Ok(res);
+    }
+
+    fn xor(&mut self) -> Result<Expr, ParseError> {
+        let mut res = self.and()?;
+        while self.eat('^') {
+            res = res ^ self.and()?;
+        }
+        return Ok(res);
+    }
+
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        let mut res = self.unary()?;
+        while self.eat('&') {
+            res = res & self.unary()?;
+        }
+        return Ok(res);
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        if self.eat('!') || self.eat('~') {
+            return Ok(!self.unary()?);
+        }
+        if self.eat('(') {
+            let res = self.or()?;
+            if !self.eat(')') {
+                return Err(self.unexpected());
+            }
+            return Ok(res);
+        }
+        self.skip_spaces();
+        let start = self.pos;
+        while self.pos < self.chars.len()
+            && (self.chars[self.pos].is_ascii_alphanumeric() || self.chars[self.pos] == '_')
+        {
+            self.pos += 1;
+        }
+        let token: String = self.chars[start..self.pos].iter().collect();
+        return match token.as_str() {
+            "" => Err(self.unexpected()),
+            "0" => Ok(Expr::Const(false)),
+            "1" => Ok(Expr::Const(true)),
+            _ if token.starts_with(|c: char| c.is_ascii_digit()) => {
+                self.pos = start;
+                Err(self.unexpected())
+            }
+            _ => Ok(Expr::Var(token)),
+        };
+    }
+}
+
+// 式を変換した値. 否定は必要になるまで NOT ゲートにしない
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Lit {
+    Const(bool),
+    Net(String, bool), // (配線, 否定するか)
+}
+
+// 論理式を LogicNetwork のセルに変換する
+// 同じ演算は 1 つのセルにまとめ, 定数は畳み込む
+pub(crate) struct Lowering<'a> {
+    network: &'a mut LogicNetwork,
+    vars: HashMap<String, String>, // 変数名 -> 配線
+    cells: HashMap<(Op, Vec<String>), String>,
+    n_temp: usize,
+}
+
+impl<'a> Lowering<'a> {
+    // names の順に network の入力とする
+    pub(crate) fn new(network: &'a mut LogicNetwork, names: &[&str]) -> Self {
+        let vars: HashMap<String, String> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.to_string(), format!("$i{}", i)))
+            .collect();
+        network.inputs = (0..names.len()).map(|i| format!("$i{}", i)).collect();
+        return Self {
+            network,
+            vars,
+            cells: HashMap::new(),
+            n_temp: 0,
+        };
+    }
+
+    pub(crate) fn fresh(&mut self) -> String {
+        self.n_temp += 1;
+        return format!("$t{}", self.n_temp);
+    }
+
+    // 式を network の出力に加える
+    pub(crate) fn output(&mut self, expr: &Expr) {
+        let lit = self.lower(expr);
+        let net = self.net(lit);
+        self.network.outputs.push(net);
+    }
+
+    // 入力の順序によらないように並べてからセルを探す
+    fn cell(&mut self, op: Op, mut inputs: Vec<String>) -> String {
+        inputs.sort();
+        if let Some(net) = self.cells.get(&(op, inputs.clone())) {
+            return net.clone();
+        }
+        let net = self.fresh();
+        let refs: Vec<&str> = inputs.iter().map(|s| s.as_str()).collect();
+        self.network.add(op, &refs, &net);
+        self.cells.insert((op, inputs), net.clone());
+        return net;
+    }
+
+    // 否定を実体化した配線
+    // XOR の否定は XNOR にする
+    fn net(&mut self, lit: Lit) -> String {
+        return match lit {
+            Lit::Const(c) => self.cell(if c { Op::One } else { Op::Zero }, vec![]),
+            Lit::Net(net, false) => net,
+            Lit::Net(net, true) => {
+                let xor = self
+                    .cells
+                    .iter()
+                    .find(|((op, _), out)| *op == Op::Xor && **out == net)
+                    .map(|((_, inputs), _)| inputs.clone());
+                match xor {
+                    Some(inputs) => self.cell(Op::Xnor, inputs),
+                    None => self.cell(Op::Not, vec![net]),
+                }
+            }
+        };
+    }
+
+    fn lower(&mut self, expr: &Expr) -> Lit {
+        return match expr {
+            Expr::Const(c) => Lit::Const(*c),
+            Expr::Var(name) => Lit::Net(self.vars[name].clone(), false),
+            Expr::Not(a) => match self.lower(a) {
+                Lit::Const(c) => Lit::Const(!c),
+                Lit::Net(net, neg) => Lit::Net(net, !neg),
+            },
+            Expr::And(a, b) => {
+                let (a, b) = (self.lower(a), self.lower(b));
+                self.and_or(Op::And, a, b)
+            }
+            Expr::Or(a, b) => {
+                let (a, b) = (self.lower(a), self.lower(b));
+                self.and_or(Op::Or, a, b)
+            }
+            Expr::Xor(a, b) => match (self.lower(a), self.lower(b)) {
+                (Lit::Const(c), Lit::Net(net, neg)) | (Lit::Net(net, neg), Lit::Const(c)) => {
+                    Lit::Net(net, neg ^ c)
+                }
+                (Lit::Const(c1), Lit::Const(c2)) => Lit::Const(c1 ^ c2),
+                (Lit::Net(n1, neg1), Lit::Net(n2, neg2)) => {
+                    if n1 == n2 {
+                        Lit::Const(neg1 ^ neg2)
+                    } else {
+                        // 否定は XOR の外へ出す
+                        Lit::Net(self.cell(Op::Xor, vec![n1, n2]), neg1 ^ neg2)
+                    }
+                }
+            },
+        };
+    }
+
+    // AND と OR の定数畳み込み. OR は AND の双対
+    fn and_or(&mut self, op: Op, a: Lit, b: Lit) -> Lit {
+        // AND では false, OR では true が支配的
+        let dominant = op == Op::Or;
+        return match (a, b) {
+            (Lit::Const(c), other) | (other, Lit::Const(c)) => {
+                if c == dominant {
+                    Lit::Const(dominant)
+                } else {
+                    other
+                }
+            }
+            (Lit::Net(n1, neg1), Lit::Net(n2, neg2)) if n1 == n2 => {
+                if neg1 == neg2 {
+                    Lit::Net(n1, neg1)
+                } else {
+                    Lit::Const(dominant)
+                }
+            }
+            (a, b) => {
+                let inputs = vec![self.net(a), self.net(b)];
+                Lit::Net(self.cell(op, inputs), false)
+            }
+        };
+    }
+}
+
+// 論理式を段数をそろえたクロック付きゲートとして circuit 上に構築する
+pub(crate) fn synthesize<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>(
+    circuit: &mut Circuit<N_I, N_CI, N_O, N_CO>,
+    exprs: &[&Expr],
+    names: &[&str],
+    inputs: Vec<Wire>,
+    clk: Wire,
+) -> Vec<Wire> {
+    let mut declared = HashSet::new();
+    for name in names {
+        assert!(
+            declared.insert(*name),
+            "{}",
+            format!("Variable `{}` is declared twice!", name).red()
+        );
+    }
+    for var in exprs.iter().flat_map(|e| e.vars()) {
+        assert!(
+            declared.contains(var),
+            "{}",
+            format!("Variable `{}` is not given!", var).red()
+        );
+    }
+
+    let mut network = LogicNetwork::new();
+    let mut lowering = Lowering::new(&mut network, names);
+    for expr in exprs {
+        lowering.output(expr);
+    }
+    let mut n_temp = lowering.n_temp;
+    // XNOR にまとめた XOR などは使われない
+    network.sweep();
+    network.balance(&mut || {
+        n_temp += 1;
+        format!("$t{}", n_temp)
+    });
+    network.check().unwrap();
+    let mut res = Vec::new();
+    for mut wire in network.build(circuit, inputs, Some(clk), false) {
+        // 変数がそのまま出力になると入力ポートの配線が返るため, JTL を挟んで出力ポートにつなげられるようにする
+        if circuit.is_named(&wire) {
+            wire = circuit.jtl(wire);
+        }
+        res.push(wire);
+    }
+    return res;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Expr;
+    use crate::circuit::Circuit;
+    use crate::equivalence::EquivalenceChecker;
+    use crate::wire::Wire;
+
+    type Gate = Circuit<3, 0, 1, 0>;
+
+    // a, b, clk -> y
+    fn synthesized(expr: &str) -> Gate {
+        let (mut c, [a, b, clk], [], [y], []) =
+            Circuit::create(["a", "b", "clk"], [], ["y"], [], "expr");
+        let q = c.expr(&Expr::parse(expr).unwrap(), ["a", "b"], [a, b], clk);
+        c.unify(q, y);
+        return c;
+    }
+
+    fn reference(build: impl FnOnce(&mut Gate, Wire, Wire, Wire) -> Wire) -> Gate {
+        let (mut c, [a, b, clk], [], [y], []) =
+            Circuit::create(["a", "b", "clk"], [], ["y"], [], "reference");
+        let q = build(&mut c, a, b, clk);
+        c.unify(q, y);
+        return c;
+    }
+
+    fn assert_equivalent(expr: &str, reference: &Gate) {
+        let res = EquivalenceChecker::new().check(&synthesized(expr), reference);
+        assert!(res.is_ok(), "`{}`: {}", expr, res.unwrap_err());
+        assert!(res.unwrap().is_proven());
+    }
+
+    // 変数 1 つに畳み込まれる式
+    #[test]
+    fn single_variable() {
+        let a = reference(|c, a, b, clk| {
+            c.terminate(b);
+            c.dff(a, clk)
+        });
+        for expr in ["a", "a & a", "!!a", "a | 0", "a & 1"] {
+            assert_equivalent(expr, &a);
+        }
+        let b = reference(|c, a, b, clk| {
+            c.terminate(a);
+            c.dff(b, clk)
+        });
+        assert_equivalent("a ^ a ^ b", &b);
+    }
+
+    #[test]
+    fn constants() {
+        let one = reference(|c, a, b, clk| {
+            c.terminate(a);
+            c.terminate(b);
+            c.one_sync(clk)
+        });
+        for expr in ["1", "a | 1", "a | !a", "!0"] {
+            assert_equivalent(expr, &one);
+        }
+        let zero = reference(|c, a, b, clk| {
+            c.terminate(a);
+            c.terminate(b);
+            c.terminate(clk);
+            c.zero_async()
+        });
+        for expr in ["0", "a & 0", "a & !a", "b ^ b"] {
+            assert_equivalent(expr, &zero);
+        }
+    }
+
+    #[test]
+    fn gates() {
+        let nand = reference(|c, a, b, clk| {
+            let (k1, k2) = c.split(clk);
+            let q = c.and(a, b, k1);
+            c.not(q, k2)
+        });
+        assert_equivalent("!(a & b)", &nand);
+        assert_equivalent("!a | !b", &nand);
+        let xnor = reference(|c, a, b, clk| c.xnor(a, b, clk));
+        assert_equivalent("!(a ^ b)", &xnor);
+        assert_equivalent("a ^ !b", &xnor);
+    }
+
+    // 同じ変数を複数の出力に使っても出力ポートにつなげる
+    #[test]
+    fn shared_outputs() {
+        let (mut c, [a, clk], [], [y, z], []) =
+            Circuit::create(["a", "clk"], [], ["y", "z"], [], "shared");
+        let e = Expr::parse("a").unwrap();
+        let [q1, q2] = c.exprs([&e, &e], ["a"], [a], clk);
+        c.unify(q1, y);
+        c.unify(q2, z);
+    }
+
+    #[test]
+    fn parse_errors() {
+        for src in ["", "a &", "(a", "a b", "1a", "a $ b"] {
+            assert!(Expr::parse(src).is_err(), "`{}`", src);
+        }
+        assert_eq!(
+            Expr::parse("!a & b | c ^ d").unwrap(),
+            (!Expr::var("a") & Expr::var("b")) | (Expr::var("c") ^ Expr::var("d"))
+        );
+    }
+}
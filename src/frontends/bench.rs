@@ -0,0 +1,149 @@
+use std::collections::HashSet;
+
+use super::build_circuit;
+use crate::circuit::Circuit;
+use crate::error::ParseError;
+use crate::logic::{LogicNetwork, Op};
+
+// ISCAS85/89 の .bench 形式を読み込み, SFQ ゲートにマッピングする
+// 多入力ゲートは 2 入力ゲートの木に, NAND/NOR/XNOR は否定を付けて分解する
+pub struct Bench;
+
+impl Bench {
+    // .bench には回路名がないので name で指定する
+    // DFF は暗黙のクロックで動くので, clock という名前の入力ポートを最後に追加する
+    pub fn parse<const N_I: usize, const N_O: usize>(
+        src: &str,
+        name: &str,
+        clock: &str,
+    ) -> Result<Circuit<N_I, 0, N_O, 0>, ParseError> {
+        let mut inputs: Vec<String> = Vec::new();
+        let mut outputs: Vec<String> = Vec::new();
+        let mut network = LogicNetwork::new();
+        // (行番号, 出力, 関数名, 引数)
+        let mut gates: Vec<(usize, String, String, Vec<String>)> = Vec::new();
+
+        for (i, raw) in src.lines().enumerate() {
+            let line = i + 1;
+            let text = raw.split('#').next().unwrap().trim();
+            if text.is_empty() {
+                continue;
+            }
+            let (lhs, rhs) = match text.split_once('=') {
+                Some((lhs, rhs)) => (Some(lhs.trim()), rhs.trim()),
+                None => (None, text),
+            };
+            let (func, args) = rhs
+                .strip_suffix(')')
+                .and_then(|s| s.split_once('('))
+                .ok_or_else(|| ParseError::at(line, format!("Invalid line `{}`!", text)))?;
+            let func = func.trim().to_uppercase();
+            let args: Vec<String> = args
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect();
+
+            match (lhs, func.as_str(), args.as_slice()) {
+                (None, "INPUT", [net]) => inputs.push(net.clone()),
+                (None, "OUTPUT", [net]) => outputs.push(net.clone()),
+                (Some(out), _, _) if !out.is_empty() && !args.is_empty() => {
+                    gates.push((line, out.to_string(), func, args));
+                }
+                _ => return Err(ParseError::at(line, format!("Invalid line `{}`!", text))),
+            }
+        }
+        if inputs.iter().any(|net| net == clock) {
+            return Err(ParseError::new(format!(
+                "Clock `{}` conflicts with an input!",
+                clock
+            )));
+        }
+
+        // 中間の配線名はファイル中の名前と重ならないようにする
+        let used: HashSet<String> = inputs
+            .iter()
+            .chain(gates.iter().map(|(_, out, _, _)| out))
+            .cloned()
+            .collect();
+        let mut n_temp = 0;
+        let mut fresh = || loop {
+            n_temp += 1;
+            let name = format!("$t{}", n_temp);
+            if !used.contains(&name) {
+                return name;
+            }
+        };
+
+        for (line, out, func, args) in gates {
+            let unary = |op: Op| -> Result<Op, ParseError> {
+                if args.len() != 1 {
+                    return Err(ParseError::at(
+                        line,
+                        format!("{} takes 1 input, but {} are given!", func, args.len()),
+                    ));
+                }
+                return Ok(op);
+            };
+            match func.as_str() {
+                "BUF" | "BUFF" => network.add(unary(Op::Buf)?, &[&args[0]], &out),
+                "NOT" => network.add(unary(Op::Not)?, &[&args[0]], &out),
+                "DFF" => network.add(unary(Op::Dff)?, &[&args[0]], &out),
+                "AND" => network.add_tree(Op::And, &args, &out, &mut fresh),
+                "OR" => network.add_tree(Op::Or, &args, &out, &mut fresh),
+                "XOR" => network.add_tree(Op::Xor, &args, &out, &mut fresh),
+                // 2 入力の XNOR はそのままゲートにする
+                "XNOR" if args.len() == 2 => network.add(Op::Xnor, &[&args[0], &args[1]], &out),
+                "NAND" | "NOR" | "XNOR" => {
+                    let op = match func.as_str() {
+                        "NAND" => Op::And,
+                        "NOR" => Op::Or,
+                        _ => Op::Xor,
+                    };
+                    let t = fresh();
+                    network.add_tree(op, &args, &t, &mut fresh);
+                    network.add(Op::Not, &[&t], &out);
+                }
+                _ => {
+                    return Err(ParseError::at(
+                        line,
+                        format!("Gate `{}` is not supported!", func),
+                    ));
+                }
+            }
+        }
+        network.inputs = inputs.clone();
+        network.outputs = outputs.clone();
+        inputs.push(clock.to_string());
+
+        return build_circuit(name, &network, &inputs, Some(clock), &outputs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Bench;
+    use crate::circuit::Circuit;
+    use crate::frontends::tests::assert_expr;
+
+    fn parse(body: &str) -> Circuit<4, 0, 1, 0> {
+        let src = format!("INPUT(a)\nINPUT(b)\nINPUT(c)\nOUTPUT(y)\n{}\n", body);
+        return Bench::parse(&src, "top", "clk").unwrap();
+    }
+
+    // 3 入力の木では a だけ 1 段浅い
+    #[test]
+    fn three_input_and() {
+        assert_expr(&parse("y = AND(a, b, c)"), "a & b & c");
+    }
+
+    #[test]
+    fn nor() {
+        assert_expr(&parse("y = NOR(a, b, c)"), "!(a | b | c)");
+    }
+
+    #[test]
+    fn not_on_one_input() {
+        assert_expr(&parse("t = NOT(a)\ny = AND(t, b)"), "!a & b");
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::{HashMap, HashSet};
+
+use super::build_circuit;
+use crate::circuit::Circuit;
+use crate::error::ParseError;
+use crate::logic::{LogicNetwork, Op};
+
+// BLIF (Berkeley Logic Interchange Format) を読み込み, SFQ ゲートにマッピングする
+// .names は積和形に分解し, .latch は DFF にする
+pub struct Blif;
+
+impl Blif {
+    // 最初の .model を読み込む
+    // clock という名前の入力をクロックとし, なければ最後の入力ポートとして追加する
+    pub fn parse<const N_I: usize, const N_O: usize>(
+        src: &str,
+        clock: &str,
+    ) -> Result<Circuit<N_I, 0, N_O, 0>, ParseError> {
+        let lines = logical_lines(src);
+        let used: HashSet<&str> = lines
+            .iter()
+            .flat_map(|(_, tokens)| tokens.iter().map(|t| t.as_str()))
+            .collect();
+        // 中間の配線名はファイル中の名前と重ならないようにする
+        let mut n_temp = 0;
+        let mut fresh = || loop {
+            n_temp += 1;
+            let name = format!("$t{}", n_temp);
+            if !used.contains(name.as_str()) {
+                return name;
+            }
+        };
+
+        let mut name: Option<String> = None;
+        let mut inputs: Vec<String> = Vec::new();
+        let mut outputs: Vec<String> = Vec::new();
+        let mut network = LogicNetwork::new();
+        let mut negated: HashMap<String, String> = HashMap::new();
+
+        let mut i = 0;
+        while i < lines.len() {
+            let (line, tokens) = &lines[i];
+            let line = *line;
+            i += 1;
+            let args = &tokens[1..];
+            match tokens[0].as_str() {
+                ".model" => {
+                    if name.is_some() {
+                        // 2 つ目以降のモデルは読まない
+                        break;
+                    }
+                    name = Some(
+                        args.first()
+                            .ok_or_else(|| ParseError::at(line, "Model name is missing!"))?
+                            .clone(),
+                    );
+                }
+                ".inputs" => inputs.extend(args.iter().cloned()),
+                ".outputs" => outputs.extend(args.iter().cloned()),
+                ".names" => {
+                    let Some((output, ins)) = args.split_last() else {
+                        return Err(ParseError::at(line, "`.names` needs an output!"));
+                    };
+                    // 続く行がカバー
+                    let mut rows: Vec<(&str, &str)> = Vec::new();
+                    while i < lines.len() && !lines[i].1[0].starts_with('.') {
+                        let (row_line, row) = &lines[i];
+                        let cover = match (ins.len(), row.as_slice()) {
+                            (0, [out]) => ("", out.as_str()),
+                            (_, [plane, out]) if !ins.is_empty() => (plane.as_str(), out.as_str()),
+                            _ => return Err(ParseError::at(*row_line, "Invalid cover line!")),
+                        };
+                        check_cover(cover, ins.len()).map_err(|e| ParseError::at(*row_line, e))?;
+                        rows.push(cover);
+                        i += 1;
+                    }
+                    if rows.iter().any(|(_, out)| *out != rows[0].1) {
+                        return Err(ParseError::at(
+                            line,
+                            format!("Cover of `{}` mixes on-set and off-set!", output),
+                        ));
+                    }
+                    add_names(&mut network, ins, output, &rows, &mut negated, &mut fresh);
+                }
+                ".latch" => {
+                    let (input, output, control, init) = match args {
+                        [a, q] => (a, q, None, None),
+                        [a, q, init] => (a, q, None, Some(init)),
+                        [a, q, kind, ctrl] => (a, q, Some((kind, ctrl)), None),
+                        [a, q, kind, ctrl, init] => (a, q, Some((kind, ctrl)), Some(init)),
+                        _ => return Err(ParseError::at(line, "Invalid `.latch`!")),
+                    };
+                    if let Some((kind, ctrl)) = control
+                        && (kind != "re" || (ctrl != clock && ctrl != "NIL"))
+                    {
+                        return Err(ParseError::at(
+                            line,
+                            format!(
+                                "Latch `{}` must be `re` triggered by `{}`, but is `{} {}`!",
+                                output, clock, kind, ctrl
+                            ),
+                        ));
+                    }
+                    // SFQ の DFF の初期値は 0
+                    if init.is_some_and(|v| v == "1") {
+                        return Err(ParseError::at(
+                            line,
+                            format!("Latch `{}` cannot be initialized to 1!", output),
+                        ));
+                    }
+                    network.add(Op::Dff, &[input], output);
+                }
+                ".end" => break,
+                ".exdc" => break,
+                ".clock" => {}
+                directive => {
+                    return Err(ParseError::at(
+                        line,
+                        format!("`{}` is not supported!", directive),
+                    ));
+                }
+            }
+        }
+
+        let name = name.unwrap_or_else(|| "top".to_string());
+        let has_clock = inputs.iter().any(|net| net == clock);
+        network.inputs = inputs.iter().filter(|net| *net != clock).cloned().collect();
+        network.outputs = outputs.clone();
+        if has_clock
+            && network
+                .cells
+                .iter()
+                .flat_map(|c| c.inputs.iter())
+                .chain(network.outputs.iter())
+                .any(|net| net == clock)
+        {
+            return Err(ParseError::new(format!(
+                "Clock `{}` is used as data!",
+                clock
+            )));
+        }
+        if !has_clock {
+            inputs.push(clock.to_string());
+        }
+
+        return build_circuit(&name, &network, &inputs, Some(clock), &outputs);
+    }
+}
+
+// コメントと行継続を処理し, (行番号, トークン列) にする
+fn logical_lines(src: &str) -> Vec<(usize, Vec<String>)> {
+    let mut res: Vec<(usize, Vec<String>)> = Vec::new();
+    let mut continued = false;
+    for (i, raw) in src.lines().enumerate() {
+        let text = raw.split('#').next().unwrap().trim_end();
+        let (text, next) = match text.strip_suffix('\\') {
+            Some(text) => (text, true),
+            None => (text, false),
+        };
+        let tokens: Vec<String> = text.split_whitespace().map(String::from).collect();
+        if continued {
+            res.last_mut().unwrap().1.extend(tokens);
+        } else if !tokens.is_empty() {
+            res.push((i + 1, tokens));
+        }
+        continued = next && !res.is_empty();
+    }
+    return res;
+}
+
+fn check_cover((plane, out): (&str, &str), n: usize) -> Result<(), String> {
+    if plane.len() != n || !plane.chars().all(|c| matches!(c, '0' | '1' | '-')) {
+        return Err(format!(
+            "Input plane `{}` must have {} of 0, 1 or -!",
+            plane, n
+        ));
+    }
+    if out != "0" && out != "1" {
+        return Err(format!("Output `{}` must be 0 or 1!", out));
+    }
+    return Ok(());
+}
+
+// 積和形のカバーを 2 入力ゲートに分解する
+fn add_names(
+    network: &mut LogicNetwork,
+    inputs: &[String],
+    output: &str,
+    rows: &[(&str, &str)],
+    negated: &mut HashMap<String, String>,
+    fresh: &mut impl FnMut() -> String,
+) {
+    // 2 入力の XOR/XNOR はそのままゲートにする
+    if inputs.len() == 2 && rows.len() == 2 {
+        let mut planes: Vec<&str> = rows.iter().map(|(p, _)| *p).collect();
+        planes.sort();
+        let on = rows[0].1 == "1";
+        let op = match planes.as_slice() {
+            ["01", "10"] => Some(if on { Op::Xor } else { Op::Xnor }),
+            ["00", "11"] => Some(if on { Op::Xnor } else { Op::Xor }),
+            _ => None,
+        };
+        if let Some(op) = op {
+            network.add(op, &[&inputs[0], &inputs[1]], output);
+            return;
+        }
+    }
+
+    // 出力が 0 の行は否定を取る
+    let complemented = rows.first().is_some_and(|(_, out)| *out == "0");
+    let sum = if complemented {
+        fresh()
+    } else {
+        output.to_string()
+    };
+
+    let mut terms = Vec::new();
+    for (plane, _) in rows {
+        let mut literals = Vec::new();
+        for (c, net) in plane.chars().zip(inputs.iter()) {
+            match c {
+                '1' => literals.push(net.clone()),
+                '0' => {
+                    let neg = negated.entry(net.clone()).or_insert_with(|| {
+                        let neg = fresh();
+                        network.add(Op::Not, &[net], &neg);
+                        neg
+                    });
+                    literals.push(neg.clone());
+                }
+                _ => {}
+            }
+        }
+        // 1 行なら積項がそのまま出力
+        let term = if rows.len() == 1 {
+            sum.clone()
+        } else {
+            fresh()
+        };
+        network.add_tree(Op::And, &literals, &term, fresh);
+        terms.push(term);
+    }
+    if rows.len() != 1 {
+        network.add_tree(Op::Or, &terms, &sum, fresh);
+    }
+
+    if complemented {
+        network.add(Op::Not, &[&sum], output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Blif;
+    use crate::circuit::Circuit;
+    use crate::frontends::tests::assert_expr;
+
+    fn parse(body: &str) -> Circuit<4, 0, 1, 0> {
+        let src = format!(".model top\n.inputs a b c\n.outputs y\n{}\n.end\n", body);
+        return Blif::parse(&src, "clk").unwrap();
+    }
+
+    #[test]
+    fn mixed_polarity_cube() {
+        assert_expr(&parse(".names a b y\n10 1"), "a & !b");
+    }
+
+    #[test]
+    fn sum_of_products() {
+        assert_expr(
+            &parse(".names a b c y\n1-0 1\n011 1"),
+            "a & !c | !a & (b & c)",
+        );
+    }
+
+    #[test]
+    fn off_set_cover() {
+        assert_expr(&parse(".names a b c y\n11- 0\n--1 0"), "!(a & b | c)");
+    }
+
+    #[test]
+    fn xor() {
+        assert_expr(
+            &parse(".names a b t\n01 1\n10 1\n.names t c y\n11 1"),
+            "(a ^ b) & c",
+        );
+    }
+}
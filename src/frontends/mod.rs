@@ -0,0 +1,146 @@
+mod bench;
+mod blif;
+mod yosys;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::circuit::Circuit;
+use crate::error::ParseError;
+use crate::logic::LogicNetwork;
+
+pub use bench::Bench;
+pub use blif::Blif;
+pub use yosys::Yosys;
+
+// 論理回路から入出力ポートを持つ Circuit を構築する
+// network.inputs は inputs からクロックポートを除いたもの, outputs は network.outputs に対応するポート名
+pub(crate) fn build_circuit<const N_I: usize, const N_O: usize>(
+    name: &str,
+    network: &LogicNetwork,
+    inputs: &[String],
+    clock: Option<&str>,
+    outputs: &[String],
+) -> Result<Circuit<N_I, 0, N_O, 0>, ParseError> {
+    // Circuit の構築中に失敗すると panic になるため, 先にすべて検査する
+    if inputs.len() != N_I || outputs.len() != N_O {
+        return Err(ParseError::new(format!(
+            "Circuit `{}` has {} inputs and {} outputs, but {} and {} are expected!",
+            name,
+            inputs.len(),
+            outputs.len(),
+            N_I,
+            N_O
+        )));
+    }
+    let mut ports = HashSet::new();
+    for port in inputs.iter().chain(outputs.iter()) {
+        if port.starts_with("_") {
+            return Err(ParseError::new(format!(
+                "Port `{}` must not start with underscore!",
+                port
+            )));
+        }
+        if !ports.insert(port) {
+            return Err(ParseError::new(format!(
+                "Port `{}` is declared twice!",
+                port
+            )));
+        }
+    }
+    network.check().map_err(ParseError::new)?;
+    if network.is_clocked() && clock.is_none() {
+        return Err(ParseError::new(format!(
+            "Circuit `{}` needs a clock!",
+            name
+        )));
+    }
+
+    // 再収斂する経路の段数がそろっていないと別の論理になるため, DFF で段数をそろえる
+    let mut network = network.clone();
+    network.sweep();
+    let used: HashSet<String> = network
+        .inputs
+        .iter()
+        .chain(network.cells.iter().map(|c| &c.output))
+        .cloned()
+        .collect();
+    let mut n_temp = 0;
+    network.balance(&mut || {
+        loop {
+            n_temp += 1;
+            let net = format!("$b{}", n_temp);
+            if !used.contains(&net) {
+                return net;
+            }
+        }
+    });
+
+    let input_names: Vec<&str> = inputs.iter().map(|s| s.as_str()).collect();
+    let output_names: Vec<&str> = outputs.iter().map(|s| s.as_str()).collect();
+    let (mut circuit, input_wires, _, output_cwires, _) = Circuit::create(
+        input_names.try_into().unwrap(),
+        [],
+        output_names.try_into().unwrap(),
+        [],
+        name,
+    );
+
+    let mut clk = None;
+    let mut data = HashMap::new();
+    for (port, wire) in inputs.iter().zip(input_wires) {
+        if Some(port.as_str()) == clock {
+            clk = Some(wire);
+        } else {
+            data.insert(port, wire);
+        }
+    }
+    let data = network
+        .inputs
+        .iter()
+        .map(|net| data.remove(net).unwrap())
+        .collect();
+    let wires = network.build(&mut circuit, data, clk, true);
+
+    for (mut wire, cwire) in wires.into_iter().zip(output_cwires) {
+        // 入力ポートなど名前付きの配線は JTL を挟んで出力ポートにつなぐ
+        if circuit.is_named(&wire) {
+            wire = circuit.jtl(wire);
+        }
+        circuit.unify(wire, cwire);
+    }
+    return Ok(circuit);
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::circuit::Circuit;
+    use crate::equivalence::EquivalenceChecker;
+    use crate::expr::{Expr, synthesize};
+
+    // 読み込んだ回路が, 最後の入力をクロックとして論理式を合成した回路と等価であること
+    pub(crate) fn assert_expr<const N_I: usize>(imported: &Circuit<N_I, 0, 1, 0>, expr: &str) {
+        let expr = Expr::parse(expr).unwrap();
+        let mut names: Vec<&str> = imported.netlist.inputs.iter().map(|s| s.as_str()).collect();
+        let (mut reference, inputs, _, [y], _) = Circuit::create(
+            names.clone().try_into().unwrap(),
+            [],
+            ["y"],
+            [],
+            "reference",
+        );
+        let mut inputs = Vec::from(inputs);
+        let clk = inputs.pop().unwrap();
+        names.pop();
+        let [q] = synthesize(&mut reference, &[&expr], &names, inputs, clk)
+            .try_into()
+            .unwrap();
+        reference.unify(q, y);
+        match EquivalenceChecker::new().check(imported, &reference) {
+            Ok(verdict) => assert!(verdict.is_proven(), "{:?}", verdict),
+            Err(e) => panic!(
+                "`{}` is not equivalent to `{}`:\n{}",
+                imported.netlist.name, expr, e
+            ),
+        }
+    }
+}
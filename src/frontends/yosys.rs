@@ -0,0 +1,418 @@
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use super::build_circuit;
+use crate::circuit::Circuit;
+use crate::error::ParseError;
+use crate::logic::{LogicNetwork, Op};
+
+// Yosys の write_json で出力したネットリストを SFQ ゲートにマッピングする
+// 事前に `synth -flatten` などで $_AND_ / $_DFF_P_ 等の汎用セルにしておくこと
+pub struct Yosys;
+
+impl Yosys {
+    // トップモジュールを読み込む
+    // clock という名前の入力ポートをクロックとし, なければ最後の入力ポートとして追加する
+    pub fn parse<const N_I: usize, const N_O: usize>(
+        src: &str,
+        clock: &str,
+    ) -> Result<Circuit<N_I, 0, N_O, 0>, ParseError> {
+        let doc = parse_json(src)?;
+        let modules = modules(&doc)?;
+        let top: Vec<&String> = modules
+            .iter()
+            .filter(|(_, m)| is_true(&m["attributes"]["top"]))
+            .map(|(name, _)| name)
+            .collect();
+        let name = match (top.as_slice(), modules.len()) {
+            ([name], _) => *name,
+            ([], 1) => modules.keys().next().unwrap(),
+            _ => return Err(ParseError::new("Top module is ambiguous!")),
+        };
+        return load(name, &modules[name], clock);
+    }
+
+    // 名前を指定してモジュールを読み込む
+    pub fn parse_module<const N_I: usize, const N_O: usize>(
+        src: &str,
+        module: &str,
+        clock: &str,
+    ) -> Result<Circuit<N_I, 0, N_O, 0>, ParseError> {
+        let doc = parse_json(src)?;
+        let modules = modules(&doc)?;
+        let m = modules
+            .get(module)
+            .ok_or_else(|| ParseError::new(format!("Module `{}` is not found!", module)))?;
+        return load(module, m, clock);
+    }
+}
+
+fn parse_json(src: &str) -> Result<Value, ParseError> {
+    return serde_json::from_str(src).map_err(|e| ParseError::at(e.line(), e.to_string()));
+}
+
+fn modules(doc: &Value) -> Result<&Map<String, Value>, ParseError> {
+    return doc["modules"]
+        .as_object()
+        .ok_or_else(|| ParseError::new("`modules` must be an object!"));
+}
+
+// 属性値は "00..01" の文字列か数値
+fn is_true(v: &Value) -> bool {
+    return match v {
+        Value::String(s) => s.contains('1'),
+        Value::Number(n) => n.as_u64().is_some_and(|n| n != 0),
+        _ => false,
+    };
+}
+
+// ビットの参照: 配線番号または定数
+enum Bit {
+    Net(u64),
+    Const(bool),
+}
+
+fn bits(v: &Value, what: &str) -> Result<Vec<Bit>, ParseError> {
+    let err = || ParseError::new(format!("Bits of {} are invalid!", what));
+    let arr = v.as_array().ok_or_else(err)?;
+    let mut res = Vec::new();
+    for b in arr {
+        res.push(match b {
+            Value::Number(n) => Bit::Net(n.as_u64().ok_or_else(err)?),
+            Value::String(s) if s == "0" => Bit::Const(false),
+            Value::String(s) if s == "1" => Bit::Const(true),
+            Value::String(s) => {
+                return Err(ParseError::new(format!(
+                    "Undefined bit `{}` in {}!",
+                    s, what
+                )));
+            }
+            _ => return Err(err()),
+        });
+    }
+    return Ok(res);
+}
+
+// 多ビットの信号は name_0, name_1, ... に分ける
+fn bit_name(name: &str, width: usize, i: usize) -> String {
+    if width == 1 {
+        return name.to_string();
+    }
+    return format!("{}_{}", name, i);
+}
+
+fn load<const N_I: usize, const N_O: usize>(
+    name: &str,
+    m: &Value,
+    clock: &str,
+) -> Result<Circuit<N_I, 0, N_O, 0>, ParseError> {
+    let err = |msg: String| ParseError::new(format!("In module `{}`: {}", name, msg));
+
+    /* ------------------- ports ------------------- */
+    let empty = Map::new();
+    let ports = m["ports"].as_object().unwrap_or(&empty);
+    // 配線番号 -> 配線名 (ポート名を優先する)
+    let mut names: HashMap<u64, String> = HashMap::new();
+    let mut inputs: Vec<String> = Vec::new();
+    let mut outputs: Vec<(String, Bit)> = Vec::new();
+    let mut clock_bit: Option<u64> = None;
+    for (port, def) in ports {
+        let port_bits = bits(&def["bits"], &format!("port `{}`", port))?;
+        let width = port_bits.len();
+        match def["direction"].as_str() {
+            Some("input") => {
+                if port == clock && width != 1 {
+                    return Err(err(format!("Clock `{}` must be 1 bit!", port)));
+                }
+                for (i, bit) in port_bits.into_iter().enumerate() {
+                    let Bit::Net(b) = bit else {
+                        return Err(err(format!("Input `{}` is tied to a constant!", port)));
+                    };
+                    let net = bit_name(port, width, i);
+                    if port == clock {
+                        clock_bit = Some(b);
+                    }
+                    names.insert(b, net.clone());
+                    inputs.push(net);
+                }
+            }
+            Some("output") => {
+                for (i, bit) in port_bits.into_iter().enumerate() {
+                    outputs.push((bit_name(port, width, i), bit));
+                }
+            }
+            _ => {
+                return Err(err(format!("Port `{}` must be input or output!", port)));
+            }
+        }
+    }
+    if clock_bit.is_none() {
+        inputs.push(clock.to_string());
+    }
+    for (port, bit) in outputs.iter() {
+        if let Bit::Net(b) = bit {
+            names.entry(*b).or_insert_with(|| port.clone());
+        }
+    }
+    // 内部の配線は ($ で始まらない) 名前があれば使う
+    if let Some(netnames) = m["netnames"].as_object() {
+        for (net, def) in netnames {
+            if is_true(&def["hide_name"]) {
+                continue;
+            }
+            let net_bits = bits(&def["bits"], &format!("net `{}`", net))?;
+            let width = net_bits.len();
+            for (i, bit) in net_bits.into_iter().enumerate() {
+                if let Bit::Net(b) = bit {
+                    let label = if width == 1 {
+                        net.clone()
+                    } else {
+                        format!("{}[{}]", net, i)
+                    };
+                    names.entry(b).or_insert(label);
+                }
+            }
+        }
+    }
+
+    /* ------------------- cells ------------------- */
+    let mut network = LogicNetwork::new();
+    network.inputs = inputs.iter().filter(|p| *p != clock).cloned().collect();
+    let mut n_temp = 0;
+    let mut fresh = || {
+        n_temp += 1;
+        return format!("$t{}", n_temp);
+    };
+    // 定数は 1 度だけ生成する
+    let mut consts = [false, false];
+    let net = |bit: &Bit, consts: &mut [bool; 2]| -> String {
+        return match bit {
+            Bit::Net(b) => names.get(b).cloned().unwrap_or_else(|| format!("${}", b)),
+            Bit::Const(v) => {
+                consts[*v as usize] = true;
+                format!("$const{}", *v as u8)
+            }
+        };
+    };
+
+    let empty = Map::new();
+    let cells = m["cells"].as_object().unwrap_or(&empty);
+    for (cell_name, cell) in cells {
+        let kind = cell["type"].as_str().unwrap_or("");
+        let conns = &cell["connections"];
+        // ピンごとのビット列
+        let mut pin = |p: &str| -> Result<Vec<String>, ParseError> {
+            let bs = bits(&conns[p], &format!("pin `{}` of cell `{}`", p, cell_name))?;
+            return Ok(bs.iter().map(|b| net(b, &mut consts)).collect());
+        };
+        // 多ビットのセルは幅がそろっていること
+        let same_width = |nets: &[&Vec<String>]| -> Result<(), ParseError> {
+            if nets.iter().any(|n| n.len() != nets[0].len()) {
+                return Err(err(format!(
+                    "Widths of cell `{}` differ, run `techmap` first!",
+                    cell_name
+                )));
+            }
+            return Ok(());
+        };
+        // 1 ビットのセルはすべてのピンが 1 ビットであること
+        let one_bit = |nets: &[&Vec<String>]| -> Result<(), ParseError> {
+            if nets.iter().any(|n| n.len() != 1) {
+                return Err(err(format!(
+                    "Pins of cell `{}` must be 1 bit wide!",
+                    cell_name
+                )));
+            }
+            return Ok(());
+        };
+
+        match kind {
+            "$_BUF_" | "$_NOT_" | "$pos" | "$not" => {
+                let (a, y) = (pin("A")?, pin("Y")?);
+                same_width(&[&a, &y])?;
+                let op = if kind.contains("BUF") || kind == "$pos" {
+                    Op::Buf
+                } else {
+                    Op::Not
+                };
+                for (a, y) in a.iter().zip(y.iter()) {
+                    network.add(op, &[a], y);
+                }
+            }
+            "$_AND_" | "$_OR_" | "$_XOR_" | "$_XNOR_" | "$and" | "$or" | "$xor" | "$xnor" => {
+                let (a, b, y) = (pin("A")?, pin("B")?, pin("Y")?);
+                same_width(&[&a, &b, &y])?;
+                let op = match kind.trim_matches(|c| c == '$' || c == '_') {
+                    "AND" | "and" => Op::And,
+                    "OR" | "or" => Op::Or,
+                    "XOR" | "xor" => Op::Xor,
+                    _ => Op::Xnor,
+                };
+                for i in 0..y.len() {
+                    network.add(op, &[&a[i], &b[i]], &y[i]);
+                }
+            }
+            "$_NAND_" | "$_NOR_" => {
+                let (a, b, y) = (pin("A")?, pin("B")?, pin("Y")?);
+                one_bit(&[&a, &b, &y])?;
+                let op = if kind == "$_NAND_" { Op::And } else { Op::Or };
+                let t = fresh();
+                network.add(op, &[&a[0], &b[0]], &t);
+                network.add(Op::Not, &[&t], &y[0]);
+            }
+            // A & ~B, A | ~B
+            "$_ANDNOT_" | "$_ORNOT_" => {
+                let (a, b, y) = (pin("A")?, pin("B")?, pin("Y")?);
+                one_bit(&[&a, &b, &y])?;
+                let op = if kind == "$_ANDNOT_" { Op::And } else { Op::Or };
+                let t = fresh();
+                network.add(Op::Not, &[&b[0]], &t);
+                network.add(op, &[&a[0], &t], &y[0]);
+            }
+            // S ? B : A = (A & ~S) | (B & S)
+            "$_MUX_" => {
+                let (a, b, s, y) = (pin("A")?, pin("B")?, pin("S")?, pin("Y")?);
+                one_bit(&[&a, &b, &s, &y])?;
+                let (ns, t1, t2) = (fresh(), fresh(), fresh());
+                network.add(Op::Not, &[&s[0]], &ns);
+                network.add(Op::And, &[&a[0], &ns], &t1);
+                network.add(Op::And, &[&b[0], &s[0]], &t2);
+                network.add(Op::Or, &[&t1, &t2], &y[0]);
+            }
+            "$_DFF_P_" | "$dff" => {
+                let clk_pin = if kind == "$dff" { "CLK" } else { "C" };
+                let (c, d, q) = (pin(clk_pin)?, pin("D")?, pin("Q")?);
+                same_width(&[&d, &q])?;
+                if kind == "$dff" && !is_true(&cell["parameters"]["CLK_POLARITY"]) {
+                    return Err(err(format!(
+                        "Cell `{}` is a negative edge flip-flop!",
+                        cell_name
+                    )));
+                }
+                if c.len() != 1 || c[0] != clock {
+                    return Err(err(format!(
+                        "Cell `{}` is clocked by `{}` instead of `{}`!",
+                        cell_name,
+                        c.join(", "),
+                        clock
+                    )));
+                }
+                for (d, q) in d.iter().zip(q.iter()) {
+                    network.add(Op::Dff, &[d], q);
+                }
+            }
+            _ => {
+                return Err(err(format!(
+                    "Cell `{}` of type `{}` cannot be mapped to SFQ gates!",
+                    cell_name, kind
+                )));
+            }
+        }
+    }
+    network.outputs = outputs.iter().map(|(_, b)| net(b, &mut consts)).collect();
+    for (v, used) in consts.iter().enumerate() {
+        if *used {
+            let op = if v == 1 { Op::One } else { Op::Zero };
+            network.add(op, &[], &format!("$const{}", v));
+        }
+    }
+
+    // クロックはフリップフロップのクロック入力以外に使えない
+    if let Some(b) = clock_bit
+        && network
+            .cells
+            .iter()
+            .flat_map(|c| c.inputs.iter())
+            .chain(network.outputs.iter())
+            .any(|n| *n == names[&b])
+    {
+        return Err(err(format!("Clock `{}` is used as data!", clock)));
+    }
+
+    let output_ports: Vec<String> = outputs.into_iter().map(|(p, _)| p).collect();
+    return build_circuit(name, &network, &inputs, Some(clock), &output_ports);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Yosys;
+    use crate::circuit::Circuit;
+    use crate::frontends::tests::assert_expr;
+
+    // 入力 a, b, c (配線 2, 3, 4) と出力 y (配線 9) を持つモジュール
+    fn module(cells: &str) -> String {
+        return format!(
+            r#"{{"modules": {{"top": {{
+                "attributes": {{"top": "1"}},
+                "ports": {{
+                    "a": {{"direction": "input", "bits": [2]}},
+                    "b": {{"direction": "input", "bits": [3]}},
+                    "c": {{"direction": "input", "bits": [4]}},
+                    "y": {{"direction": "output", "bits": [9]}}
+                }},
+                "cells": {{{}}}
+            }}}}}}"#,
+            cells
+        );
+    }
+
+    #[test]
+    fn reconvergent_paths_are_balanced() {
+        let src = module(
+            r#""g1": {"type": "$_AND_", "connections": {"A": [2], "B": [3], "Y": [5]}},
+               "g2": {"type": "$_XOR_", "connections": {"A": [5], "B": [4], "Y": [9]}}"#,
+        );
+        let c: Circuit<4, 0, 1, 0> = Yosys::parse(&src, "clk").unwrap();
+        assert_expr(&c, "a & b ^ c");
+    }
+
+    #[test]
+    fn mux() {
+        let src = module(
+            r#""g1": {"type": "$_MUX_", "connections": {"A": [2], "B": [3], "S": [4], "Y": [9]}}"#,
+        );
+        let c: Circuit<4, 0, 1, 0> = Yosys::parse(&src, "clk").unwrap();
+        assert_expr(&c, "a & !c | b & c");
+    }
+
+    #[test]
+    fn andnot() {
+        let src = module(
+            r#""g1": {"type": "$_ANDNOT_", "connections": {"A": [2], "B": [3], "Y": [5]}},
+               "g2": {"type": "$_OR_", "connections": {"A": [5], "B": [4], "Y": [9]}}"#,
+        );
+        let c: Circuit<4, 0, 1, 0> = Yosys::parse(&src, "clk").unwrap();
+        assert_expr(&c, "a & !b | c");
+    }
+
+    // q を毎サイクル a と XOR する. ループがあっても段数をそろえられること
+    #[test]
+    fn feedback_loop() {
+        let src = r#"{"modules": {"acc": {
+            "ports": {
+                "a": {"direction": "input", "bits": [2]},
+                "clk": {"direction": "input", "bits": [3]},
+                "y": {"direction": "output", "bits": [9]}
+            },
+            "cells": {
+                "g1": {"type": "$_XOR_", "connections": {"A": [2], "B": [9], "Y": [5]}},
+                "g2": {"type": "$_DFF_P_", "connections": {"C": [3], "D": [5], "Q": [9]}}
+            }
+        }}}"#;
+        let res: Result<Circuit<2, 0, 1, 0>, _> = Yosys::parse(src, "clk");
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn wide_single_bit_cell_is_rejected() {
+        let src = module(
+            r#""g1": {"type": "$_NAND_", "connections": {"A": [2, 3], "B": [4], "Y": [9]}}"#,
+        );
+        let res: Result<Circuit<4, 0, 1, 0>, _> = Yosys::parse(&src, "clk");
+        assert!(res.is_err());
+        let src =
+            module(r#""g1": {"type": "$_MUX_", "connections": {"A": [2], "B": [3], "Y": [9]}}"#);
+        let res: Result<Circuit<4, 0, 1, 0>, _> = Yosys::parse(&src, "clk");
+        assert!(res.is_err());
+    }
+}
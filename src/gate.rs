@@ -1,6 +1,9 @@
+use std::sync::Arc;
+
 use crate::id::WireID;
+use crate::netlist::Netlist;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) enum Gate {
     Jtl {
         name: String,
@@ -19,6 +22,36 @@ pub(crate) enum Gate {
         b: WireID,
         q: WireID,
     },
+    Split3 {
+        name: String,
+        a: WireID,
+        q1: WireID,
+        q2: WireID,
+        q3: WireID,
+    },
+    Split4 {
+        name: String,
+        a: WireID,
+        q1: WireID,
+        q2: WireID,
+        q3: WireID,
+        q4: WireID,
+    },
+    Merge3 {
+        name: String,
+        a: WireID,
+        b: WireID,
+        c: WireID,
+        q: WireID,
+    },
+    Merge4 {
+        name: String,
+        a: WireID,
+        b: WireID,
+        c: WireID,
+        d: WireID,
+        q: WireID,
+    },
     And {
         name: String,
         a: WireID,
@@ -66,15 +99,99 @@ pub(crate) enum Gate {
         clk: WireID,
         q: WireID,
     },
+    DffR {
+        name: String,
+        a: WireID,
+        r: WireID,
+        clk: WireID,
+        q: WireID,
+    },
+    DffRSync {
+        name: String,
+        a: WireID,
+        r: WireID,
+        clk: WireID,
+        q: WireID,
+    },
+    DffC {
+        name: String,
+        a: WireID,
+        clk: WireID,
+        q1: WireID,
+        q2: WireID,
+    },
+    NdroC {
+        name: String,
+        a: WireID,
+        b: WireID,
+        clk: WireID,
+        q1: WireID,
+        q2: WireID,
+    },
     Buff {
         name: String,
         a: WireID,
         q: WireID,
     },
+    FirstArrival {
+        name: String,
+        a: WireID,
+        b: WireID,
+        q: WireID,
+    },
+    LastArrival {
+        name: String,
+        a: WireID,
+        b: WireID,
+        q: WireID,
+    },
+    Inhibit {
+        name: String,
+        a: WireID,
+        b: WireID,
+        q: WireID,
+    },
+    Tff {
+        name: String,
+        a: WireID,
+        q: WireID,
+    },
+    Tff2 {
+        name: String,
+        a: WireID,
+        q1: WireID,
+        q2: WireID,
+    },
+    TffR {
+        name: String,
+        a: WireID,
+        r: WireID,
+        q: WireID,
+    },
+    DcSfq {
+        name: String,
+        a: WireID,
+        q: WireID,
+    },
+    SfqDc {
+        name: String,
+        a: WireID,
+        q: WireID,
+    },
     ZeroAsync {
         name: String,
         q: WireID,
     },
+    ZeroSync {
+        name: String,
+        clk: WireID,
+        q: WireID,
+    },
+    OneSync {
+        name: String,
+        clk: WireID,
+        q: WireID,
+    },
     Terminate {
         name: String,
         a: WireID,
@@ -83,7 +200,415 @@ pub(crate) enum Gate {
         name: String,
         inputs: Vec<WireID>,
         outputs: Vec<WireID>,
-        circuit: String,
+        circuit: Arc<Netlist>,
     },
     _Reserved, // 将来のゲート追加に備えてパターンマッチでワイルドカードを使ってもWarningが出ないようにする用
 }
+
+impl Gate {
+    pub(crate) fn name(&self) -> &str {
+        return match self {
+            Gate::Jtl { name, .. }
+            | Gate::Split { name, .. }
+            | Gate::Merge { name, .. }
+            | Gate::Split3 { name, .. }
+            | Gate::Split4 { name, .. }
+            | Gate::Merge3 { name, .. }
+            | Gate::Merge4 { name, .. }
+            | Gate::And { name, .. }
+            | Gate::Or { name, .. }
+            | Gate::Xor { name, .. }
+            | Gate::Not { name, .. }
+            | Gate::Xnor { name, .. }
+            | Gate::Dff { name, .. }
+            | Gate::Ndro { name, .. }
+            | Gate::DffR { name, .. }
+            | Gate::DffRSync { name, .. }
+            | Gate::DffC { name, .. }
+            | Gate::NdroC { name, .. }
+            | Gate::Buff { name, .. }
+            | Gate::FirstArrival { name, .. }
+            | Gate::LastArrival { name, .. }
+            | Gate::Inhibit { name, .. }
+            | Gate::Tff { name, .. }
+            | Gate::Tff2 { name, .. }
+            | Gate::TffR { name, .. }
+            | Gate::DcSfq { name, .. }
+            | Gate::SfqDc { name, .. }
+            | Gate::ZeroAsync { name, .. }
+            | Gate::ZeroSync { name, .. }
+            | Gate::OneSync { name, .. }
+            | Gate::Terminate { name, .. }
+            | Gate::Subcircuit { name, .. } => name,
+            Gate::_Reserved => unreachable!(),
+        };
+    }
+
+    // ゲートの種類 (サブサーキットは回路名)
+    pub(crate) fn kind(&self) -> &str {
+        return match self {
+            Gate::Jtl { .. } => "JTL",
+            Gate::Split { .. } => "SPLIT",
+            Gate::Merge { .. } => "MERGE",
+            Gate::Split3 { .. } => "SPLIT3",
+            Gate::Split4 { .. } => "SPLIT4",
+            Gate::Merge3 { .. } => "MERGE3",
+            Gate::Merge4 { .. } => "MERGE4",
+            Gate::And { .. } => "AND",
+            Gate::Or { .. } => "OR",
+            Gate::Xor { .. } => "XOR",
+            Gate::Not { .. } => "NOT",
+            Gate::Xnor { .. } => "XNOR",
+            Gate::Dff { .. } => "DFF",
+            Gate::Ndro { .. } => "NDRO",
+            Gate::DffR { .. } => "DFFR",
+            Gate::DffRSync { .. } => "DFFR_SYNC",
+            Gate::DffC { .. } => "DFFC",
+            Gate::NdroC { .. } => "NDROC",
+            Gate::Buff { .. } => "BUFF",
+            Gate::FirstArrival { .. } => "FIRST_ARRIVAL",
+            Gate::LastArrival { .. } => "LAST_ARRIVAL",
+            Gate::Inhibit { .. } => "INHIBIT",
+            Gate::Tff { .. } => "TFF",
+            Gate::Tff2 { .. } => "TFF2",
+            Gate::TffR { .. } => "TFFR",
+            Gate::DcSfq { .. } => "DCSFQ",
+            Gate::SfqDc { .. } => "SFQDC",
+            Gate::ZeroAsync { .. } => "ZERO_ASYNC",
+            Gate::ZeroSync { .. } => "ZERO_SYNC",
+            Gate::OneSync { .. } => "ONE_SYNC",
+            Gate::Terminate { .. } => "TERMINATE",
+            Gate::Subcircuit { circuit, .. } => &circuit.name,
+            Gate::_Reserved => unreachable!(),
+        };
+    }
+
+    // (ピン名, WireID) の入力ピン一覧
+    pub(crate) fn inputs(&self) -> Vec<(&str, WireID)> {
+        return match self {
+            Gate::Jtl { a, .. }
+            | Gate::Split { a, .. }
+            | Gate::Split3 { a, .. }
+            | Gate::Split4 { a, .. }
+            | Gate::Buff { a, .. }
+            | Gate::Tff { a, .. }
+            | Gate::Tff2 { a, .. }
+            | Gate::DcSfq { a, .. }
+            | Gate::SfqDc { a, .. } => vec![("a", *a)],
+            Gate::TffR { a, r, .. } => vec![("a", *a), ("r", *r)],
+            Gate::Merge3 { a, b, c, .. } => vec![("a", *a), ("b", *b), ("c", *c)],
+            Gate::Merge4 { a, b, c, d, .. } => {
+                vec![("a", *a), ("b", *b), ("c", *c), ("d", *d)]
+            }
+            Gate::Terminate { a, .. } => vec![("a", *a)],
+            Gate::Merge { a, b, .. }
+            | Gate::FirstArrival { a, b, .. }
+            | Gate::LastArrival { a, b, .. }
+            | Gate::Inhibit { a, b, .. } => vec![("a", *a), ("b", *b)],
+            Gate::And { a, b, clk, .. }
+            | Gate::Or { a, b, clk, .. }
+            | Gate::Xor { a, b, clk, .. }
+            | Gate::Xnor { a, b, clk, .. }
+            | Gate::Ndro { a, b, clk, .. }
+            | Gate::NdroC { a, b, clk, .. } => vec![("a", *a), ("b", *b), ("clk", *clk)],
+            Gate::DffR { a, r, clk, .. } | Gate::DffRSync { a, r, clk, .. } => {
+                vec![("a", *a), ("r", *r), ("clk", *clk)]
+            }
+            Gate::Not { a, clk, .. } | Gate::Dff { a, clk, .. } | Gate::DffC { a, clk, .. } => {
+                vec![("a", *a), ("clk", *clk)]
+            }
+            Gate::ZeroSync { clk, .. } | Gate::OneSync { clk, .. } => vec![("clk", *clk)],
+            Gate::ZeroAsync { .. } => vec![],
+            Gate::Subcircuit {
+                inputs, circuit, ..
+            } => circuit
+                .input_ports()
+                .map(|s| s.as_str())
+                .zip(inputs.iter().copied())
+                .collect(),
+            Gate::_Reserved => unreachable!(),
+        };
+    }
+
+    // (ピン名, WireID) の出力ピン一覧
+    pub(crate) fn outputs(&self) -> Vec<(&str, WireID)> {
+        return match self {
+            Gate::Split { q1, q2, .. }
+            | Gate::Tff2 { q1, q2, .. }
+            | Gate::DffC { q1, q2, .. }
+            | Gate::NdroC { q1, q2, .. } => {
+                vec![("q1", *q1), ("q2", *q2)]
+            }
+            Gate::Split3 { q1, q2, q3, .. } => vec![("q1", *q1), ("q2", *q2), ("q3", *q3)],
+            Gate::Split4 { q1, q2, q3, q4, .. } => {
+                vec![("q1", *q1), ("q2", *q2), ("q3", *q3), ("q4", *q4)]
+            }
+            Gate::Jtl { q, .. }
+            | Gate::Merge { q, .. }
+            | Gate::Merge3 { q, .. }
+            | Gate::Merge4 { q, .. }
+            | Gate::And { q, .. }
+            | Gate::Or { q, .. }
+            | Gate::Xor { q, .. }
+            | Gate::Not { q, .. }
+            | Gate::Xnor { q, .. }
+            | Gate::Dff { q, .. }
+            | Gate::Ndro { q, .. }
+            | Gate::DffR { q, .. }
+            | Gate::DffRSync { q, .. }
+            | Gate::Buff { q, .. }
+            | Gate::FirstArrival { q, .. }
+            | Gate::LastArrival { q, .. }
+            | Gate::Inhibit { q, .. }
+            | Gate::Tff { q, .. }
+            | Gate::TffR { q, .. }
+            | Gate::DcSfq { q, .. }
+            | Gate::SfqDc { q, .. }
+            | Gate::ZeroAsync { q, .. }
+            | Gate::ZeroSync { q, .. }
+            | Gate::OneSync { q, .. } => vec![("q", *q)],
+            Gate::Terminate { .. } => vec![],
+            Gate::Subcircuit {
+                outputs, circuit, ..
+            } => circuit
+                .output_ports()
+                .map(|s| s.as_str())
+                .zip(outputs.iter().copied())
+                .collect(),
+            Gate::_Reserved => unreachable!(),
+        };
+    }
+
+    // 種類ごとの (入力ピン名, 出力ピン名). サブサーキットは含まない
+    pub(crate) fn pin_names(
+        kind: &str,
+    ) -> Option<(&'static [&'static str], &'static [&'static str])> {
+        return match kind {
+            "JTL" | "BUFF" | "TFF" | "DCSFQ" | "SFQDC" => Some((&["a"], &["q"])),
+            "SPLIT" | "TFF2" => Some((&["a"], &["q1", "q2"])),
+            "SPLIT3" => Some((&["a"], &["q1", "q2", "q3"])),
+            "SPLIT4" => Some((&["a"], &["q1", "q2", "q3", "q4"])),
+            "MERGE3" => Some((&["a", "b", "c"], &["q"])),
+            "MERGE4" => Some((&["a", "b", "c", "d"], &["q"])),
+            "TFFR" => Some((&["a", "r"], &["q"])),
+            "MERGE" | "FIRST_ARRIVAL" | "LAST_ARRIVAL" | "INHIBIT" => Some((&["a", "b"], &["q"])),
+            "AND" | "OR" | "XOR" | "XNOR" | "NDRO" => Some((&["a", "b", "clk"], &["q"])),
+            "NOT" | "DFF" => Some((&["a", "clk"], &["q"])),
+            "DFFR" | "DFFR_SYNC" => Some((&["a", "r", "clk"], &["q"])),
+            "DFFC" => Some((&["a", "clk"], &["q1", "q2"])),
+            "NDROC" => Some((&["a", "b", "clk"], &["q1", "q2"])),
+            "ZERO_ASYNC" => Some((&[], &["q"])),
+            "ZERO_SYNC" | "ONE_SYNC" => Some((&["clk"], &["q"])),
+            "TERMINATE" => Some((&["a"], &[])),
+            _ => None,
+        };
+    }
+
+    // pin_names() の順に並んだ WireID からゲートを作る
+    pub(crate) fn from_pins(kind: &str, name: String, pins: &[WireID]) -> Option<Gate> {
+        let (inputs, outputs) = Self::pin_names(kind)?;
+        if pins.len() != inputs.len() + outputs.len() {
+            return None;
+        }
+        let p = |i: usize| pins[i];
+        let gate = match kind {
+            "JTL" => Gate::Jtl {
+                name,
+                a: p(0),
+                q: p(1),
+            },
+            "BUFF" => Gate::Buff {
+                name,
+                a: p(0),
+                q: p(1),
+            },
+            "TFF" => Gate::Tff {
+                name,
+                a: p(0),
+                q: p(1),
+            },
+            "TFF2" => Gate::Tff2 {
+                name,
+                a: p(0),
+                q1: p(1),
+                q2: p(2),
+            },
+            "TFFR" => Gate::TffR {
+                name,
+                a: p(0),
+                r: p(1),
+                q: p(2),
+            },
+            "SPLIT" => Gate::Split {
+                name,
+                a: p(0),
+                q1: p(1),
+                q2: p(2),
+            },
+            "MERGE" => Gate::Merge {
+                name,
+                a: p(0),
+                b: p(1),
+                q: p(2),
+            },
+            "SPLIT3" => Gate::Split3 {
+                name,
+                a: p(0),
+                q1: p(1),
+                q2: p(2),
+                q3: p(3),
+            },
+            "SPLIT4" => Gate::Split4 {
+                name,
+                a: p(0),
+                q1: p(1),
+                q2: p(2),
+                q3: p(3),
+                q4: p(4),
+            },
+            "MERGE3" => Gate::Merge3 {
+                name,
+                a: p(0),
+                b: p(1),
+                c: p(2),
+                q: p(3),
+            },
+            "MERGE4" => Gate::Merge4 {
+                name,
+                a: p(0),
+                b: p(1),
+                c: p(2),
+                d: p(3),
+                q: p(4),
+            },
+            "FIRST_ARRIVAL" => Gate::FirstArrival {
+                name,
+                a: p(0),
+                b: p(1),
+                q: p(2),
+            },
+            "LAST_ARRIVAL" => Gate::LastArrival {
+                name,
+                a: p(0),
+                b: p(1),
+                q: p(2),
+            },
+            "INHIBIT" => Gate::Inhibit {
+                name,
+                a: p(0),
+                b: p(1),
+                q: p(2),
+            },
+            "AND" => Gate::And {
+                name,
+                a: p(0),
+                b: p(1),
+                clk: p(2),
+                q: p(3),
+            },
+            "OR" => Gate::Or {
+                name,
+                a: p(0),
+                b: p(1),
+                clk: p(2),
+                q: p(3),
+            },
+            "XOR" => Gate::Xor {
+                name,
+                a: p(0),
+                b: p(1),
+                clk: p(2),
+                q: p(3),
+            },
+            "XNOR" => Gate::Xnor {
+                name,
+                a: p(0),
+                b: p(1),
+                clk: p(2),
+                q: p(3),
+            },
+            "NDRO" => Gate::Ndro {
+                name,
+                a: p(0),
+                b: p(1),
+                clk: p(2),
+                q: p(3),
+            },
+            "NOT" => Gate::Not {
+                name,
+                a: p(0),
+                clk: p(1),
+                q: p(2),
+            },
+            "DFF" => Gate::Dff {
+                name,
+                a: p(0),
+                clk: p(1),
+                q: p(2),
+            },
+            "DFFR" => Gate::DffR {
+                name,
+                a: p(0),
+                r: p(1),
+                clk: p(2),
+                q: p(3),
+            },
+            "DFFR_SYNC" => Gate::DffRSync {
+                name,
+                a: p(0),
+                r: p(1),
+                clk: p(2),
+                q: p(3),
+            },
+            "DFFC" => Gate::DffC {
+                name,
+                a: p(0),
+                clk: p(1),
+                q1: p(2),
+                q2: p(3),
+            },
+            "NDROC" => Gate::NdroC {
+                name,
+                a: p(0),
+                b: p(1),
+                clk: p(2),
+                q1: p(3),
+                q2: p(4),
+            },
+            "DCSFQ" => Gate::DcSfq {
+                name,
+                a: p(0),
+                q: p(1),
+            },
+            "SFQDC" => Gate::SfqDc {
+                name,
+                a: p(0),
+                q: p(1),
+            },
+            "ZERO_ASYNC" => Gate::ZeroAsync { name, q: p(0) },
+            "ZERO_SYNC" => Gate::ZeroSync {
+                name,
+                clk: p(0),
+                q: p(1),
+            },
+            "ONE_SYNC" => Gate::OneSync {
+                name,
+                clk: p(0),
+                q: p(1),
+            },
+            "TERMINATE" => Gate::Terminate { name, a: p(0) },
+            _ => return None,
+        };
+        return Some(gate);
+    }
+}
+
+// X{kind}{id}. kind が数字で終わる (SPLIT3 など) ときは id との間に _ を入れて区別する
+pub(crate) fn gate_name(kind: &str, id: u32) -> String {
+    let sep = if kind.ends_with(|c: char| c.is_ascii_digit()) {
+        "_"
+    } else {
+        ""
+    };
+    return format!("X{}{}{}", kind, sep, id);
+}
@@ -1,9 +1,30 @@
 mod backends;
 mod circuit;
+mod components;
+mod constant;
+mod design;
+mod equivalence;
+mod error;
+mod expr;
+mod frontends;
 mod gate;
 mod id;
+mod logic;
+mod netlist;
+mod simulator;
+mod temporal;
+mod truth_table;
 mod wire;
 
 pub use backends::*;
 pub use circuit::Circuit;
+pub use components::*;
+pub use design::Design;
+pub use equivalence::{CheckError, Counterexample, EquivalenceChecker, Verdict};
+pub use error::ParseError;
+pub use expr::Expr;
+pub use frontends::*;
+pub use netlist::Netlist;
+pub use temporal::TemporalSimulator;
+pub use truth_table::TruthTable;
 pub use wire::{CounterWire, Wire};
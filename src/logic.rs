@@ -0,0 +1,383 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::circuit::Circuit;
+use crate::wire::{CounterWire, Wire};
+
+// 論理合成ツールの出力などを表すゲートレベルの論理回路
+// 配線は名前で識別し, SFQ ゲートへのマッピングは build() で行う
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Op {
+    Buf, // 配線の別名
+    Not,
+    And,
+    Or,
+    Xor,
+    Xnor,
+    Dff,
+    Zero,
+    One,
+}
+
+impl Op {
+    pub(crate) fn arity(&self) -> usize {
+        return match self {
+            Op::Zero | Op::One => 0,
+            Op::Buf | Op::Not | Op::Dff => 1,
+            Op::And | Op::Or | Op::Xor | Op::Xnor => 2,
+        };
+    }
+
+    // クロックを必要とするか
+    pub(crate) fn is_clocked(&self) -> bool {
+        return !matches!(self, Op::Buf | Op::Zero);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Cell {
+    pub(crate) op: Op,
+    pub(crate) inputs: Vec<String>,
+    pub(crate) output: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LogicNetwork {
+    pub(crate) inputs: Vec<String>,
+    pub(crate) outputs: Vec<String>, // 同じ配線が複数の出力になってもよい
+    pub(crate) cells: Vec<Cell>,
+}
+
+impl LogicNetwork {
+    pub(crate) fn new() -> Self {
+        return Self::default();
+    }
+
+    pub(crate) fn add(&mut self, op: Op, inputs: &[&str], output: &str) {
+        self.cells.push(Cell {
+            op,
+            inputs: inputs.iter().map(|s| s.to_string()).collect(),
+            output: output.to_string(),
+        });
+    }
+
+    // 多入力の AND/OR/XOR を 2 入力ゲートの平衡木に分解する
+    // 中間の配線名は fresh() で生成する
+    pub(crate) fn add_tree(
+        &mut self,
+        op: Op,
+        inputs: &[String],
+        output: &str,
+        fresh: &mut impl FnMut() -> String,
+    ) {
+        match inputs.len() {
+            0 => {
+                // 空の AND は 1, 空の OR/XOR は 0
+                let c = if op == Op::And { Op::One } else { Op::Zero };
+                self.add(c, &[], output);
+            }
+            1 => self.add(Op::Buf, &[&inputs[0]], output),
+            2 => self.add(op, &[&inputs[0], &inputs[1]], output),
+            n => {
+                let left = fresh();
+                let right = fresh();
+                self.add_tree(op, &inputs[..n / 2], &left, fresh);
+                self.add_tree(op, &inputs[n / 2..], &right, fresh);
+                self.add(op, &[&left, &right], output);
+            }
+        }
+    }
+
+    pub(crate) fn is_clocked(&self) -> bool {
+        return self.cells.iter().any(|c| c.op.is_clocked());
+    }
+
+    // 配線のドライバが 1 つずつ存在し, ゲートの入力数が正しいか検査する
+    pub(crate) fn check(&self) -> Result<(), String> {
+        let mut drivers: HashSet<&str> = HashSet::new();
+        for net in self.inputs.iter() {
+            if !drivers.insert(net) {
+                return Err(format!("Input `{}` is declared twice!", net));
+            }
+        }
+        for cell in self.cells.iter() {
+            if cell.inputs.len() != cell.op.arity() {
+                return Err(format!(
+                    "{:?} cell driving `{}` has {} inputs, but {} are expected!",
+                    cell.op,
+                    cell.output,
+                    cell.inputs.len(),
+                    cell.op.arity()
+                ));
+            }
+            if !drivers.insert(&cell.output) {
+                return Err(format!("Net `{}` has multiple drivers!", cell.output));
+            }
+        }
+        for net in self
+            .cells
+            .iter()
+            .flat_map(|c| c.inputs.iter())
+            .chain(self.outputs.iter())
+        {
+            if !drivers.contains(net.as_str()) {
+                return Err(format!("Net `{}` is not driven!", net));
+            }
+        }
+        // Buf だけのループは SFQ ゲートにできない
+        let aliases: HashMap<&str, &str> = self
+            .cells
+            .iter()
+            .filter(|c| c.op == Op::Buf)
+            .map(|c| (c.output.as_str(), c.inputs[0].as_str()))
+            .collect();
+        for start in aliases.keys() {
+            let mut net = *start;
+            for _ in 0..=aliases.len() {
+                match aliases.get(net) {
+                    Some(next) => net = next,
+                    None => break,
+                }
+            }
+            if aliases.contains_key(net) {
+                return Err(format!("Net `{}` is a loop of buffers!", start));
+            }
+        }
+        return Ok(());
+    }
+
+    // 出力に届かないセルを取り除く
+    pub(crate) fn sweep(&mut self) {
+        let driver: HashMap<&str, &Cell> =
+            self.cells.iter().map(|c| (c.output.as_str(), c)).collect();
+        let mut used: HashSet<&str> = HashSet::new();
+        let mut stack: Vec<&str> = self.outputs.iter().map(|s| s.as_str()).collect();
+        while let Some(net) = stack.pop() {
+            if !used.insert(net) {
+                continue;
+            }
+            if let Some(cell) = driver.get(net) {
+                stack.extend(cell.inputs.iter().map(|s| s.as_str()));
+            }
+        }
+        let used: HashSet<String> = used.into_iter().map(|s| s.to_string()).collect();
+        self.cells.retain(|c| used.contains(&c.output));
+    }
+
+    // すべての経路のクロック段数がそろうように DFF を挿入する
+    // 同じ配線の遅延は共有する. 定数は毎サイクル同じなので段数を問わない
+    // ループを閉じる配線は, 後から決まる段数を問わずにそのままつなぐ
+    pub(crate) fn balance(&mut self, fresh: &mut impl FnMut() -> String) {
+        let driver: HashMap<&str, usize> = self
+            .cells
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (c.output.as_str(), i))
+            .collect();
+
+        // 出力側から深さ優先でたどり, 入力に近いセルから並べる
+        let mut order: Vec<usize> = Vec::new();
+        let mut visited = vec![false; self.cells.len()];
+        let mut stack: Vec<(usize, bool)> = self
+            .outputs
+            .iter()
+            .filter_map(|net| driver.get(net.as_str()))
+            .map(|i| (*i, false))
+            .collect();
+        while let Some((i, done)) = stack.pop() {
+            if done {
+                order.push(i);
+                continue;
+            }
+            if std::mem::replace(&mut visited[i], true) {
+                continue;
+            }
+            stack.push((i, true));
+            for net in self.cells[i].inputs.iter() {
+                if let Some(j) = driver.get(net.as_str()) {
+                    stack.push((*j, false));
+                }
+            }
+        }
+
+        // 配線の段数 (None は定数だけから決まる配線)
+        // ループを閉じる配線はまだ段数が決まっていないので除いて数える
+        let mut depth: HashMap<String, Option<usize>> = HashMap::new();
+        for net in self.inputs.iter() {
+            depth.insert(net.clone(), Some(0));
+        }
+        for i in order.iter() {
+            let cell = &self.cells[*i];
+            let d = cell
+                .inputs
+                .iter()
+                .filter_map(|net| depth.get(net).copied().flatten())
+                .max()
+                .map(|d| if cell.op.is_clocked() { d + 1 } else { d });
+            depth.insert(cell.output.clone(), d);
+        }
+
+        // (配線, 段数) -> 遅延させた配線
+        let mut delayed: HashMap<(String, usize), String> = HashMap::new();
+        let mut added: Vec<Cell> = Vec::new();
+        let mut delay = |net: &String, to: usize| -> String {
+            let Some(from) = depth[net] else {
+                return net.clone();
+            };
+            let mut res = net.clone();
+            for d in from + 1..=to {
+                res = delayed
+                    .entry((net.clone(), d))
+                    .or_insert_with(|| {
+                        let q = fresh();
+                        added.push(Cell {
+                            op: Op::Dff,
+                            inputs: vec![res.clone()],
+                            output: q.clone(),
+                        });
+                        q
+                    })
+                    .clone();
+            }
+            return res;
+        };
+
+        for i in order {
+            let cell = &self.cells[i];
+            let Some(d) = depth[&cell.output] else {
+                continue;
+            };
+            let to = if cell.op.is_clocked() { d - 1 } else { d };
+            let inputs: Vec<String> = cell.inputs.iter().map(|net| delay(net, to)).collect();
+            self.cells[i].inputs = inputs;
+        }
+        let max = self.outputs.iter().filter_map(|net| depth[net]).max();
+        if let Some(max) = max {
+            self.outputs = self.outputs.iter().map(|net| delay(net, max)).collect();
+        }
+        self.cells.extend(added);
+    }
+
+    // Circuit 上に SFQ ゲートとして構築する
+    // inputs は self.inputs に対応する Wire, 戻り値は self.outputs に対応する Wire
+    // 事前に check() を通っていること. クロックを使う場合 clk は Some であること
+    // label が true なら, 名前の付いた配線に (使える場合) ラベルを付ける
+    pub(crate) fn build<
+        const N_I: usize,
+        const N_CI: usize,
+        const N_O: usize,
+        const N_CO: usize,
+    >(
+        &self,
+        circuit: &mut Circuit<N_I, N_CI, N_O, N_CO>,
+        inputs: Vec<Wire>,
+        clk: Option<Wire>,
+        label: bool,
+    ) -> Vec<Wire> {
+        // Buf を取り除き, 配線名を代表名に置き換える
+        let mut aliases: HashMap<&str, &str> = self
+            .cells
+            .iter()
+            .filter(|c| c.op == Op::Buf)
+            .map(|c| (c.output.as_str(), c.inputs[0].as_str()))
+            .collect();
+        loop {
+            let mut changed = false;
+            let keys: Vec<&str> = aliases.keys().copied().collect();
+            for key in keys {
+                let to = aliases[key];
+                if let Some(next) = aliases.get(to) {
+                    aliases.insert(key, next);
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        let root = |net: &str| -> String {
+            return aliases.get(net).copied().unwrap_or(net).to_string();
+        };
+        let cells: Vec<&Cell> = self.cells.iter().filter(|c| c.op != Op::Buf).collect();
+
+        // 各配線のファンアウト数
+        let mut fanout: HashMap<String, usize> = HashMap::new();
+        for net in cells
+            .iter()
+            .flat_map(|c| c.inputs.iter())
+            .chain(self.outputs.iter())
+        {
+            *fanout.entry(root(net)).or_default() += 1;
+        }
+        let n_clocks = cells.iter().filter(|c| c.op.is_clocked()).count();
+
+        // 配線名 -> 未使用の分岐
+        let mut pool: HashMap<String, Vec<Wire>> = HashMap::new();
+        for (net, wire) in self.inputs.iter().zip(inputs) {
+            let n = fanout.get(net).copied().unwrap_or(0);
+            pool.insert(net.clone(), circuit.split_tree(wire, n));
+        }
+        let mut clocks = match clk {
+            Some(clk) => circuit.split_tree(clk, n_clocks),
+            None => {
+                assert!(n_clocks == 0);
+                Vec::new()
+            }
+        };
+
+        // ゲート出力はループで先に作り, 後でゲートと統合する
+        let mut pending: HashMap<&str, CounterWire> = HashMap::new();
+        for cell in cells.iter() {
+            let (wire, cwire) = if label && !self.outputs.contains(&cell.output) {
+                match sanitize(&cell.output) {
+                    Some(name) if !circuit.has_net(&name) => circuit.gen_loop(&name),
+                    _ => circuit.gen_loop_unnamed(),
+                }
+            } else {
+                circuit.gen_loop_unnamed()
+            };
+            let n = fanout.get(&cell.output).copied().unwrap_or(0);
+            pool.insert(cell.output.clone(), circuit.split_tree(wire, n));
+            pending.insert(&cell.output, cwire);
+        }
+
+        let mut take = |net: &str| -> Wire {
+            return pool.get_mut(&root(net)).unwrap().pop().unwrap();
+        };
+        for cell in cells.iter() {
+            let a: Vec<Wire> = cell.inputs.iter().map(|net| take(net)).collect();
+            let mut a = a.into_iter();
+            let mut arg = || a.next().unwrap();
+            let q = match cell.op {
+                Op::Not => circuit.not(arg(), clocks.pop().unwrap()),
+                Op::And => circuit.and(arg(), arg(), clocks.pop().unwrap()),
+                Op::Or => circuit.or(arg(), arg(), clocks.pop().unwrap()),
+                Op::Xor => circuit.xor(arg(), arg(), clocks.pop().unwrap()),
+                Op::Xnor => circuit.xnor(arg(), arg(), clocks.pop().unwrap()),
+                Op::Dff => circuit.dff(arg(), clocks.pop().unwrap()),
+                Op::Zero => circuit.zero_async(),
+                // 定数 1 はクロックごとにパルスを出す ONE_SYNC
+                Op::One => circuit.one_sync(clocks.pop().unwrap()),
+                Op::Buf => unreachable!(),
+            };
+            circuit.unify(q, pending.remove(cell.output.as_str()).unwrap());
+        }
+
+        return self.outputs.iter().map(|net| take(net)).collect();
+    }
+}
+
+// ラベルとして使える名前に変換する ($ で始まる名前は内部名)
+fn sanitize(name: &str) -> Option<String> {
+    if name.starts_with('$') {
+        return None;
+    }
+    let res: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let res = res.trim_matches('_').to_string();
+    if res.is_empty() || res.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    return Some(res);
+}
@@ -0,0 +1,364 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::gate::Gate;
+use crate::id::WireID;
+
+// 型パラメータを持たない回路の中身
+// Circuit はこれをポート数で型付けしたもの
+#[derive(Debug, Clone)]
+pub struct Netlist {
+    pub(crate) name: String,
+    pub(crate) inputs: Vec<String>,
+    pub(crate) counter_inputs: Vec<String>,
+    pub(crate) outputs: Vec<String>,
+    pub(crate) counter_outputs: Vec<String>,
+
+    pub(crate) wire_names: HashMap<WireID, String>,
+    pub(crate) counter_wires: HashSet<WireID>, // CounterWire として生成された配線
+    pub(crate) gates: Vec<Gate>,
+}
+
+impl Netlist {
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+
+    // 物理的な入力ポート: inputs, counter_outputs の順
+    pub(crate) fn input_ports(&self) -> impl Iterator<Item = &String> {
+        return self.inputs.iter().chain(self.counter_outputs.iter());
+    }
+
+    // 物理的な出力ポート: outputs, counter_inputs の順
+    pub(crate) fn output_ports(&self) -> impl Iterator<Item = &String> {
+        return self.outputs.iter().chain(self.counter_inputs.iter());
+    }
+
+    pub(crate) fn wire_name(&self, wid: &WireID) -> &str {
+        return self.wire_names.get(wid).unwrap().as_str();
+    }
+
+    // DC レベルのポート: DCSFQ が受ける入力ポートと SFQDC が出力する出力ポート
+    pub(crate) fn dc_ports(&self) -> HashSet<&str> {
+        let mut res = HashSet::new();
+        for gate in self.gates.iter() {
+            match gate {
+                Gate::DcSfq { a, .. } => {
+                    let net = self.wire_name(a);
+                    if self.input_ports().any(|p| p == net) {
+                        res.insert(net);
+                    }
+                }
+                Gate::SfqDc { q, .. } => {
+                    let net = self.wire_name(q);
+                    if self.output_ports().any(|p| p == net) {
+                        res.insert(net);
+                    }
+                }
+                _ => {}
+            }
+        }
+        return res;
+    }
+
+    // 新しく付けるゲート名の番号. ゲート名末尾の番号と重ならないようにする
+    pub(crate) fn next_gate_id(&self) -> u32 {
+        return self
+            .gates
+            .iter()
+            .filter_map(|g| {
+                let name = g.name();
+                let digits = name.len() - name.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+                name[name.len() - digits..].parse::<u32>().ok()
+            })
+            .max()
+            .unwrap_or(0)
+            + 1;
+    }
+
+    // 各配線がちょうど 1 つのドライバと 1 つのレシーバを持つか検査する
+    pub(crate) fn check(&self) -> Result<(), String> {
+        // 配線名 -> (ドライバ数, レシーバ数)
+        let mut counts: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+        for port in self.input_ports() {
+            counts.entry(port).or_default().0 += 1;
+        }
+        for port in self.output_ports() {
+            counts.entry(port).or_default().1 += 1;
+        }
+        for gate in self.gates.iter() {
+            if let Gate::Subcircuit {
+                name,
+                inputs,
+                outputs,
+                circuit,
+            } = gate
+                && (inputs.len() != circuit.input_ports().count()
+                    || outputs.len() != circuit.output_ports().count())
+            {
+                return Err(format!(
+                    "Ports of `{}` do not match circuit `{}`!",
+                    name, circuit.name
+                ));
+            }
+            for (_, wid) in gate.outputs() {
+                counts.entry(self.wire_name(&wid)).or_default().0 += 1;
+            }
+            for (_, wid) in gate.inputs() {
+                counts.entry(self.wire_name(&wid)).or_default().1 += 1;
+            }
+        }
+        for (net, (drivers, receivers)) in counts {
+            if drivers != 1 || receivers != 1 {
+                return Err(format!(
+                    "Fan-in or Fan-out of `{}` is invalid: drivers: {}, receivers: {}!",
+                    net, drivers, receivers
+                ));
+            }
+        }
+        return Ok(());
+    }
+
+    // サブサーキットを再帰的に展開した回路
+    // 展開したゲートと配線の名前は `XFA3/XAND4` のように階層を含む
+    pub fn flatten(&self) -> Netlist {
+        let mut res = Netlist {
+            name: self.name.clone(),
+            inputs: self.inputs.clone(),
+            counter_inputs: self.counter_inputs.clone(),
+            outputs: self.outputs.clone(),
+            counter_outputs: self.counter_outputs.clone(),
+            wire_names: HashMap::new(),
+            counter_wires: HashSet::new(),
+            gates: Vec::new(),
+        };
+        let mut ids = HashMap::new();
+        inline(self, "", &HashMap::new(), &mut res, &mut ids);
+        return res;
+    }
+
+    // 入力ポートから出力ポートまでに通るクロック付きゲートの最大段数
+    // クロックピンへの経路は数えない. ループを閉じる配線はそこで 0 段とする
+    pub(crate) fn latency(&self) -> usize {
+        let flat = self.flatten();
+        let mut driver: HashMap<&str, &Gate> = HashMap::new();
+        for gate in flat.gates.iter() {
+            for (_, wid) in gate.outputs() {
+                driver.insert(flat.wire_name(&wid), gate);
+            }
+        }
+        fn depth<'a>(
+            c: &'a Netlist,
+            net: &'a str,
+            driver: &HashMap<&'a str, &'a Gate>,
+            memo: &mut HashMap<&'a str, usize>,
+            on_stack: &mut HashSet<&'a str>,
+        ) -> usize {
+            if let Some(d) = memo.get(net) {
+                return *d;
+            }
+            if !on_stack.insert(net) {
+                return 0;
+            }
+            let d = match driver.get(net) {
+                None => 0,
+                Some(gate) => {
+                    let clocked = gate.inputs().iter().any(|(pin, _)| *pin == "clk");
+                    let d = gate
+                        .inputs()
+                        .iter()
+                        .filter(|(pin, _)| *pin != "clk")
+                        .map(|(_, wid)| depth(c, c.wire_name(wid), driver, memo, on_stack))
+                        .max()
+                        .unwrap_or(0);
+                    if clocked { d + 1 } else { d }
+                }
+            };
+            on_stack.remove(net);
+            memo.insert(net, d);
+            return d;
+        }
+        let mut memo = HashMap::new();
+        let mut on_stack = HashSet::new();
+        return flat
+            .output_ports()
+            .map(|port| depth(&flat, port, &driver, &mut memo, &mut on_stack))
+            .max()
+            .unwrap_or(0);
+    }
+
+    // 名前, ポート, ゲートと接続 (配線名) が同じか
+    // サブサーキットは回路名のみ比較する
+    pub(crate) fn same_as(&self, other: &Netlist) -> bool {
+        if self.name != other.name
+            || self.inputs != other.inputs
+            || self.counter_inputs != other.counter_inputs
+            || self.outputs != other.outputs
+            || self.counter_outputs != other.counter_outputs
+            || self.gates.len() != other.gates.len()
+        {
+            return false;
+        }
+        let pins = |c: &Netlist, gate: &Gate| -> Vec<String> {
+            return gate
+                .inputs()
+                .into_iter()
+                .chain(gate.outputs())
+                .map(|(pin, wid)| format!("{}={}", pin, c.wire_name(&wid)))
+                .collect();
+        };
+        return self.gates.iter().zip(other.gates.iter()).all(|(g1, g2)| {
+            g1.name() == g2.name() && g1.kind() == g2.kind() && pins(self, g1) == pins(other, g2)
+        });
+    }
+
+    // 配線名が CounterWire 由来かどうか
+    pub(crate) fn is_counter_net(&self, net: &str) -> bool {
+        return self
+            .counter_wires
+            .iter()
+            .any(|wid| self.wire_name(wid) == net);
+    }
+
+    // クロックピンにのみ (SPLIT, JTL, BUFF を介して) つながる配線
+    pub(crate) fn clock_nets(&self) -> HashSet<&str> {
+        let mut receivers: HashMap<&str, Vec<(&Gate, &str)>> = HashMap::new();
+        for gate in self.gates.iter() {
+            for (pin, wid) in gate.inputs() {
+                receivers
+                    .entry(self.wire_name(&wid))
+                    .or_default()
+                    .push((gate, pin));
+            }
+        }
+
+        let mut clocks: HashSet<&str> = HashSet::new();
+        loop {
+            let mut changed = false;
+            for (net, gates) in receivers.iter() {
+                if clocks.contains(net) {
+                    continue;
+                }
+                let is_clock = gates.iter().all(|(gate, pin)| match gate {
+                    Gate::Split { .. }
+                    | Gate::Split3 { .. }
+                    | Gate::Split4 { .. }
+                    | Gate::Jtl { .. }
+                    | Gate::Buff { .. }
+                    | Gate::DcSfq { .. } => gate
+                        .outputs()
+                        .iter()
+                        .all(|(_, wid)| clocks.contains(self.wire_name(wid))),
+                    _ => *pin == "clk",
+                });
+                if is_clock {
+                    clocks.insert(net);
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        return clocks;
+    }
+}
+
+// 階層名: ラベルのない配線 (_ で始まる) はラベルのないまま
+fn hier_name(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        return name.to_string();
+    }
+    return match name.strip_prefix('_') {
+        Some(rest) => format!("_{}/{}", prefix, rest),
+        None => format!("{}/{}", prefix, name),
+    };
+}
+
+// 配線名ごとに 1 つの WireID を割り当てる
+fn intern(name: String, res: &mut Netlist, ids: &mut HashMap<String, WireID>) -> WireID {
+    if let Some(wid) = ids.get(&name) {
+        return *wid;
+    }
+    let wid = WireID(ids.len() as u32 + 1);
+    ids.insert(name.clone(), wid);
+    res.wire_names.insert(wid, name);
+    return wid;
+}
+
+// c のゲートを res に展開する. rename はポートの配線名から親の配線名への対応
+fn inline(
+    c: &Netlist,
+    prefix: &str,
+    rename: &HashMap<&str, String>,
+    res: &mut Netlist,
+    ids: &mut HashMap<String, WireID>,
+) {
+    let net = |wid: &WireID| -> String {
+        let name = c.wire_name(wid);
+        return match rename.get(name) {
+            Some(parent) => parent.clone(),
+            None => hier_name(prefix, name),
+        };
+    };
+
+    // ポートの配線は先に作る
+    if prefix.is_empty() {
+        for port in c.input_ports().chain(c.output_ports()) {
+            intern(port.clone(), res, ids);
+        }
+    }
+    for wid in c.counter_wires.iter() {
+        let cw = intern(net(wid), res, ids);
+        res.counter_wires.insert(cw);
+    }
+
+    for gate in c.gates.iter() {
+        let gate_name = hier_name(prefix, gate.name());
+        match gate {
+            Gate::Subcircuit {
+                inputs,
+                outputs,
+                circuit,
+                ..
+            } => {
+                let child_rename: HashMap<&str, String> = circuit
+                    .input_ports()
+                    .chain(circuit.output_ports())
+                    .zip(inputs.iter().chain(outputs.iter()))
+                    .map(|(port, wid)| (port.as_str(), net(wid)))
+                    .collect();
+                inline(circuit, &gate_name, &child_rename, res, ids);
+            }
+            _ => {
+                let pins: Vec<WireID> = gate
+                    .inputs()
+                    .into_iter()
+                    .chain(gate.outputs())
+                    .map(|(_, wid)| intern(net(&wid), res, ids))
+                    .collect();
+                res.gates
+                    .push(Gate::from_pins(gate.kind(), gate_name, &pins).unwrap());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::{Clocking, Sequential};
+
+    #[test]
+    fn latency_of_pipeline() {
+        let sr = Sequential::shift_register(3, Clocking::Concurrent);
+        assert_eq!(sr.netlist.latency(), 3);
+    }
+
+    // ループを閉じる配線で止まること
+    #[test]
+    fn latency_with_feedback() {
+        let counter = Sequential::ripple_counter::<3>(3);
+        let fifo = Sequential::fifo::<4, 2>(2, 2);
+        assert_eq!(counter.netlist.latency(), 3);
+        assert_eq!(fifo.netlist.latency(), 4);
+    }
+}
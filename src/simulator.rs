@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+
+use crate::gate::Gate;
+use crate::netlist::Netlist;
+
+// 配線のパルスを受け取る先
+#[derive(Debug, Clone, Copy)]
+enum Receiver {
+    Pin(usize, usize), // (ゲート番号, 入力ピン番号)
+    Port(usize),       // 物理的な出力ポート番号
+    None,
+}
+
+// サイクル単位の論理シミュレータ
+// 各サイクルでクロックを受けたゲートは前のサイクルまでに受けた入力から出力し,
+// 同じサイクルに届いたパルスは次のクロックで読まれる
+// 1 サイクル内の同じ配線へのパルスは 1 つにまとめる
+#[derive(Debug, Clone)]
+pub(crate) struct Simulator {
+    kinds: Vec<String>,
+    outputs: Vec<Vec<usize>>, // ゲートごとの出力配線
+    receivers: Vec<Receiver>, // 配線ごと
+    inputs: Vec<usize>,       // 物理的な入力ポートの配線
+    n_outputs: usize,
+    pub(crate) state: Vec<[bool; 2]>, // ゲートごとの (a, b) の保持状態
+}
+
+impl Simulator {
+    // サブサーキットを含まず, check() を通ったネットリストから作る
+    // 到着時刻で動くゲートを含むときはその説明を返す
+    pub(crate) fn new<'a>(netlist: &'a Netlist) -> Result<Self, String> {
+        let mut nets: HashMap<&str, usize> = HashMap::new();
+        let mut net = |name: &'a str| -> usize {
+            let n = nets.len();
+            return *nets.entry(name).or_insert(n);
+        };
+        let inputs: Vec<usize> = netlist.input_ports().map(|p| net(p)).collect();
+        let ports: Vec<usize> = netlist.output_ports().map(|p| net(p)).collect();
+
+        let mut kinds = Vec::new();
+        let mut outputs = Vec::new();
+        let mut pins = Vec::new();
+        for (g, gate) in netlist.gates.iter().enumerate() {
+            assert!(!matches!(gate, Gate::Subcircuit { .. }));
+            if matches!(
+                gate,
+                Gate::FirstArrival { .. } | Gate::LastArrival { .. } | Gate::Inhibit { .. }
+            ) {
+                return Err(format!(
+                    "`{}` works on arrival times and cannot be simulated cycle by cycle!",
+                    gate.kind()
+                ));
+            }
+            kinds.push(gate.kind().to_string());
+            outputs.push(
+                gate.outputs()
+                    .iter()
+                    .map(|(_, wid)| net(netlist.wire_name(wid)))
+                    .collect(),
+            );
+            for (i, (_, wid)) in gate.inputs().iter().enumerate() {
+                pins.push((net(netlist.wire_name(wid)), Receiver::Pin(g, i)));
+            }
+        }
+
+        let mut receivers = vec![Receiver::None; nets.len()];
+        for (i, n) in ports.iter().enumerate() {
+            receivers[*n] = Receiver::Port(i);
+        }
+        for (n, r) in pins {
+            receivers[n] = r;
+        }
+        return Ok(Self {
+            state: vec![[false; 2]; kinds.len()],
+            kinds,
+            outputs,
+            receivers,
+            inputs,
+            n_outputs: ports.len(),
+        });
+    }
+
+    // SPLIT (SPLIT3, SPLIT4), JTL, BUFF, DCSFQ を介してクロックピンにつながる入力ポート
+    pub(crate) fn clock_ports(&self) -> Vec<bool> {
+        return self.inputs.iter().map(|n| self.reaches_clock(*n)).collect();
+    }
+
+    fn reaches_clock(&self, net: usize) -> bool {
+        // 非同期ゲートのループに備えて訪れた配線を記録する
+        let mut visited = vec![false; self.receivers.len()];
+        let mut stack = vec![net];
+        while let Some(n) = stack.pop() {
+            if std::mem::replace(&mut visited[n], true) {
+                continue;
+            }
+            if let Receiver::Pin(g, pin) = self.receivers[n] {
+                match self.kinds[g].as_str() {
+                    "SPLIT" | "SPLIT3" | "SPLIT4" | "JTL" | "BUFF" | "DCSFQ" => {
+                        stack.extend(self.outputs[g].iter().copied())
+                    }
+                    kind if is_clocked(kind) && pin == clock_pin(kind) => return true,
+                    _ => {}
+                }
+            }
+        }
+        return false;
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.state.iter_mut().for_each(|s| *s = [false; 2]);
+    }
+
+    // 物理的な入力ポートにパルスを入れて 1 サイクル進め, 出力ポートのパルスを返す
+    pub(crate) fn step(&mut self, inputs: &[bool]) -> Vec<bool> {
+        let mut pulsed = vec![false; self.receivers.len()];
+        let mut fired = vec![false; self.kinds.len()];
+        let mut arrived = vec![[false; 2]; self.kinds.len()];
+        let mut res = vec![false; self.n_outputs];
+
+        let mut queue: Vec<usize> = self
+            .inputs
+            .iter()
+            .zip(inputs)
+            .filter(|(_, p)| **p)
+            .map(|(n, _)| *n)
+            .collect();
+        while let Some(net) = queue.pop() {
+            if pulsed[net] {
+                continue;
+            }
+            pulsed[net] = true;
+            let (g, pin) = match self.receivers[net] {
+                Receiver::Pin(g, pin) => (g, pin),
+                Receiver::Port(i) => {
+                    res[i] = true;
+                    continue;
+                }
+                Receiver::None => continue,
+            };
+            let kind = self.kinds[g].as_str();
+            if is_toggle(kind) {
+                // r はサイクルの終わりに反映する
+                if pin == 1 {
+                    arrived[g][1] = true;
+                    continue;
+                }
+                // パルスごとに反転し, 1 から 0 に戻るときに出力する (TFF2 は 0 から 1 で q1 にも)
+                let s = &mut self.state[g][0];
+                *s = !*s;
+                if !*s {
+                    queue.push(*self.outputs[g].last().unwrap());
+                } else if kind == "TFF2" {
+                    queue.push(self.outputs[g][0]);
+                }
+                continue;
+            }
+            if !is_clocked(kind) {
+                // 非同期ゲートはそのまま出力へ伝える
+                if kind != "TERMINATE" {
+                    queue.extend(self.outputs[g].iter().copied());
+                }
+                continue;
+            }
+            if pin != clock_pin(kind) {
+                arrived[g][pin] = true;
+                continue;
+            }
+            if fired[g] {
+                continue;
+            }
+            fired[g] = true;
+            let [sa, sb] = self.state[g];
+            let q = match kind {
+                "AND" => sa && sb,
+                "OR" => sa || sb,
+                "XOR" => sa ^ sb,
+                "XNOR" => !(sa ^ sb),
+                "NOT" => !sa,
+                "DFF" | "DFFR" | "DFFC" => sa,
+                "DFFR_SYNC" => sa && !sb,
+                "NDRO" | "NDROC" => sa,
+                "ZERO_SYNC" => false,
+                "ONE_SYNC" => true,
+                _ => unreachable!(),
+            };
+            // NDRO は読み出しで状態を失わない
+            if !keeps_state(kind) {
+                self.state[g] = [false; 2];
+            }
+            if q {
+                queue.push(self.outputs[g][0]);
+            } else if matches!(kind, "DFFC" | "NDROC") {
+                // 相補出力は状態が 0 なら q2 に出す
+                queue.push(self.outputs[g][1]);
+            }
+        }
+
+        for (g, [a, b]) in arrived.into_iter().enumerate() {
+            let s = &mut self.state[g];
+            if self.kinds[g] == "TFFR" {
+                // 同じサイクルの反転の後に 0 に戻す
+                if b {
+                    s[0] = false;
+                }
+            } else if matches!(self.kinds[g].as_str(), "NDRO" | "NDROC" | "DFFR") {
+                // 同じサイクルにセットとリセットが届いたらリセットを優先する
+                if a {
+                    s[0] = true;
+                }
+                if b {
+                    s[0] = false;
+                }
+            } else {
+                s[0] |= a;
+                s[1] |= b;
+            }
+        }
+        return res;
+    }
+}
+
+fn is_toggle(kind: &str) -> bool {
+    return matches!(kind, "TFF" | "TFF2" | "TFFR");
+}
+
+fn is_clocked(kind: &str) -> bool {
+    return matches!(
+        kind,
+        "AND"
+            | "OR"
+            | "XOR"
+            | "XNOR"
+            | "NOT"
+            | "DFF"
+            | "NDRO"
+            | "DFFR"
+            | "DFFR_SYNC"
+            | "DFFC"
+            | "NDROC"
+            | "ZERO_SYNC"
+            | "ONE_SYNC"
+    );
+}
+
+fn keeps_state(kind: &str) -> bool {
+    return matches!(kind, "NDRO" | "NDROC");
+}
+
+// クロックピンの番号 (pin_names() の入力ピンの最後)
+fn clock_pin(kind: &str) -> usize {
+    let (inputs, _) = Gate::pin_names(kind).unwrap();
+    return inputs.len() - 1;
+}
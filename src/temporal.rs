@@ -0,0 +1,243 @@
+use colored::Colorize;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::circuit::Circuit;
+use crate::netlist::Netlist;
+
+// パルスの到着時刻で動くイベント駆動のシミュレータ
+// ゲートの遅延は種類ごとに決める (既定はすべて 1). 時刻の単位は任意
+#[derive(Debug, Clone)]
+pub struct TemporalSimulator {
+    delays: HashMap<String, f64>,
+    default_delay: f64,
+    stop: f64,
+}
+
+impl Default for TemporalSimulator {
+    fn default() -> Self {
+        Self {
+            delays: HashMap::new(),
+            default_delay: 1.0,
+            stop: f64::INFINITY,
+        }
+    }
+}
+
+impl TemporalSimulator {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    // kind ("JTL", "FIRST_ARRIVAL", ...) のゲートの遅延
+    pub fn delay(mut self, kind: &str, delay: f64) -> Self {
+        self.delays.insert(kind.to_string(), delay);
+        return self;
+    }
+
+    // delay() で指定していないゲートの遅延
+    pub fn default_delay(mut self, delay: f64) -> Self {
+        self.default_delay = delay;
+        return self;
+    }
+
+    // この時刻より後のパルスは捨てる. 非同期のループがある回路で使う
+    pub fn stop(mut self, time: f64) -> Self {
+        self.stop = time;
+        return self;
+    }
+
+    // inputs[i] は i 番目の入力ポートにパルスが届く時刻の列
+    // 出力ポートごとにパルスの出た時刻の列を返す
+    pub fn run<const N_I: usize, const N_CI: usize, const N_O: usize, const N_CO: usize>(
+        &self,
+        circuit: &Circuit<N_I, N_CI, N_O, N_CO>,
+        inputs: &[&[f64]],
+    ) -> Vec<Vec<f64>> {
+        return self.run_netlist(&circuit.netlist, inputs);
+    }
+
+    pub fn run_netlist(&self, netlist: &Netlist, inputs: &[&[f64]]) -> Vec<Vec<f64>> {
+        let netlist = netlist.flatten();
+        assert!(
+            inputs.len() == netlist.input_ports().count(),
+            "{}",
+            format!(
+                "`{}` has {} input ports, but {} pulse trains are given!",
+                netlist.name,
+                netlist.input_ports().count(),
+                inputs.len()
+            )
+            .red()
+        );
+
+        // 配線名 -> (ゲート番号, 入力ピン番号) または出力ポート番号
+        let mut pins: HashMap<&str, (usize, usize)> = HashMap::new();
+        for (g, gate) in netlist.gates.iter().enumerate() {
+            for (i, (_, wid)) in gate.inputs().iter().enumerate() {
+                pins.insert(netlist.wire_name(wid), (g, i));
+            }
+        }
+        let ports: HashMap<&str, usize> = netlist
+            .output_ports()
+            .enumerate()
+            .map(|(i, p)| (p.as_str(), i))
+            .collect();
+
+        let mut queue = BinaryHeap::new();
+        let mut seq = 0;
+        let mut push = |queue: &mut BinaryHeap<Event>, time: f64, net: String| {
+            if time <= self.stop {
+                seq += 1;
+                queue.push(Event { time, seq, net });
+            }
+        };
+        for (port, times) in netlist.input_ports().zip(inputs) {
+            for t in times.iter() {
+                push(&mut queue, *t, port.clone());
+            }
+        }
+
+        let mut state = vec![[false; 2]; netlist.gates.len()];
+        let mut res = vec![Vec::new(); ports.len()];
+        while let Some(Event { time, net, .. }) = queue.pop() {
+            if let Some(i) = ports.get(net.as_str()) {
+                res[*i].push(time);
+                continue;
+            }
+            let Some(&(g, pin)) = pins.get(net.as_str()) else {
+                continue;
+            };
+            let gate = &netlist.gates[g];
+            let kind = gate.kind();
+            let delay = self.delays.get(kind).copied().unwrap_or(self.default_delay);
+            let s = &mut state[g];
+            let fire = match kind {
+                "JTL" | "BUFF" | "SPLIT" | "MERGE" | "SPLIT3" | "SPLIT4" | "MERGE3" | "MERGE4" => {
+                    true
+                }
+                // DC レベルの変化もパルスとして扱う
+                "DCSFQ" | "SFQDC" => true,
+                "TERMINATE" => false,
+                // パルスごとに反転し, 1 から 0 に戻るときに出力する
+                "TFF" => {
+                    s[0] = !s[0];
+                    !s[0]
+                }
+                // 0 から 1 になるときに q1, 1 から 0 に戻るときに q2 に出力する
+                "TFF2" => {
+                    s[0] = !s[0];
+                    let q = gate.outputs()[usize::from(!s[0])].1;
+                    push(&mut queue, time + delay, netlist.wire_name(&q).to_string());
+                    false
+                }
+                // r のパルスは出力せずに 0 に戻す
+                "TFFR" => {
+                    if pin == 0 {
+                        s[0] = !s[0];
+                        !s[0]
+                    } else {
+                        s[0] = false;
+                        false
+                    }
+                }
+                "AND" | "OR" | "XOR" | "XNOR" | "NOT" | "DFF" | "NDRO" | "DFFR" | "DFFR_SYNC"
+                | "DFFC" | "NDROC" | "ZERO_SYNC" | "ONE_SYNC" => {
+                    let n_inputs = gate.inputs().len();
+                    if pin + 1 < n_inputs {
+                        // NDRO の a はセット, b はリセット. DFFR の r は保持したデータを消す
+                        if matches!(kind, "NDRO" | "NDROC" | "DFFR") {
+                            s[0] = pin == 0;
+                        } else {
+                            s[pin] = true;
+                        }
+                        false
+                    } else {
+                        let [sa, sb] = *s;
+                        if !matches!(kind, "NDRO" | "NDROC") {
+                            *s = [false; 2];
+                        }
+                        let q = match kind {
+                            "AND" => sa && sb,
+                            "OR" => sa || sb,
+                            "XOR" => sa ^ sb,
+                            "XNOR" => !(sa ^ sb),
+                            "NOT" => !sa,
+                            "DFFR_SYNC" => sa && !sb,
+                            "ZERO_SYNC" => false,
+                            "ONE_SYNC" => true,
+                            _ => sa,
+                        };
+                        if matches!(kind, "DFFC" | "NDROC") {
+                            // 相補出力: 状態が 1 なら q1, 0 なら q2
+                            let q = gate.outputs()[usize::from(!q)].1;
+                            push(&mut queue, time + delay, netlist.wire_name(&q).to_string());
+                            false
+                        } else {
+                            q
+                        }
+                    }
+                }
+                // 両方の入力が届いたら次の到着に備えて状態を戻す
+                "FIRST_ARRIVAL" | "LAST_ARRIVAL" | "INHIBIT" => {
+                    let other = s[1 - pin];
+                    let fire = match kind {
+                        "FIRST_ARRIVAL" => !s[0] && !s[1],
+                        "LAST_ARRIVAL" => other,
+                        _ => pin == 0 && !other,
+                    };
+                    if other {
+                        *s = [false; 2];
+                    } else {
+                        s[pin] = true;
+                    }
+                    fire
+                }
+                _ => panic!(
+                    "{}",
+                    format!("`{}` is not supported by TemporalSimulator!", kind).red()
+                ),
+            };
+            if fire {
+                for (_, wid) in gate.outputs() {
+                    push(
+                        &mut queue,
+                        time + delay,
+                        netlist.wire_name(&wid).to_string(),
+                    );
+                }
+            }
+        }
+        return res;
+    }
+}
+
+// 時刻の早い順 (同時刻なら発生順) に取り出すイベント
+struct Event {
+    time: f64,
+    seq: u64,
+    net: String,
+}
+
+impl Ord for Event {
+    fn cmp(&self, other: &Self) -> Ordering {
+        return other
+            .time
+            .total_cmp(&self.time)
+            .then(other.seq.cmp(&self.seq));
+    }
+}
+
+impl PartialOrd for Event {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+impl PartialEq for Event {
+    fn eq(&self, other: &Self) -> bool {
+        return self.cmp(other) == Ordering::Equal;
+    }
+}
+
+impl Eq for Event {}
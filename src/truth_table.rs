@@ -0,0 +1,451 @@
+use colored::Colorize;
+use std::collections::{BTreeSet, HashSet};
+
+use crate::error::ParseError;
+use crate::expr::{Expr, Lowering};
+use crate::logic::LogicNetwork;
+
+// 多出力の論理関数を真理値表 (キューブの並び) で表したもの
+// 入力は 0, 1, - (どちらでもよい), 出力は 1 (オンセット), - (ドントケア), 0 (指定なし)
+// どの行にも 1 と指定されない入力の組では出力は 0 になる (PLA の fd 形式)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TruthTable {
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    rows: Vec<(String, String)>,
+}
+
+// 全探索する入力数の上限
+const MAX_INPUTS: usize = 16;
+
+// (値, どちらでもよいビット) の積項. ビット i は i 番目の入力
+type Cube = (u32, u32);
+
+impl TruthTable {
+    pub fn new(inputs: &[&str], outputs: &[&str]) -> Self {
+        assert!(
+            inputs.len() <= MAX_INPUTS,
+            "{}",
+            format!("Truth table has more than {} inputs!", MAX_INPUTS).red()
+        );
+        return Self {
+            inputs: inputs.iter().map(|s| s.to_string()).collect(),
+            outputs: outputs.iter().map(|s| s.to_string()).collect(),
+            rows: Vec::new(),
+        };
+    }
+
+    // 行を追加する. 例: row("1-0", "10")
+    pub fn row(mut self, inputs: &str, outputs: &str) -> Self {
+        if let Err(e) = self.check_row(inputs, outputs) {
+            panic!("{}", e.red());
+        }
+        self.rows.push((inputs.to_string(), outputs.to_string()));
+        return self;
+    }
+
+    fn check_row(&self, inputs: &str, outputs: &str) -> Result<(), String> {
+        if inputs.len() != self.inputs.len() || outputs.len() != self.outputs.len() {
+            return Err(format!(
+                "Row `{} {}` must have {} inputs and {} outputs!",
+                inputs,
+                outputs,
+                self.inputs.len(),
+                self.outputs.len()
+            ));
+        }
+        if !inputs.chars().chain(outputs.chars()).all(|c| "01-".contains(c)) {
+            return Err(format!(
+                "Row `{} {}` may only contain `0`, `1` and `-`!",
+                inputs, outputs
+            ));
+        }
+        return Ok(());
+    }
+
+    // espresso の PLA 形式 (.i, .o, .ilb, .ob, .p, .type fd, .e) を読み込む
+    // .ilb, .ob がなければ x0, x1, ... と f0, f1, ... とする
+    pub fn parse_pla(src: &str) -> Result<Self, ParseError> {
+        let mut n_inputs: Option<usize> = None;
+        let mut n_outputs: Option<usize> = None;
+        let mut input_names: Option<Vec<String>> = None;
+        let mut output_names: Option<Vec<String>> = None;
+        let mut table: Option<TruthTable> = None;
+
+        for (i, raw) in src.lines().enumerate() {
+            let line = i + 1;
+            let text = raw.split('#').next().unwrap().trim();
+            if text.is_empty() {
+                continue;
+            }
+            let tokens: Vec<&str> = text.split_whitespace().collect();
+            let count = || -> Result<usize, ParseError> {
+                return tokens
+                    .get(1)
+                    .and_then(|s| s.parse().ok())
+                    .ok_or_else(|| ParseError::at(line, format!("Invalid line `{}`!", text)));
+            };
+            let header = || ParseError::at(line, format!("`{}` must precede the rows!", tokens[0]));
+            match tokens[0] {
+                ".i" if table.is_none() => n_inputs = Some(count()?),
+                ".o" if table.is_none() => n_outputs = Some(count()?),
+                ".ilb" if table.is_none() => {
+                    input_names = Some(tokens[1..].iter().map(|s| s.to_string()).collect())
+                }
+                ".ob" if table.is_none() => {
+                    output_names = Some(tokens[1..].iter().map(|s| s.to_string()).collect())
+                }
+                ".i" | ".o" | ".ilb" | ".ob" => return Err(header()),
+                ".p" => {}
+                ".type" if tokens.get(1) == Some(&"fd") => {}
+                ".e" | ".end" => break,
+                t if t.starts_with('.') => {
+                    return Err(ParseError::at(
+                        line,
+                        format!("`{}` is not supported!", text),
+                    ));
+                }
+                _ => {
+                    if table.is_none() {
+                        let (Some(ni), Some(no)) = (n_inputs, n_outputs) else {
+                            return Err(ParseError::at(line, "`.i` and `.o` are missing!"));
+                        };
+                        let inputs = input_names
+                            .take()
+                            .unwrap_or_else(|| (0..ni).map(|i| format!("x{}", i)).collect());
+                        let outputs = output_names
+                            .take()
+                            .unwrap_or_else(|| (0..no).map(|i| format!("f{}", i)).collect());
+                        if inputs.len() != ni || outputs.len() != no {
+                            return Err(ParseError::at(
+                                line,
+                                "Numbers of names do not match `.i` and `.o`!",
+                            ));
+                        }
+                        if ni > MAX_INPUTS {
+                            return Err(ParseError::at(
+                                line,
+                                format!("Truth table has more than {} inputs!", MAX_INPUTS),
+                            ));
+                        }
+                        let inputs: Vec<&str> = inputs.iter().map(|s| s.as_str()).collect();
+                        let outputs: Vec<&str> = outputs.iter().map(|s| s.as_str()).collect();
+                        table = Some(TruthTable::new(&inputs, &outputs));
+                    }
+                    let table = table.as_mut().unwrap();
+                    // 入力部と出力部の間の空白は省略できる
+                    let row: String = tokens.concat();
+                    let split = table.inputs.len().min(row.len());
+                    let (ins, outs) = row.split_at(split);
+                    table.check_row(ins, outs).map_err(|e| ParseError::at(line, e))?;
+                    table.rows.push((ins.to_string(), outs.to_string()));
+                }
+            }
+        }
+        return match table {
+            Some(table) => Ok(table),
+            None => match (n_inputs, n_outputs) {
+                // 行がなければ出力はすべて 0
+                (Some(ni), Some(no)) => {
+                    let inputs = input_names
+                        .unwrap_or_else(|| (0..ni).map(|i| format!("x{}", i)).collect());
+                    let outputs = output_names
+                        .unwrap_or_else(|| (0..no).map(|i| format!("f{}", i)).collect());
+                    let inputs: Vec<&str> = inputs.iter().map(|s| s.as_str()).collect();
+                    let outputs: Vec<&str> = outputs.iter().map(|s| s.as_str()).collect();
+                    Ok(TruthTable::new(&inputs, &outputs))
+                }
+                _ => Err(ParseError::new("`.i` and `.o` are missing!")),
+            },
+        };
+    }
+
+    pub fn inputs(&self) -> Vec<&str> {
+        return self.inputs.iter().map(|s| s.as_str()).collect();
+    }
+
+    pub fn outputs(&self) -> Vec<&str> {
+        return self.outputs.iter().map(|s| s.as_str()).collect();
+    }
+
+    // 出力 k の (オンセット, ドントケア) の最小項
+    fn minterms(&self, k: usize) -> (BTreeSet<u32>, BTreeSet<u32>) {
+        let mut on = BTreeSet::new();
+        let mut dc = BTreeSet::new();
+        for (ins, outs) in self.rows.iter() {
+            let set = match outs.as_bytes()[k] {
+                b'1' => &mut on,
+                b'-' => &mut dc,
+                _ => continue,
+            };
+            let (value, free) = cube(ins);
+            // free の部分集合をすべて列挙する
+            let mut sub = free;
+            loop {
+                set.insert(value | sub);
+                if sub == 0 {
+                    break;
+                }
+                sub = (sub - 1) & free;
+            }
+        }
+        let dc = dc.difference(&on).copied().collect();
+        return (on, dc);
+    }
+
+    // 出力ごとに最小化した論理式
+    // 積和形, 否定の積和形, XOR の和 (Reed-Muller 展開) のうちゲート数の少ないものを選ぶ
+    pub fn exprs(&self) -> Vec<Expr> {
+        let n = self.inputs.len();
+        let names = self.inputs();
+        return (0..self.outputs.len())
+            .map(|k| {
+                let (on, dc) = self.minterms(k);
+                let off: BTreeSet<u32> = (0..1u32 << n)
+                    .filter(|m| !on.contains(m) && !dc.contains(m))
+                    .collect();
+                let candidates = [
+                    self.sop(&on, &dc),
+                    !self.sop(&off, &dc),
+                    self.reed_muller(&on),
+                ];
+                candidates
+                    .into_iter()
+                    .min_by_key(|e| cost(e, &names))
+                    .unwrap()
+            })
+            .collect();
+    }
+
+    // Quine-McCluskey 法で主項を求め, 貪欲法で被覆する
+    fn sop(&self, on: &BTreeSet<u32>, dc: &BTreeSet<u32>) -> Expr {
+        let n = self.inputs.len();
+        let minterms: Vec<u32> = on.union(dc).copied().collect();
+        let primes = primes(n, &minterms);
+        let covers = |(value, free): Cube, m: u32| m & !free == value;
+
+        let mut chosen: Vec<Cube> = Vec::new();
+        let mut uncovered: BTreeSet<u32> = on.clone();
+        // 1 つの主項でしか覆えない最小項があれば, その主項は必須
+        for m in on.iter() {
+            let covering: Vec<Cube> = primes.iter().copied().filter(|p| covers(*p, *m)).collect();
+            if covering.len() == 1 && !chosen.contains(&covering[0]) {
+                chosen.push(covering[0]);
+            }
+        }
+        uncovered.retain(|m| !chosen.iter().any(|p| covers(*p, *m)));
+        while !uncovered.is_empty() {
+            // 多く覆うもの, 次にリテラルの少ないものを選ぶ
+            let best = primes
+                .iter()
+                .copied()
+                .max_by_key(|p| {
+                    let n_covered = uncovered.iter().filter(|m| covers(*p, **m)).count();
+                    (n_covered, p.1.count_ones(), std::cmp::Reverse(*p))
+                })
+                .unwrap();
+            chosen.push(best);
+            uncovered.retain(|m| !covers(best, *m));
+        }
+        chosen.sort();
+
+        let terms: Vec<Expr> = chosen
+            .into_iter()
+            .map(|(value, free)| {
+                let literals: Vec<Expr> = (0..n)
+                    .filter(|i| free >> i & 1 == 0)
+                    .map(|i| {
+                        let var = Expr::var(&self.inputs[i]);
+                        if value >> i & 1 == 1 { var } else { !var }
+                    })
+                    .collect();
+                tree(literals, |a, b| a & b, true)
+            })
+            .collect();
+        return tree(terms, |a, b| a | b, false);
+    }
+
+    // 正極性の Reed-Muller 展開 (ドントケアは 0 とする)
+    fn reed_muller(&self, on: &BTreeSet<u32>) -> Expr {
+        let n = self.inputs.len();
+        let mut coef: Vec<bool> = (0..1u32 << n).map(|m| on.contains(&m)).collect();
+        for i in 0..n {
+            for m in 0..coef.len() {
+                if m >> i & 1 == 1 {
+                    coef[m] ^= coef[m ^ (1 << i)];
+                }
+            }
+        }
+        let terms: Vec<Expr> = (0..coef.len())
+            .filter(|m| coef[*m])
+            .map(|m| {
+                let vars: Vec<Expr> = (0..n)
+                    .filter(|i| m >> i & 1 == 1)
+                    .map(|i| Expr::var(&self.inputs[i]))
+                    .collect();
+                tree(vars, |a, b| a & b, true)
+            })
+            .collect();
+        return tree(terms, |a, b| a ^ b, false);
+    }
+}
+
+// "1-0" のようなキューブの (値, どちらでもよいビット)
+fn cube(s: &str) -> Cube {
+    let mut value = 0;
+    let mut free = 0;
+    for (i, c) in s.chars().enumerate() {
+        match c {
+            '1' => value |= 1 << i,
+            '-' => free |= 1 << i,
+            _ => {}
+        }
+    }
+    return (value, free);
+}
+
+// 最小項から主項をすべて求める
+fn primes(n: usize, minterms: &[u32]) -> Vec<Cube> {
+    let mut current: BTreeSet<Cube> = minterms.iter().map(|m| (*m, 0)).collect();
+    let mut res = Vec::new();
+    while !current.is_empty() {
+        let mut next = BTreeSet::new();
+        let mut merged: HashSet<Cube> = HashSet::new();
+        for &(value, free) in current.iter() {
+            for i in 0..n {
+                let bit = 1 << i;
+                // 値が 0 の側から 1 ビットだけ異なる相手を探す
+                if (value | free) & bit != 0 {
+                    continue;
+                }
+                if current.contains(&(value | bit, free)) {
+                    next.insert((value, free | bit));
+                    merged.insert((value, free));
+                    merged.insert((value | bit, free));
+                }
+            }
+        }
+        res.extend(current.iter().filter(|c| !merged.contains(c)));
+        current = next;
+    }
+    return res;
+}
+
+// 2 入力の演算の平衡木 (空なら定数 empty)
+fn tree(mut items: Vec<Expr>, op: fn(Expr, Expr) -> Expr, empty: bool) -> Expr {
+    return match items.len() {
+        0 => Expr::Const(empty),
+        1 => items.pop().unwrap(),
+        n => {
+            let right = items.split_off(n / 2);
+            op(tree(items, op, empty), tree(right, op, empty))
+        }
+    };
+}
+
+// 式を単独で構築したときのゲート数
+fn cost(expr: &Expr, names: &[&str]) -> usize {
+    let mut network = LogicNetwork::new();
+    Lowering::new(&mut network, names).output(expr);
+    network.sweep();
+    return network.cells.len();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TruthTable;
+    use crate::circuit::Circuit;
+    use crate::equivalence::EquivalenceChecker;
+    use crate::expr::Expr;
+
+    type Table = Circuit<3, 0, 5, 0>;
+
+    const OUTPUTS: [&str; 5] = ["p", "one", "zero", "nb", "x"];
+
+    // p = a, one = 1, zero = 0, nb = !b, x = a ^ b
+    fn table() -> TruthTable {
+        return TruthTable::new(&["a", "b"], &OUTPUTS)
+            .row("1-", "10000")
+            .row("--", "01000")
+            .row("-0", "00010")
+            .row("10", "00001")
+            .row("01", "00001");
+    }
+
+    fn synthesized(table: &TruthTable) -> Table {
+        let (mut c, [a, b, clk], [], outputs, []) =
+            Circuit::create(["a", "b", "clk"], [], OUTPUTS, [], "table");
+        let qs: [_; 5] = c.truth_table(table, [a, b], clk);
+        c.unify_array(qs, outputs);
+        return c;
+    }
+
+    fn reference() -> Table {
+        let (mut c, [a, b, clk], [], outputs, []) =
+            Circuit::create(["a", "b", "clk"], [], OUTPUTS, [], "reference");
+        let exprs = ["a", "1", "0", "!b", "a ^ b"].map(|e| Expr::parse(e).unwrap());
+        let qs = c.exprs(exprs.each_ref(), ["a", "b"], [a, b], clk);
+        c.unify_array(qs, outputs);
+        return c;
+    }
+
+    #[test]
+    fn pass_through_and_constant_columns() {
+        let exprs = table().exprs();
+        assert_eq!(exprs[0], Expr::var("a"));
+        assert_eq!(exprs[1], Expr::constant(true));
+        assert_eq!(exprs[2], Expr::constant(false));
+        let res = EquivalenceChecker::new().check(&synthesized(&table()), &reference());
+        assert!(res.is_ok(), "{}", res.unwrap_err());
+    }
+
+    #[test]
+    fn pla() {
+        let src = "\
+            .i 2\n.o 5\n.ilb a b\n.ob p one zero nb x\n.p 5\n\
+            1- 10000\n-- 01000\n-0 00010\n10 00001\n01 00001\n.e\n";
+        let table = TruthTable::parse_pla(src).unwrap();
+        assert_eq!(table.inputs(), ["a", "b"]);
+        assert_eq!(table.outputs(), OUTPUTS);
+        let res = EquivalenceChecker::new().check(&synthesized(&table), &reference());
+        assert!(res.is_ok(), "{}", res.unwrap_err());
+    }
+
+    #[test]
+    fn full_adder() {
+        let table = TruthTable::parse_pla(
+            ".i 3\n.o 2\n001 10\n010 10\n100 10\n111 11\n-11 01\n1-1 01\n11- 01\n.e\n",
+        )
+        .unwrap();
+        let build = |exprs: Option<[&str; 2]>| -> Circuit<4, 0, 2, 0> {
+            let (mut c, [a, b, cin, clk], [], outputs, []) =
+                Circuit::create(["x0", "x1", "x2", "clk"], [], ["f0", "f1"], [], "adder");
+            let qs = match exprs {
+                Some(exprs) => {
+                    let exprs = exprs.map(|e| Expr::parse(e).unwrap());
+                    c.exprs(exprs.each_ref(), ["x0", "x1", "x2"], [a, b, cin], clk)
+                }
+                None => c.truth_table(&table, [a, b, cin], clk),
+            };
+            c.unify_array(qs, outputs);
+            return c;
+        };
+        let reference = build(Some(["x0 ^ x1 ^ x2", "x0 & x1 | x2 & (x0 ^ x1)"]));
+        let res = EquivalenceChecker::new().check(&build(None), &reference);
+        assert!(res.is_ok(), "{}", res.unwrap_err());
+    }
+
+    #[test]
+    fn pla_errors() {
+        for src in [
+            "11 1\n",
+            ".i 2\n.o 1\n.ilb a\n11 1\n",
+            ".i 2\n.o 1\n11 2\n",
+            ".i 2\n.o 1\n1x 1\n",
+            ".i 2\n.o 1\n.type fr\n",
+            ".i 2\n.o 1\n11 1\n.i 3\n",
+        ] {
+            assert!(TruthTable::parse_pla(src).is_err(), "{:?}", src);
+        }
+    }
+}